@@ -0,0 +1,20 @@
+/* Bootloader memory layout.
+ * Flash map of the STM32F103C8 (64K, 1K pages):
+ *   0x08000000  7K   bootloader (this image)
+ *   0x08001C00  1K   application image descriptor, written by the bootloader
+ *   0x08002000  55K  application, see ../firmware/memory.x
+ *   0x0800FC00  1K   application config block
+ *
+ * The last 16 bytes of RAM are not initialized by either image. The
+ * application uses them to ask the bootloader to stay resident after a reset,
+ * and we use them to pass on the reset flags we saw.
+ * The 128 bytes below them hold the fault log of the application, which
+ * must survive a pass through the bootloader, so we stay out of them too.
+ */
+MEMORY
+{
+  FLASH (rx)      : ORIGIN = 0x08000000, LENGTH = 7K
+  RAM (xrw)       : ORIGIN = 0x20000000, LENGTH = 20K - 16 - 128
+  FAULT_LOG (rw)  : ORIGIN = 0x20004F70, LENGTH = 128
+  BOOT_SHARED (rw): ORIGIN = 0x20004FF0, LENGTH = 16
+}
@@ -0,0 +1,165 @@
+//! Application image layout and verification.
+
+use stm32f1xx_hal::flash;
+
+use bmc_protocol::crc32;
+
+/// Address the application is linked at
+pub const APP_BASE: u32 = 0x0800_2000;
+
+/// Offset of the application from the start of flash
+const APP_OFFSET: u32 = APP_BASE - FLASH_BASE;
+
+/// Largest image that fits between the bootloader and the config page
+pub const APP_MAX_LEN: u32 = 55 * 1024;
+
+const FLASH_BASE: u32 = 0x0800_0000;
+
+/// Offset of the image descriptor page from the start of flash
+const DESCRIPTOR_OFFSET: u32 = 7 * 1024;
+
+const PAGE_SIZE: u32 = 1024;
+
+/// Descriptor magic of an image that passed its crc check
+const MAGIC_VALID: u32 = 0x4D49_4D42; // "BMIM"
+
+/// Descriptor magic written before an update starts erasing the application.
+/// If we reset during an update, this keeps us from booting half an image
+const MAGIC_IN_PROGRESS: u32 = 0x5055_4D42; // "BMUP"
+
+const RAM_START: u32 = 0x2000_0000;
+const RAM_END: u32 = 0x2000_5000;
+
+/// Result of checking the application region at boot
+#[derive(defmt::Format, PartialEq, Eq)]
+pub enum ImageState {
+    /// Descriptor is valid and the crc matches
+    Valid,
+    /// Descriptor page is erased but the vector table looks sane.
+    /// This happens when the application was flashed with a debug probe
+    Unverified,
+    /// An update was interrupted, or the crc does not match
+    Invalid,
+}
+
+#[derive(defmt::Format)]
+pub enum ImageError {
+    TooLarge,
+    Flash,
+}
+
+impl From<flash::Error> for ImageError {
+    fn from(_: flash::Error) -> Self {
+        ImageError::Flash
+    }
+}
+
+fn read_word(addr: u32) -> u32 {
+    unsafe { core::ptr::read_volatile(addr as *const u32) }
+}
+
+/// The first `len` bytes of the application region
+fn app_bytes(len: u32) -> &'static [u8] {
+    unsafe { core::slice::from_raw_parts(APP_BASE as *const u8, len as usize) }
+}
+
+/// Checks whether the application can be booted.
+/// This only reads flash, so it is safe to call straight out of reset
+pub fn check() -> ImageState {
+    let descriptor = FLASH_BASE + DESCRIPTOR_OFFSET;
+    let magic = read_word(descriptor);
+
+    match magic {
+        MAGIC_VALID => {
+            let len = read_word(descriptor + 4);
+            let crc = read_word(descriptor + 8);
+            if len > APP_MAX_LEN {
+                return ImageState::Invalid;
+            }
+            if crc32(app_bytes(len)) == crc {
+                ImageState::Valid
+            } else {
+                ImageState::Invalid
+            }
+        }
+        0xFFFF_FFFF => {
+            // no descriptor, so all we can do is check the initial stack pointer
+            // and reset vector point somewhere reasonable
+            let sp = read_word(APP_BASE);
+            let reset = read_word(APP_BASE + 4);
+            let sp_ok = sp > RAM_START && sp <= RAM_END;
+            let reset_ok = reset > APP_BASE && reset < APP_BASE + APP_MAX_LEN;
+            if sp_ok && reset_ok {
+                ImageState::Unverified
+            } else {
+                ImageState::Invalid
+            }
+        }
+        _ => ImageState::Invalid,
+    }
+}
+
+/// Marks an update as in progress and erases enough pages for an image of `len` bytes
+pub fn begin_update(writer: &mut flash::FlashWriter, len: u32) -> Result<(), ImageError> {
+    if len == 0 || len > APP_MAX_LEN {
+        return Err(ImageError::TooLarge);
+    }
+
+    writer.page_erase(DESCRIPTOR_OFFSET)?;
+    writer.write(DESCRIPTOR_OFFSET, &MAGIC_IN_PROGRESS.to_le_bytes())?;
+
+    let pages = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+    writer.erase(APP_OFFSET, (pages * PAGE_SIZE) as usize)?;
+    Ok(())
+}
+
+/// Writes one word of the image
+pub fn write_word(
+    writer: &mut flash::FlashWriter,
+    index: u16,
+    word: &[u8; 4],
+) -> Result<(), ImageError> {
+    let offset = index as u32 * 4;
+    if offset + 4 > APP_MAX_LEN {
+        return Err(ImageError::TooLarge);
+    }
+    writer.write(APP_OFFSET + offset, word)?;
+    Ok(())
+}
+
+/// Checks the crc of the first `len` bytes of the application region.
+/// If it matches `expected`, a valid descriptor is written
+pub fn finish_update(
+    writer: &mut flash::FlashWriter,
+    len: u32,
+    expected: u32,
+) -> Result<bool, ImageError> {
+    if len > APP_MAX_LEN {
+        return Err(ImageError::TooLarge);
+    }
+
+    let crc = crc32(app_bytes(len));
+    if crc != expected {
+        return Ok(false);
+    }
+
+    let mut descriptor = [0_u8; 12];
+    descriptor[0..4].copy_from_slice(&MAGIC_VALID.to_le_bytes());
+    descriptor[4..8].copy_from_slice(&len.to_le_bytes());
+    descriptor[8..12].copy_from_slice(&crc.to_le_bytes());
+
+    writer.page_erase(DESCRIPTOR_OFFSET)?;
+    writer.write(DESCRIPTOR_OFFSET, &descriptor)?;
+    Ok(true)
+}
+
+/// Points the vector table at the application and jumps to its reset handler.
+///
+/// # Safety
+/// This must be called straight out of reset, before any peripherals are configured,
+/// since the application expects to find the chip in its reset state.
+pub unsafe fn jump_to_app() -> ! {
+    let scb = &*cortex_m::peripheral::SCB::ptr();
+    scb.vtor.write(APP_BASE);
+    cortex_m::asm::bootload(APP_BASE as *const u32)
+}
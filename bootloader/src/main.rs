@@ -0,0 +1,277 @@
+//! CAN bootloader.
+//!
+//! After a reset we listen on the bus for a short window. If nobody asks us to stay,
+//! and the application image checks out, we reset again and jump straight into the
+//! application before touching any peripherals. See `protocol.rs` for the frames.
+#![no_std]
+#![no_main]
+
+use embedded_hal::digital::v2::InputPin;
+use stm32f1xx_hal::prelude::*;
+
+use cortex_m::peripheral::{DWT, SCB};
+use cortex_m_rt::entry;
+
+use stm32f1xx_hal::{can, flash, gpio, pac};
+
+use bxcan::{filter::Mask32, ExtendedId};
+
+use bmc_protocol::{BootCommand, BootStatus, IdFields, DEVICE_MASK};
+
+/// this makes sure that the rtt logger is linked into the binary
+use defmt_rtt as _;
+
+mod image;
+mod protocol;
+
+use image::ImageState;
+
+/// Clock speed in mhz of the HSE
+const HSE_CLOCK_MHZ: u32 = 8;
+
+/// System clock speed after scalars in mhz
+const SYS_CLOCK_MHZ: u32 = 72;
+
+/// CAN bit timing, must match the application (1 Mbs)
+const CAN_CONFIG: u32 = 0x001e0001;
+
+/// How long we listen for a stay request after reset, in cycles (200ms)
+const BOOT_WINDOW: u32 = SYS_CLOCK_MHZ * 1_000_000 / 5;
+
+/// Address of the word shared with the application.
+/// This sits in the `BOOT_SHARED` region of memory.x, which neither image initializes
+const BOOT_FLAG_ADDR: u32 = 0x2000_4FF0;
+
+/// Written by the application before a reset to keep us in the bootloader
+const BOOT_FLAG_STAY: u32 = 0x5941_5453; // "STAY"
+
+/// Written by us before a reset to jump straight into the application
+const BOOT_FLAG_JUMP: u32 = 0x504D_554A; // "JUMP"
+
+/// Address of the reset flags we saw, the word after the boot flag.
+/// Our own reset into the application would hide them, so we keep them for it
+const RESET_FLAGS_ADDR: u32 = 0x2000_4FF4;
+
+/// Reads and clears the boot flag
+fn take_boot_flag() -> u32 {
+    unsafe {
+        let flag = core::ptr::read_volatile(BOOT_FLAG_ADDR as *const u32);
+        core::ptr::write_volatile(BOOT_FLAG_ADDR as *mut u32, 0);
+        flag
+    }
+}
+
+/// Keeps the reset flags of RCC_CSR for the application, unless we caused the reset ourselves,
+/// and clears them so the next reset starts clean
+fn take_reset_flags(flag: u32) {
+    unsafe {
+        let rcc = &*pac::RCC::ptr();
+        if flag != BOOT_FLAG_JUMP {
+            core::ptr::write_volatile(RESET_FLAGS_ADDR as *mut u32, rcc.csr.read().bits());
+        }
+        rcc.csr.modify(|_, w| w.rmvf().set_bit());
+    }
+}
+
+/// Resets the chip so the application starts with every peripheral in its reset state
+fn reset_into_app() -> ! {
+    unsafe { core::ptr::write_volatile(BOOT_FLAG_ADDR as *mut u32, BOOT_FLAG_JUMP) };
+    SCB::sys_reset()
+}
+
+/// State of an update in progress
+struct Update {
+    len: u32,
+    next_word: u16,
+}
+
+#[entry]
+fn main() -> ! {
+    let flag = take_boot_flag();
+    take_reset_flags(flag);
+
+    if flag == BOOT_FLAG_JUMP && image::check() != ImageState::Invalid {
+        unsafe { image::jump_to_app() }
+    }
+
+    let image_state = image::check();
+    defmt::info!("Application image: {:?}", image_state);
+
+    // stay resident if the application asked us to, or there is nothing to boot
+    let mut stay = flag == BOOT_FLAG_STAY || image_state == ImageState::Invalid;
+
+    let mut peripherals = cortex_m::Peripherals::take().unwrap();
+    let device = pac::Peripherals::take().unwrap();
+
+    let mut flash = device.FLASH.constrain();
+    let mut rcc = device.RCC.constrain();
+    let mut afio = device.AFIO.constrain(&mut rcc.apb2);
+
+    // same clock setup as the application, so the CAN bit timing matches
+    let _clocks = rcc
+        .cfgr
+        .use_hse(HSE_CLOCK_MHZ.mhz())
+        .sysclk(SYS_CLOCK_MHZ.mhz())
+        .hclk(SYS_CLOCK_MHZ.mhz())
+        .pclk1((SYS_CLOCK_MHZ / 2).mhz())
+        .pclk2(SYS_CLOCK_MHZ.mhz())
+        .freeze(&mut flash.acr);
+
+    let gpioa = device.GPIOA.split(&mut rcc.apb2);
+    let mut gpiob = device.GPIOB.split(&mut rcc.apb2);
+
+    // get the system can id from the dip switches.
+    // The pin order has to match `init` in the application
+    let can_id = {
+        let (_, pb3, pb4) = afio.mapr.disable_jtag(gpioa.pa15, gpiob.pb3, gpiob.pb4);
+
+        let pins: [gpio::Pxx<gpio::Input<gpio::PullDown>>; 8] = [
+            gpiob.pb0.into_pull_down_input(&mut gpiob.crl).downgrade(),
+            gpiob.pb1.into_pull_down_input(&mut gpiob.crl).downgrade(),
+            gpiob.pb2.into_pull_down_input(&mut gpiob.crl).downgrade(),
+            gpiob.pb7.into_pull_down_input(&mut gpiob.crl).downgrade(),
+            gpiob.pb6.into_pull_down_input(&mut gpiob.crl).downgrade(),
+            gpiob.pb5.into_pull_down_input(&mut gpiob.crl).downgrade(),
+            pb4.into_pull_down_input(&mut gpiob.crl).downgrade(),
+            pb3.into_pull_down_input(&mut gpiob.crl).downgrade(),
+        ];
+
+        let mut id = 0_u8;
+        for (shift, pin) in pins.iter().enumerate() {
+            id |= (pin.is_high().unwrap() as u8) << shift;
+        }
+        id
+    };
+
+    defmt::info!("Can Id: {=u8}", can_id);
+
+    let mut can = {
+        let can = can::Can::new(device.CAN1, &mut rcc.apb1, device.USB);
+
+        let tx_pin = gpiob.pb9.into_alternate_push_pull(&mut gpiob.crh);
+        let rx_pin = gpiob.pb8.into_floating_input(&mut gpiob.crh);
+        can.assign_pins((tx_pin, rx_pin), &mut afio.mapr);
+
+        let mut can = bxcan::Can::new(can);
+        can.configure(|config| {
+            config.set_bit_timing(CAN_CONFIG);
+            config.set_silent(false);
+            config.set_loopback(false);
+        });
+
+        // only accept frames addressed to our id, like the application.
+        // `protocol::parse` drops the ones that are not bootloader commands
+        let can_id_mask = ExtendedId::new(DEVICE_MASK).unwrap();
+        let device_id = ExtendedId::new(IdFields::new(0, 0, can_id).raw()).unwrap();
+        can.modify_filters()
+            .enable_bank(0, Mask32::frames_with_ext_id(device_id, can_id_mask));
+
+        nb::block!(can.enable()).unwrap();
+        can
+    };
+
+    let mut writer = flash.writer(flash::SectorSize::Sz1K, flash::FlashSize::Sz64K);
+
+    // cycle counter for the boot window
+    peripherals.DCB.enable_trace();
+    DWT::unlock();
+    peripherals.DWT.enable_cycle_counter();
+    let start = DWT::cycle_count();
+
+    let mut update: Option<Update> = None;
+
+    if stay {
+        let frame = protocol::status_frame(BootStatus::Ready(image_state as u8), can_id);
+        nb::block!(can.transmit(&frame)).unwrap();
+    }
+
+    loop {
+        if !stay && DWT::cycle_count().wrapping_sub(start) > BOOT_WINDOW {
+            defmt::info!("Booting application");
+            reset_into_app();
+        }
+
+        let frame = match can.receive() {
+            Ok(frame) => frame,
+            Err(nb::Error::WouldBlock) => continue,
+            Err(nb::Error::Other(_)) => {
+                defmt::warn!("Can rx overrun");
+                continue;
+            }
+        };
+
+        let command = match protocol::parse(&frame) {
+            Some(command) => command,
+            None => continue,
+        };
+        defmt::debug!("Command: {:?}", command);
+
+        // any bootloader command means somebody is talking to us, so dont boot
+        stay = true;
+
+        let status = match command {
+            BootCommand::Stay => BootStatus::Ready(image::check() as u8),
+            BootCommand::BeginUpdate { len } => match image::begin_update(&mut writer, len) {
+                Ok(()) => {
+                    update = Some(Update { len, next_word: 0 });
+                    BootStatus::Erased
+                }
+                Err(e) => {
+                    defmt::error!("Could not start update: {:?}", e);
+                    update = None;
+                    BootStatus::Error
+                }
+            },
+            BootCommand::WriteData { index, word } => match update.as_mut() {
+                // words have to arrive in order. Anything else is answered with
+                // the index we want, so the host can resend from there
+                Some(u) if index == u.next_word => {
+                    match image::write_word(&mut writer, index, &word) {
+                        Ok(()) => {
+                            u.next_word += 1;
+                            BootStatus::Ack(u.next_word)
+                        }
+                        Err(e) => {
+                            defmt::error!("Could not write word {=u16}: {:?}", index, e);
+                            BootStatus::Error
+                        }
+                    }
+                }
+                Some(u) => BootStatus::Ack(u.next_word),
+                None => BootStatus::Error,
+            },
+            BootCommand::FinishUpdate { crc } => match update.take() {
+                Some(u) => match image::finish_update(&mut writer, u.len, crc) {
+                    Ok(true) => BootStatus::ImageValid,
+                    Ok(false) => BootStatus::ImageInvalid,
+                    Err(e) => {
+                        defmt::error!("Could not finish update: {:?}", e);
+                        BootStatus::Error
+                    }
+                },
+                None => BootStatus::Error,
+            },
+            BootCommand::Boot => {
+                if image::check() != ImageState::Invalid {
+                    reset_into_app();
+                }
+                BootStatus::Error
+            }
+        };
+
+        nb::block!(can.transmit(&protocol::status_frame(status, can_id))).unwrap();
+    }
+}
+
+#[panic_handler]
+#[inline(never)]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    // the safest thing to do is start over, the flags make sure we stay resident
+    unsafe { core::ptr::write_volatile(BOOT_FLAG_ADDR as *mut u32, BOOT_FLAG_STAY) };
+    SCB::sys_reset()
+}
+
+#[defmt::panic_handler]
+fn defmt_panic() -> ! {
+    SCB::sys_reset()
+}
@@ -0,0 +1,32 @@
+//! Glue between `bxcan` frames and the bootloader frames of `bmc_protocol`.
+//!
+//! The frames themselves, and their ids, are documented with `bmc_protocol::BootCommand`.
+//! Like the application we use the 29 bit ids of protocol version 4, so a host needs
+//! only one id scheme for a board.
+
+use core::convert::TryFrom;
+
+use bxcan::{Data, ExtendedId, Frame, Id};
+
+use bmc_protocol::{BootCommand, BootStatus, CanFrame, IntoWithId};
+
+/// Parses a frame that passed our acceptance filter.
+/// Anything that isnt a bootloader command is ignored
+pub fn parse(frame: &Frame) -> Option<BootCommand> {
+    let id = match frame.id() {
+        Id::Extended(id) => bmc_protocol::Id::Extended(id.as_raw()),
+        Id::Standard(_) => return None,
+    };
+    let frame = CanFrame::new_data(id, frame.data()?)?;
+    BootCommand::try_from(frame).ok()
+}
+
+/// Builds the frame that answers a command, from the board `board_id`
+pub fn status_frame(status: BootStatus, board_id: u8) -> Frame {
+    let frame: CanFrame = status.into_with_id(bmc_protocol::device_id(board_id));
+    let id = match frame.id() {
+        bmc_protocol::Id::Extended(raw) => ExtendedId::new(raw).unwrap(),
+        bmc_protocol::Id::Standard(_) => unreachable!(),
+    };
+    Frame::new_data(id, Data::new(frame.data()).unwrap())
+}
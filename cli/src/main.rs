@@ -0,0 +1,495 @@
+//! `bmc-cli`: command and monitor motor controllers over SocketCAN.
+//!
+//! Every frame is encoded and decoded with `bmc_protocol`, so the byte layout
+//! always matches the firmware.
+//!
+//! To try it without hardware, create a virtual bus:
+//!
+//! ```text
+//! sudo ip link add dev vcan0 type vcan
+//! sudo ip link set up vcan0
+//! bmc-cli -i vcan0 watch
+//! ```
+
+use std::convert::TryFrom;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use structopt::StructOpt;
+
+use bmc_protocol::param::{ParamInfo, ParamType, PARAMS};
+use bmc_protocol::{
+    CanFrame, IncomingFrame, IntoWithId, OutgoingFrame, ParamId, ParamValue, BROADCAST,
+    FAULT_LOG_LEN, GROUP_SLOTS, PROTOCOL_VERSION,
+};
+use bmc_socketcan::CanSocket;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "bmc-cli",
+    about = "Command and monitor motor controllers over CAN"
+)]
+struct Opt {
+    /// SocketCAN interface to use
+    #[structopt(short, long, default_value = "can0")]
+    interface: String,
+
+    /// Id of the board to talk to, as set by its dip switches.
+    /// Accepts decimal or hex with a `0x` prefix, or `all` to broadcast to every board
+    #[structopt(short, long, parse(try_from_str = parse_board_id))]
+    board: Option<u8>,
+
+    /// Send commands without first checking that the board speaks our protocol version
+    #[structopt(long)]
+    skip_version_check: bool,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Set the duty cycle setpoint, from -1.0 to 1.0
+    Setpoint {
+        #[structopt(allow_hyphen_values = true)]
+        duty: f32,
+    },
+    /// Set the output in volts. The board scales it by the bus voltage it measures
+    Voltage {
+        #[structopt(allow_hyphen_values = true)]
+        volts: f32,
+    },
+    /// Print the value of a parameter, like `current_limit`
+    Get { name: String },
+    /// Set a parameter and check that the board took the value.
+    /// Enumerated parameters also accept the name of a value, like `idle_mode brake`.
+    /// Changes are lost on reset unless followed by `save-config`
+    Set {
+        name: String,
+        #[structopt(allow_hyphen_values = true)]
+        value: String,
+    },
+    /// Print every parameter of a board
+    Config,
+    /// Write the current parameters to flash, so they survive a reset
+    SaveConfig,
+    /// Send heartbeats until interrupted, so the board keeps its output enabled
+    Heartbeat {
+        /// Time between heartbeats in milliseconds
+        #[structopt(short, long, default_value = "100")]
+        period_ms: u64,
+    },
+    /// Stop the motor
+    Stop,
+    /// Clear the faults that are no longer present, which lets a board drive its motor again
+    ClearFaults,
+    /// Print the fault history of a board, newest first.
+    /// The history survives resets but not power loss
+    FaultLog,
+    /// Empty the fault history of a board
+    ClearFaultLog,
+    /// Set the duty cycles of every board in a group with one frame, from -1.0 to 1.0.
+    /// The first value is for slot 0, missing values are 0.
+    /// Does not need a board id, and does not check versions
+    GroupSetpoint {
+        #[structopt(short, long)]
+        group: u8,
+        #[structopt(allow_hyphen_values = true)]
+        duties: Vec<f32>,
+    },
+    /// Print the protocol and firmware version of a board
+    Version,
+    /// Print every frame sent by the boards on the bus.
+    /// If a board id is given, only frames from that board are shown
+    Watch,
+}
+
+fn parse_board_id(s: &str) -> Result<u8> {
+    if s == "all" {
+        return Ok(BROADCAST);
+    }
+    let id = if let Some(hex) = s.strip_prefix("0x") {
+        u8::from_str_radix(hex, 16)
+    } else {
+        s.parse()
+    };
+    id.with_context(|| format!("invalid board id '{}'", s))
+}
+
+fn find_param(name: &str) -> Result<&'static ParamInfo> {
+    match PARAMS.iter().find(|p| p.name == name) {
+        Some(param) => Ok(param),
+        None => {
+            let names: Vec<_> = PARAMS.iter().map(|p| p.name).collect();
+            bail!(
+                "unknown parameter '{}', expected one of: {}",
+                name,
+                names.join(", ")
+            )
+        }
+    }
+}
+
+/// Parses a value for `param`, by value name or as a number of its type
+fn parse_param_value(param: &ParamInfo, s: &str) -> Result<ParamValue> {
+    let value = if let Some(&(raw, _)) = param.values.iter().find(|&&(_, name)| name == s) {
+        ParamValue::from_raw(param.ty, raw)
+    } else {
+        match param.ty {
+            ParamType::Bool => match s {
+                "true" | "1" => Some(ParamValue::Bool(true)),
+                "false" | "0" => Some(ParamValue::Bool(false)),
+                _ => None,
+            },
+            ParamType::U8 => s.parse().ok().map(ParamValue::U8),
+            ParamType::U16 => s.parse().ok().map(ParamValue::U16),
+            ParamType::F32 => s.parse().ok().map(ParamValue::F32),
+        }
+    };
+    let value = match value {
+        Some(value) => value,
+        None => bail!("'{}' is not a valid {:?} for {}", s, param.ty, param.name),
+    };
+    if param.check(value).is_err() {
+        bail!(
+            "{} must be between {} and {}, got {}",
+            param.name,
+            param.min,
+            param.max,
+            s
+        );
+    }
+    Ok(value)
+}
+
+/// Formats a value with its name or unit, if it has one
+fn format_param_value(param: &ParamInfo, value: ParamValue) -> String {
+    let number = match value {
+        ParamValue::Bool(v) => v.to_string(),
+        ParamValue::U8(v) => v.to_string(),
+        ParamValue::U16(v) => v.to_string(),
+        ParamValue::F32(v) => v.to_string(),
+    };
+    if let Some(&(_, name)) = param.values.iter().find(|&&(raw, _)| raw == value.to_raw()) {
+        format!("{} ({})", name, number)
+    } else if param.unit.is_empty() {
+        number
+    } else {
+        format!("{} {}", number, param.unit)
+    }
+}
+
+/// Converts a duty cycle from -1.0 to 1.0 into the setpoint the firmware expects
+fn duty_to_setpoint(duty: f32) -> Result<i16> {
+    if !(-1.0..=1.0).contains(&duty) {
+        bail!("duty cycle must be between -1.0 and 1.0, got {}", duty);
+    }
+    Ok((duty * i16::MAX as f32) as i16)
+}
+
+fn send(socket: &CanSocket, board: u8, frame: IncomingFrame) -> Result<()> {
+    let frame: CanFrame = frame.into_with_id(bmc_protocol::device_id(board));
+    socket.write(&frame).context("could not send frame")
+}
+
+/// How long a board gets to answer a query
+const ANSWER_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Sends `frame` and collects the answers `pick` accepts, with the board that sent them.
+/// A frame sent to `BROADCAST` collects an answer from every board, otherwise this
+/// stops at the first one
+fn request<T>(
+    socket: &CanSocket,
+    board: u8,
+    frame: IncomingFrame,
+    mut pick: impl FnMut(OutgoingFrame) -> Option<T>,
+) -> Result<Vec<(u8, T)>> {
+    socket.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+    send(socket, board, frame)?;
+
+    let deadline = Instant::now() + ANSWER_TIMEOUT;
+    let mut answers = Vec::new();
+    while Instant::now() <= deadline {
+        let frame = match socket.read() {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(e) => return Err(e).context("could not read frame"),
+        };
+        let from = bmc_protocol::board_id(frame.id());
+        if board != BROADCAST && from != board {
+            continue;
+        }
+        if let Some(answer) = OutgoingFrame::try_from(frame).ok().and_then(&mut pick) {
+            answers.push((from, answer));
+            if board != BROADCAST {
+                break;
+            }
+        }
+    }
+    socket.set_read_timeout(None)?;
+    Ok(answers)
+}
+
+/// Protocol and firmware version of a board that answered a version query
+struct Version {
+    board: u8,
+    protocol: u8,
+    firmware: [u8; 3],
+}
+
+/// Asks a board for its version. A query sent to `BROADCAST` collects the answer of every board
+fn query_version(socket: &CanSocket, board: u8) -> Result<Vec<Version>> {
+    let answers = request(
+        socket,
+        board,
+        IncomingFrame::QueryVersion,
+        |frame| match frame {
+            OutgoingFrame::Version { protocol, firmware } => Some((protocol, firmware)),
+            _ => None,
+        },
+    )?;
+    let answers: Vec<_> = answers
+        .into_iter()
+        .map(|(board, (protocol, firmware))| Version {
+            board,
+            protocol,
+            firmware,
+        })
+        .collect();
+
+    // boards older than version 3 use 11 bit ids, so they never see the query
+    if answers.is_empty() {
+        if board == BROADCAST {
+            bail!(
+                "no board answered a version query. \
+                 They are either offline or speak a protocol older than version 3"
+            );
+        }
+        bail!(
+            "board {:#04x} did not answer a version query. \
+             It is either offline or speaks a protocol older than version 3",
+            board
+        );
+    }
+    Ok(answers)
+}
+
+/// Refuses to continue unless every board that answers speaks `PROTOCOL_VERSION`.
+/// For a broadcast, boards that do not answer at all will still get the command
+fn check_version(socket: &CanSocket, board: u8) -> Result<()> {
+    for version in query_version(socket, board)? {
+        if version.protocol != PROTOCOL_VERSION {
+            bail!(
+                "board {:#04x} speaks protocol version {}, but this tool speaks version {}",
+                version.board,
+                version.protocol,
+                PROTOCOL_VERSION
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Sends `frame` and returns the value of `id` every board answers with
+fn request_param(
+    socket: &CanSocket,
+    board: u8,
+    frame: IncomingFrame,
+    id: ParamId,
+) -> Result<Vec<(u8, ParamValue)>> {
+    let answers = request(socket, board, frame, |frame| match frame {
+        OutgoingFrame::ParamValue(answered, value) if answered == id => Some(value),
+        _ => None,
+    })?;
+    if answers.is_empty() {
+        bail!("no answer for parameter {}", id.info().name);
+    }
+    Ok(answers)
+}
+
+fn get_param(socket: &CanSocket, board: u8, name: &str) -> Result<()> {
+    let param = find_param(name)?;
+    for (from, value) in request_param(socket, board, IncomingFrame::GetParam(param.id), param.id)?
+    {
+        println!(
+            "[{:#04x}] {} = {}",
+            from,
+            param.name,
+            format_param_value(param, value)
+        );
+    }
+    Ok(())
+}
+
+/// Sets a parameter and checks the value every board echoes back.
+/// A board that rejects the value echoes the one it kept
+fn set_param(socket: &CanSocket, board: u8, name: &str, value: &str) -> Result<()> {
+    let param = find_param(name)?;
+    let value = parse_param_value(param, value)?;
+    let frame = IncomingFrame::SetParam(param.id, value);
+    for (from, echoed) in request_param(socket, board, frame, param.id)? {
+        if echoed != value {
+            bail!(
+                "board {:#04x} rejected {}, it is still {}",
+                from,
+                param.name,
+                format_param_value(param, echoed)
+            );
+        }
+    }
+    Ok(())
+}
+
+fn print_config(socket: &CanSocket, board: u8) -> Result<()> {
+    if board == BROADCAST {
+        bail!("config needs the id of a single board");
+    }
+    for param in PARAMS {
+        let answers = request_param(socket, board, IncomingFrame::GetParam(param.id), param.id)?;
+        for (_, value) in answers {
+            println!("{} = {}", param.name, format_param_value(param, value));
+        }
+    }
+    Ok(())
+}
+
+fn print_fault_log(socket: &CanSocket, board: u8) -> Result<()> {
+    if board == BROADCAST {
+        bail!("fault-log needs the id of a single board");
+    }
+    for index in 0..FAULT_LOG_LEN as u8 {
+        let answers = request(
+            socket,
+            board,
+            IncomingFrame::GetFaultLog(index),
+            |frame| match frame {
+                OutgoingFrame::FaultLogEntry { index: i, record } if i == index => Some(record),
+                _ => None,
+            },
+        )?;
+        let record = match answers.into_iter().next() {
+            Some((_, Some(record))) => record,
+            Some((_, None)) => break,
+            None => bail!(
+                "board {:#04x} did not answer for fault record {}",
+                board,
+                index
+            ),
+        };
+        println!(
+            "{:>3} boot {:>5} {:>10.3}s {:?}",
+            index,
+            record.boot,
+            record.time_ms as f32 / 1000.0,
+            record.event
+        );
+    }
+    Ok(())
+}
+
+fn group_setpoint(socket: &CanSocket, group: u8, duties: &[f32]) -> Result<()> {
+    if duties.len() > GROUP_SLOTS {
+        bail!(
+            "a group has {} slots, got {} duty cycles",
+            GROUP_SLOTS,
+            duties.len()
+        );
+    }
+    let mut setpoints = [0; GROUP_SLOTS];
+    for (setpoint, &duty) in setpoints.iter_mut().zip(duties) {
+        *setpoint = duty_to_setpoint(duty)?;
+    }
+    // group frames are addressed with the group number in place of a board id
+    send(socket, group, IncomingFrame::GroupSetpoint(setpoints))
+}
+
+fn watch(socket: &CanSocket, board: Option<u8>) -> Result<()> {
+    let start = Instant::now();
+    loop {
+        let frame = socket.read().context("could not read frame")?;
+        let from = bmc_protocol::board_id(frame.id());
+        if board.is_some_and(|b| b != BROADCAST && b != from) {
+            continue;
+        }
+
+        let time = start.elapsed().as_secs_f32();
+        match OutgoingFrame::try_from(frame) {
+            Ok(decoded) => println!("{:>10.3} [{:#04x}] {:?}", time, from, decoded),
+            // commands from other hosts and frames from other devices are not errors
+            Err(_) => println!(
+                "{:>10.3} [{:#04x}] undecoded {:?} {:02x?}",
+                time,
+                from,
+                frame.id(),
+                frame.data()
+            ),
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::from_args();
+
+    let socket = CanSocket::open(&opt.interface)
+        .with_context(|| format!("could not open can interface '{}'", opt.interface))?;
+
+    let board = match (&opt.command, opt.board) {
+        (Command::Watch, board) => return watch(&socket, board),
+        (Command::GroupSetpoint { group, duties }, _) => {
+            return group_setpoint(&socket, *group, duties)
+        }
+        (_, Some(board)) => board,
+        (_, None) => bail!("this command needs a board id, pass one with --board"),
+    };
+
+    if let Command::Version = opt.command {
+        for version in query_version(&socket, board)? {
+            let [major, minor, patch] = version.firmware;
+            println!(
+                "[{:#04x}] protocol version {}, firmware {}.{}.{}",
+                version.board, version.protocol, major, minor, patch
+            );
+        }
+        return Ok(());
+    }
+
+    if !opt.skip_version_check {
+        check_version(&socket, board)?;
+    }
+
+    match opt.command {
+        Command::Setpoint { duty } => send(
+            &socket,
+            board,
+            IncomingFrame::Setpoint(duty_to_setpoint(duty)?),
+        ),
+        Command::Voltage { volts } => {
+            send(&socket, board, IncomingFrame::SetVoltageSetpoint(volts))
+        }
+        Command::Get { name } => get_param(&socket, board, &name),
+        Command::Set { name, value } => set_param(&socket, board, &name, &value),
+        Command::Config => print_config(&socket, board),
+        Command::SaveConfig => send(&socket, board, IncomingFrame::SaveConfig),
+        Command::Stop => send(&socket, board, IncomingFrame::Stop),
+        Command::ClearFaults => send(&socket, board, IncomingFrame::ClearFaults),
+        Command::FaultLog => print_fault_log(&socket, board),
+        Command::ClearFaultLog => send(&socket, board, IncomingFrame::ClearFaultLog),
+        Command::Heartbeat { period_ms } => {
+            let period = Duration::from_millis(period_ms);
+            loop {
+                match send(&socket, board, IncomingFrame::HeartBeat) {
+                    Ok(()) => {}
+                    // the tx queue fills up if nobody acks our frames, keep trying
+                    Err(e)
+                        if e.downcast_ref::<io::Error>().map(|e| e.raw_os_error())
+                            == Some(Some(libc::ENOBUFS)) => {}
+                    Err(e) => return Err(e),
+                }
+                thread::sleep(period);
+            }
+        }
+        Command::Version | Command::Watch | Command::GroupSetpoint { .. } => unreachable!(),
+    }
+}
@@ -0,0 +1,165 @@
+//! `bmc-dbc`: generates a DBC file from the layouts in `bmc_protocol::layout`.
+//!
+//! Every frame has its own api class and index, so one file describes both the
+//! commands sent to the boards and the frames they send.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use structopt::StructOpt;
+
+use bmc_protocol::layout::{Direction, Signal, SignalType, MESSAGES};
+
+#[derive(StructOpt)]
+#[structopt(name = "bmc-dbc", about = "Generate a DBC file for a range of boards")]
+struct Opt {
+    /// First board id to include
+    #[structopt(short, long, parse(try_from_str = parse_board_id))]
+    first: u8,
+
+    /// Last board id to include. Defaults to `first`
+    #[structopt(short, long, parse(try_from_str = parse_board_id))]
+    last: Option<u8>,
+
+    /// File to write. Defaults to stdout
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+fn parse_board_id(s: &str) -> Result<u8> {
+    let id = if let Some(hex) = s.strip_prefix("0x") {
+        u8::from_str_radix(hex, 16)
+    } else {
+        s.parse()
+    };
+    id.with_context(|| format!("invalid board id '{}'", s))
+}
+
+fn node(board: u8) -> String {
+    format!("BMC_{:02X}", board)
+}
+
+/// DBC files mark extended ids by setting bit 31
+const DBC_EXTENDED: u32 = 0x8000_0000;
+
+/// Names of the values of a signal: message id, signal name, and (value, name) pairs
+type ValueTable = (u32, String, Vec<(u32, String)>);
+
+/// Collects the lines that have to come after every message
+#[derive(Default)]
+struct Attributes {
+    float_signals: Vec<(u32, &'static str)>,
+    value_tables: Vec<ValueTable>,
+}
+
+fn write_signal(
+    out: &mut String,
+    attributes: &mut Attributes,
+    id: u32,
+    signal: &Signal,
+    receiver: &str,
+) {
+    let bits = signal.len as u32 * 8;
+    let (sign, min, max) = match signal.ty {
+        SignalType::Unsigned => ('+', 0.0, (2.0_f64).powi(bits as i32) - 1.0),
+        SignalType::Signed => {
+            let half = (2.0_f64).powi(bits as i32 - 1);
+            ('-', -half, half - 1.0)
+        }
+        SignalType::Float => ('-', 0.0, 0.0),
+    };
+    let _ = writeln!(
+        out,
+        " SG_ {} : {}|{}@1{} (1,0) [{}|{}] \"{}\" {}",
+        signal.name,
+        signal.offset as u32 * 8,
+        bits,
+        sign,
+        min,
+        max,
+        signal.unit,
+        receiver
+    );
+
+    if signal.ty == SignalType::Float {
+        attributes.float_signals.push((id, signal.name));
+    }
+    if !signal.values.is_empty() {
+        let values = signal
+            .values
+            .iter()
+            .map(|&(raw, name)| (raw, name.to_string()))
+            .collect();
+        attributes
+            .value_tables
+            .push((id, signal.name.to_string(), values));
+    }
+}
+
+fn generate(first: u8, last: u8) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "VERSION \"\"\n");
+    let _ = writeln!(
+        out,
+        "NS_ :\n\tCM_\n\tBA_DEF_\n\tBA_\n\tVAL_\n\tSIG_VALTYPE_\n"
+    );
+    let _ = writeln!(out, "BS_:\n");
+
+    let nodes: Vec<String> = (first..=last).map(node).collect();
+    let _ = writeln!(out, "BU_: Host {}\n", nodes.join(" "));
+
+    let mut attributes = Attributes::default();
+    for board in first..=last {
+        for message in MESSAGES {
+            let (transmitter, receiver) = match message.direction {
+                Direction::FromBoard => (node(board), "Host".to_string()),
+                Direction::ToBoard => ("Host".to_string(), node(board)),
+            };
+
+            let id = message.id(board) | DBC_EXTENDED;
+            let _ = writeln!(
+                out,
+                "BO_ {} {}_{:02X}: {} {}",
+                id, message.name, board, message.dlc, transmitter
+            );
+            for signal in message.signals {
+                write_signal(&mut out, &mut attributes, id, signal, &receiver);
+            }
+            let _ = writeln!(out);
+        }
+    }
+
+    for (id, signal, values) in attributes.value_tables {
+        let values: Vec<String> = values
+            .iter()
+            .map(|(raw, name)| format!("{} \"{}\"", raw, name))
+            .collect();
+        let _ = writeln!(out, "VAL_ {} {} {} ;", id, signal, values.join(" "));
+    }
+    for (id, signal) in attributes.float_signals {
+        let _ = writeln!(out, "SIG_VALTYPE_ {} {} : 1;", id, signal);
+    }
+
+    out
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::from_args();
+
+    let last = opt.last.unwrap_or(opt.first);
+    if last < opt.first {
+        bail!("last board id {} is before the first {}", last, opt.first);
+    }
+    let dbc = generate(opt.first, last);
+    match opt.output {
+        Some(path) => {
+            fs::write(&path, dbc).with_context(|| format!("could not write {}", path.display()))
+        }
+        None => {
+            print!("{}", dbc);
+            Ok(())
+        }
+    }
+}
@@ -7,10 +7,16 @@ _Min_Heap_Size = 0x200;      /* required amount of heap  */
 _Min_Stack_Size = 0x400; /* required amount of stack */
 
 /* Specify the memory areas */
+/* The application is linked after the bootloader, see ../bootloader/memory.x for the full flash map.
+ * The last 1K page of flash is reserved for the config block, see src/config.rs.
+ * The last 16 bytes of RAM are shared with the bootloader and never initialized, see src/bootloader.rs.
+ * The 128 bytes below them hold the fault log, see src/fault_log.rs, and are never initialized either. */
 MEMORY
 {
-RAM (xrw)      : ORIGIN = 0x20000000, LENGTH = 20K
-FLASH (rx)      : ORIGIN = 0x8000000, LENGTH = 64K
+RAM (xrw)      : ORIGIN = 0x20000000, LENGTH = 20K - 16 - 128
+FAULT_LOG (rw) : ORIGIN = 0x20004F70, LENGTH = 128
+BOOT_SHARED (rw) : ORIGIN = 0x20004FF0, LENGTH = 16
+FLASH (rx)      : ORIGIN = 0x8002000, LENGTH = 55K
 }
 /* This is where the call stack will be allocated. */
 /* The stack is of the full descending type. */
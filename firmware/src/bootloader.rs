@@ -0,0 +1,24 @@
+//! Handoff to the CAN bootloader, see `bmc_protocol::BootCommand`
+
+/// Address of the word shared with the bootloader, see `BOOT_SHARED` in memory.x
+const BOOT_FLAG_ADDR: u32 = 0x2000_4FF0;
+
+/// Tells the bootloader to stay resident after the reset.
+/// This must match `BOOT_FLAG_STAY` in the bootloader
+const BOOT_FLAG_STAY: u32 = 0x5941_5453; // "STAY"
+
+/// Address of the reset flags the bootloader saw, the word after the boot flag.
+/// This must match `RESET_FLAGS_ADDR` in the bootloader
+const RESET_FLAGS_ADDR: u32 = 0x2000_4FF4;
+
+/// RCC_CSR as the bootloader found it after the last reset.
+/// The bootloader resets again to start us, so our own copy only ever says software reset
+pub fn reset_flags() -> u32 {
+    unsafe { core::ptr::read_volatile(RESET_FLAGS_ADDR as *const u32) }
+}
+
+/// Resets into the bootloader, which will then wait for a firmware image
+pub fn enter() -> ! {
+    unsafe { core::ptr::write_volatile(BOOT_FLAG_ADDR as *mut u32, BOOT_FLAG_STAY) };
+    cortex_m::peripheral::SCB::sys_reset()
+}
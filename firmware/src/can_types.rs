@@ -0,0 +1,55 @@
+//! Glue between `bxcan` frames and the frames of `bmc_protocol`.
+
+use bxcan::{Data, ExtendedId, Frame, StandardId};
+
+use core::cmp::Ordering;
+
+use bmc_protocol::{CanFrame, Id};
+
+/// Converts a protocol frame into a frame we can hand to the can peripheral
+pub fn to_bxcan(frame: &CanFrame) -> Frame {
+    let id: bxcan::Id = match frame.id() {
+        Id::Standard(raw) => StandardId::new(raw).unwrap().into(),
+        Id::Extended(raw) => ExtendedId::new(raw).unwrap().into(),
+    };
+    if frame.is_remote_frame() {
+        Frame::new_remote(id, frame.dlc())
+    } else {
+        Frame::new_data(id, Data::new(frame.data()).unwrap())
+    }
+}
+
+/// Converts a received frame into a protocol frame
+pub fn from_bxcan(frame: &Frame) -> CanFrame {
+    let id = match frame.id() {
+        bxcan::Id::Standard(raw) => Id::Standard(raw.as_raw()),
+        bxcan::Id::Extended(raw) => Id::Extended(raw.as_raw()),
+    };
+    match frame.data() {
+        Some(data) => CanFrame::new_data(id, data),
+        None => CanFrame::new_remote(id, frame.dlc()),
+    }
+    .unwrap()
+}
+
+#[derive(Debug, defmt::Format)]
+pub struct PriorityFrame(pub Frame);
+
+impl Ord for PriorityFrame {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.priority().cmp(&other.0.priority())
+    }
+}
+
+impl PartialOrd for PriorityFrame {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Eq for PriorityFrame {}
+impl PartialEq for PriorityFrame {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
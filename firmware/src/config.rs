@@ -0,0 +1,52 @@
+//! Persistent board configuration.
+//!
+//! The config block lives in the last page of flash, which `memory.x` keeps
+//! out of the program image. Its layout, the crc and the migration of older
+//! blocks are in `bmc_protocol::config_block`, where the host tests cover them.
+
+use defmt::Format;
+
+use stm32f1xx_hal::flash;
+
+use bmc_protocol::{decode_config, encode_config, ConfigBlockError, MAX_CONFIG_BLOCK_LEN};
+
+pub use bmc_protocol::Config;
+
+/// Offset of the config page from the start of flash.
+/// This is the last 1K page of the 64K part
+const CONFIG_OFFSET: u32 = 63 * 1024;
+
+/// Size of the config page in bytes
+const CONFIG_PAGE_SIZE: usize = 1024;
+
+#[derive(Format)]
+pub enum ConfigError {
+    /// The page does not hold a block we can read
+    Block(ConfigBlockError),
+    Flash,
+}
+
+impl From<flash::Error> for ConfigError {
+    fn from(_: flash::Error) -> Self {
+        ConfigError::Flash
+    }
+}
+
+/// Loads the config block from flash.
+pub fn load(writer: &flash::FlashWriter) -> Result<Config, ConfigError> {
+    let page = writer.read(CONFIG_OFFSET, CONFIG_PAGE_SIZE)?;
+    decode_config(page).map_err(ConfigError::Block)
+}
+
+/// Erases the config page and writes `config` to it.
+/// NOTE: the cpu stalls while the page is erased (~20ms),
+/// so this should not be called while the motor is running
+pub fn save(config: &Config, writer: &mut flash::FlashWriter) -> Result<(), ConfigError> {
+    let mut block = [0xFF_u8; MAX_CONFIG_BLOCK_LEN];
+    // flash is written a half word at a time, every block is a whole number of them
+    let len = encode_config(config, &mut block);
+
+    writer.page_erase(CONFIG_OFFSET)?;
+    writer.write(CONFIG_OFFSET, &block[..len])?;
+    Ok(())
+}
@@ -0,0 +1,15 @@
+/// Enum representing which setpoint the motor output follows
+#[derive(Eq, PartialEq, Copy, Clone, defmt::Format)]
+pub enum ControlMode {
+    /// Open loop duty cycle, taken from `setpoint`
+    DutyCycle,
+    /// Open loop voltage, taken from `voltage_setpoint` and scaled by `bus_voltage`
+    Voltage,
+    /// Closed loop current in amps, taken from `current_setpoint`
+    Current,
+    /// Closed loop velocity in rpm, taken from `velocity_setpoint`
+    Velocity,
+    /// Closed loop position in rotations, taken from `position_setpoint`.
+    /// The position loop drives the inner loop selected by `PositionInnerLoop`
+    Position,
+}
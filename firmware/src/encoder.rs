@@ -0,0 +1,59 @@
+use embedded_hal::Qei;
+
+/// Weight of a new velocity sample in the velocity low pass filter.
+/// At a 500hz update rate a few counts of quantization noise is a lot,
+/// so we smooth it over roughly 10 updates
+const VELOCITY_FILTER_ALPHA: f32 = 0.1;
+
+/// Quadrature encoder read through a timer in encoder mode.
+/// The hardware counter is only 16 bits, so we accumulate it into
+/// a wider position as long as `update` is called often enough
+pub struct Encoder<Q> {
+    qei: Q,
+    last_count: u16,
+    position: i32,
+    velocity: f32,
+}
+
+impl<Q> Encoder<Q>
+where
+    Q: Qei<Count = u16>,
+{
+    pub fn new(qei: Q) -> Self {
+        let last_count = qei.count();
+        Self {
+            qei,
+            last_count,
+            position: 0,
+            velocity: 0.0,
+        }
+    }
+
+    /// Samples the hardware counter.
+    /// `dt` is the time in seconds since the last call
+    pub fn update(&mut self, dt: f32) {
+        let count = self.qei.count();
+
+        // the counter wraps, but the difference is correct as long as
+        // we move less than half the counter range between updates
+        let delta = count.wrapping_sub(self.last_count) as i16;
+        self.last_count = count;
+
+        self.position = self.position.wrapping_add(delta as i32);
+
+        let raw_velocity = delta as f32 / dt;
+        self.velocity += VELOCITY_FILTER_ALPHA * (raw_velocity - self.velocity);
+    }
+
+    /// Position in encoder counts since boot
+    #[inline(always)]
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Filtered velocity in encoder counts per second
+    #[inline(always)]
+    pub fn velocity(&self) -> f32 {
+        self.velocity
+    }
+}
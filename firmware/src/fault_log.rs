@@ -0,0 +1,34 @@
+//! Fault history that survives resets, see `bmc_protocol::FaultLog`
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use bmc_protocol::{FaultEvent, FaultLog};
+
+/// Address of the log, see `FAULT_LOG` in memory.x.
+/// The bootloader keeps out of it too, so records survive a firmware update
+const FAULT_LOG_ADDR: u32 = 0x2000_4F70;
+
+/// Milliseconds since boot, advanced by `motor_update`.
+/// A static so the panic handler can read it
+pub static UPTIME_MS: AtomicU32 = AtomicU32::new(0);
+
+/// Recovers the log left by the previous boot and counts this one,
+/// and tells whether the log survived. Call once, from `init`
+pub fn take() -> (&'static mut FaultLog, bool) {
+    // SAFETY: nothing else is linked into this region, and this runs once
+    let log = unsafe { &mut *(FAULT_LOG_ADDR as *mut FaultLog) };
+    let kept = log.recover();
+    (log, kept)
+}
+
+/// Records an event at the current uptime
+pub fn push(log: &mut FaultLog, event: FaultEvent) {
+    log.push(event, UPTIME_MS.load(Ordering::Relaxed));
+}
+
+/// Records a panic. The panic handler has no access to resources, so this goes around them
+pub fn push_panic() {
+    // SAFETY: we are panicking and never return to the task that might hold the log
+    let log = unsafe { &mut *(FAULT_LOG_ADDR as *mut FaultLog) };
+    push(log, FaultEvent::Panic);
+}
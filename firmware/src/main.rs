@@ -0,0 +1,1860 @@
+#![feature(lang_items, panic_info_message)]
+#![no_std]
+#![no_main]
+
+use embedded_hal::prelude::*;
+use stm32f1xx_hal::prelude::*;
+
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+
+use cortex_m::peripheral::DWT;
+
+use rtic::app;
+use rtic::cyccnt::{Instant, U32Ext as _};
+
+use stm32f1xx_hal::adc::{ChannelTimeSequence, SetChannels};
+use stm32f1xx_hal::gpio::{ExtiPin, IOPinSpeed, OutputSpeed};
+use stm32f1xx_hal::pac;
+use stm32f1xx_hal::pac::Interrupt;
+use stm32f1xx_hal::watchdog::IndependentWatchdog;
+use stm32f1xx_hal::{adc, can, dma, flash, gpio, pwm, qei, timer};
+
+use bxcan::{filter::Mask32, ExtendedId, Frame, Rx, Tx};
+
+use heapless::consts;
+use heapless::pool::singleton::Box;
+use heapless::pool::singleton::Pool;
+use heapless::BinaryHeap;
+
+use core::convert::Into;
+
+/// this makes sure that the rtt logger is linked into the binary
+use defmt_rtt as _;
+
+mod bootloader;
+mod can_types;
+mod config;
+mod control_mode;
+mod encoder;
+mod fault_log;
+mod status;
+
+use config::Config;
+use control_mode::ControlMode;
+use encoder::Encoder;
+
+use bmc_protocol::{
+    voltage_duty, CanBusState, CanLastError, ErrorCode, Fault, FaultAction, FaultEvent, FaultLog,
+    FaultManager, IdleMode, IntoWithId, OutgoingFrame, OutputState, Pid, PidGains,
+    PositionInnerLoop, Ramp, ResetCause, SmartCurrentLimit, StatusFrame, StatusSchedule,
+    ThermalModel,
+};
+
+use can_types::PriorityFrame;
+
+/// Clock speed in mhz of the HSE
+const HSE_CLOCK_MHZ: u32 = 8;
+
+/// System clock speed after scalars in mhz
+const SYS_CLOCK_MHZ: u32 = 72;
+
+/// Clock speed of hse in hz
+#[allow(dead_code)]
+const HSE_CLOCK_HZ: u32 = HSE_CLOCK_MHZ * 1_000_000;
+
+/// System clock after scalars in hz
+const SYS_CLOCK_HZ: u32 = SYS_CLOCK_MHZ * 1_000_000;
+
+/// CAN transmitter configuration byte.
+/// This was generated from <http://www.bittiming.can-wiki.info/#bxCAN>
+/// We use a 1 Mbs configuration
+const CAN_CONFIG: u32 = 0x001e0001;
+
+/// Can timeout constant
+/// This represents the number of cycles before we determine that the CAN bus has disconnected
+/// For now, 2 seconds seems fine
+const CAN_TIMEOUT: u32 = SYS_CLOCK_HZ * 2;
+
+/// The motor deadband
+/// For now, a 1% deadband seems fine
+const MOTOR_DEADBAND_PERCENT: f32 = 0.01;
+
+/// Motor deadband multiplied by the max duty cycle.
+/// Any duty cycle below this value will be set to 0.
+const DEFAULT_MOTOR_DEADBAND: i16 = (i16::MAX as f32 * MOTOR_DEADBAND_PERCENT) as i16;
+
+// The following values are from the motor driver datasheet
+/// `V_OFF` is the value output by the current amplifier when no current is detected
+/// It's basically an offset. We can theoretically adjust this, but I dont think it matters
+const V_OFF: f32 = 0.050; // volts
+
+/// The value of the current sensing shunt resistor in ohms
+const R_SENSE_VAL: f32 = 0.004; // ohms
+
+/// The gain of the current amplifier  (in Volts / Volt)
+const AMP_GAIN: f32 = 20.0;
+
+/// The scaling factor of the current sense voltage divider.
+/// This also represents the scaling factor of our shop current calculations
+const CURRENT_EXTERNAL_SCALE: f32 = 22_000.0 / (22_000.0 + 10_000.0); // from the current divider
+
+/// Volts per count of the 12 bit adc, referenced to the 3.3v rail
+const ADC_VOLTS_PER_COUNT: f32 = 3.3 / 4096.0;
+
+/// The scaling factor of the bus voltage divider on PA2.
+/// Bus voltages up to 18.8v are in range of the adc
+const BUS_VOLTAGE_SCALE: f32 = (47_000.0 + 10_000.0) / 10_000.0; // from the bus voltage divider
+
+// The following values are from the stm32f103 datasheet
+/// Output of the internal temperature sensor at 25 degrees
+const MCU_TEMP_V25: f32 = 1.43; // volts
+
+/// Slope of the internal temperature sensor, it falls as the chip warms up
+const MCU_TEMP_SLOPE: f32 = 0.0043; // volts per degree
+
+/// Adc channel of the internal temperature sensor
+const MCU_TEMP_CHANNEL: u8 = 16;
+
+const CAN_QUEUE_DEPTH: usize = 128;
+const CAN_QUEUE_BYTES: usize = core::mem::size_of::<PriorityFrame>() * CAN_QUEUE_DEPTH;
+
+// hardware type defs (these are all self explanatory)
+type DmaPayload = adc::AdcPayload<AdcPins, adc::Scan>;
+type AdcDma = stm32f1xx_hal::dma::RxDma<DmaPayload, dma::dma1::C1>;
+
+/// A whole number of scans, so every half of the buffer starts with the first channel
+const ADC_BUF_LEN: usize = 21 * ADC_CHANNELS;
+type AdcBuf = [u16; ADC_BUF_LEN]; // thicc buffer
+
+/// Analog pins in scan order: motor current (PA3), then bus voltage (PA2).
+/// The internal temperature sensor is scanned after them.
+/// Samples in `AdcBuf` go round the channels in that order
+struct AdcPins(
+    gpio::gpioa::PA3<gpio::Analog>,
+    gpio::gpioa::PA2<gpio::Analog>,
+);
+
+/// Number of channels in a scan, the pins in `AdcPins` and the temperature sensor
+const ADC_CHANNELS: usize = 3;
+
+impl SetChannels<AdcPins> for adc::Adc<pac::ADC1> {
+    fn set_samples(&mut self) {
+        // NOTE: we can make this faster or slower if we want
+        self.set_channel_sample_time(3, adc::SampleTime::T_239);
+        self.set_channel_sample_time(2, adc::SampleTime::T_239);
+        // the temperature sensor needs at least 17us, which this is at any adc clock we use
+        self.set_channel_sample_time(MCU_TEMP_CHANNEL, adc::SampleTime::T_239);
+    }
+
+    fn set_sequence(&mut self) {
+        // the temperature sensor has no pin, it only has to be switched on.
+        // Setting up the dma only sets other bits of this register
+        unsafe { (*pac::ADC1::ptr()).cr2.modify(|_, w| w.tsvrefe().set_bit()) };
+        self.set_regular_sequence(&[3, 2, MCU_TEMP_CHANNEL]);
+        // set continous mode, this means the dma will run continuosly
+        self.set_continuous_mode(true);
+    }
+}
+
+type PwmChannel<C> = pwm::PwmChannel<pac::TIM1, C>;
+type MotorHighChannel = PwmChannel<pwm::C2>;
+type MotorLowChannel = PwmChannel<pwm::C3>;
+type CurrentLimitChannel = PwmChannel<pwm::C4>;
+
+type FaultPin = gpio::gpioa::PA4<gpio::Input<gpio::Floating>>;
+type OverCurrentPin = gpio::gpioa::PA5<gpio::Input<gpio::Floating>>;
+type SleepPin = gpio::gpioa::PA6<gpio::Output<gpio::PushPull>>;
+
+type EncoderPins = (
+    gpio::gpioa::PA0<gpio::Input<gpio::Floating>>,
+    gpio::gpioa::PA1<gpio::Input<gpio::Floating>>,
+);
+type EncoderQei = qei::Qei<pac::TIM2, timer::Tim2NoRemap, EncoderPins>;
+
+type Status1 = status::StatusLed<gpio::gpiob::PB10<gpio::Output<gpio::PushPull>>>;
+type Status2 = status::StatusLed<gpio::gpiob::PB11<gpio::Output<gpio::PushPull>>>;
+
+// timings
+/// This method calculates the number of cycles needed to run a certain periodic
+/// function x times per second
+const fn times_per_second(times: u32) -> u32 {
+    SYS_CLOCK_HZ / times
+}
+
+/// Number of cycles in a time from the config, in milliseconds
+const fn millis(ms: u16) -> u32 {
+    ms as u32 * (SYS_CLOCK_HZ / 1000)
+}
+
+/// Motor update period.
+/// 1kHz should be fine since we arent doing anything fancy
+const MOTOR_UPDATE_PD: u32 = times_per_second(500);
+
+/// Motor update period in seconds.
+/// This is the timestep used by the closed loop controllers
+const MOTOR_UPDATE_DT: f32 = MOTOR_UPDATE_PD as f32 / SYS_CLOCK_HZ as f32;
+
+/// Motor update period in milliseconds, which is how `fault_log::UPTIME_MS` advances
+const MOTOR_UPDATE_MS: u32 = MOTOR_UPDATE_PD / (SYS_CLOCK_HZ / 1000);
+
+/// Status frame tick. Each status frame is sent at its own rate,
+/// see `StatusSchedule`, so this is the fastest any of them can go
+const STATUS_TICK_PD: u32 = times_per_second(1000);
+
+/// Status frame tick in milliseconds, which is what `StatusSchedule` counts in
+const STATUS_TICK_MS: u32 = STATUS_TICK_PD / (SYS_CLOCK_HZ / 1000);
+
+/// How often `can_tx` moves queued frames into the tx mailboxes.
+/// This keeps up with a status frame at every tick
+const CAN_TX_PD: u32 = times_per_second(1000);
+
+/// Wait before the first attempt to bring the can peripheral back from bus off (10ms).
+/// Each bus off soon after the last one doubles the wait, see `can_sce`
+const BUS_OFF_BACKOFF_MIN_PD: u32 = times_per_second(100);
+
+/// Longest wait before we try to bring the can peripheral back from bus off
+const BUS_OFF_BACKOFF_MAX_PD: u32 = times_per_second(1);
+
+/// A bus off this long after the last one starts the backoff over
+const BUS_OFF_BACKOFF_RESET_PD: u32 = SYS_CLOCK_HZ * 5;
+
+/// How long we wait for the can peripheral to enter initialization mode (1ms)
+const CAN_INIT_TIMEOUT_PD: u32 = times_per_second(1000);
+
+/// The watchdog resets the board if it is not fed for this long, in milliseconds.
+/// Erasing the config page stalls everything for up to 40ms, so this leaves room for that
+const WATCHDOG_TIMEOUT_MS: u32 = 250;
+
+/// How often `feed_watchdog` checks that the motor and can tasks still run
+const WATCHDOG_FEED_PD: u32 = times_per_second(20);
+
+/// How often we update the leds
+/// This subsequently controls how fast the leds will flash
+const LED_UPDATE_PD: u32 = times_per_second(8);
+
+heapless::pool! {
+    /// can frame pool definition.
+    /// Allows us to allocate frames similar to allocating on the heap of a normal system
+    #[allow(non_upper_case_globals)]
+    CanFramePool: PriorityFrame
+}
+
+/// Helper function for `CanFramePool`.
+/// Allocates a `bxcan::Frame` in our memory pool
+fn allocate_tx_frame(frame: Frame) -> Box<CanFramePool, heapless::pool::Init> {
+    let b = CanFramePool::alloc().unwrap();
+    b.init(PriorityFrame(frame))
+}
+
+/// Version of this firmware as major, minor, patch, reported in `OutgoingFrame::Version`
+fn firmware_version() -> [u8; 3] {
+    [
+        env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+        env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+        env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0),
+    ]
+}
+
+/// Runs one iteration of the current loop and returns a duty cycle.
+/// The current sensor only measures magnitude, so we regulate the magnitude
+/// and apply the sign of the setpoint afterwards
+fn current_loop(
+    pid: &mut Pid,
+    gains: &PidGains,
+    setpoint: f32,
+    current_now: f32,
+    current_limit: u8,
+) -> i16 {
+    // never ask for more than the hardware chopper will allow
+    let target = setpoint.abs().min(current_limit as f32);
+
+    let output = pid.update(gains, target - current_now, MOTOR_UPDATE_DT);
+    let duty = (output * i16::MAX as f32) as i16;
+
+    if setpoint < 0.0 {
+        -duty
+    } else {
+        duty
+    }
+}
+
+/// Runs one iteration of the velocity loop and returns a duty cycle
+fn velocity_loop(pid: &mut Pid, gains: &PidGains, setpoint: f32, velocity_now: f32) -> i16 {
+    let output = pid.update(gains, setpoint - velocity_now, MOTOR_UPDATE_DT);
+    (output * i16::MAX as f32) as i16
+}
+
+#[app(device=stm32f1xx_hal::stm32, peripherals = true, monotonic=rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    struct Resources {
+        /// The time we last recieved an can frame from the bus
+        /// Used to time out our can transiciever
+        #[init(None)]
+        last_can_rx: Option<Instant>,
+
+        /// The Can id of this board,
+        /// calculated using the dip switches on the board
+        can_id: bmc_protocol::Id,
+
+        /// Can transmitter
+        can_tx: Tx<stm32f1xx_hal::can::Can<pac::CAN1>>,
+
+        /// Can reciever
+        can_rx: Rx<stm32f1xx_hal::can::Can<pac::CAN1>>,
+
+        /// Queue for outgoing can frames.
+        /// This will send the frames in order of priority,
+        /// such as in the CAN standard (lower id means higher priority)
+        can_tx_queue: heapless::BinaryHeap<
+            Box<CanFramePool, heapless::pool::Init>,
+            consts::U16,
+            heapless::binary_heap::Max,
+        >,
+
+        /// circular adc buffer. This allows us to coninuously read from the
+        /// current sensing circuit without interruption. Data will be stored in
+        /// two "Halves" of the buffer. One will be filled while the other is being processed.
+        adc_buf: dma::CircBuffer<AdcBuf, AdcDma>,
+
+        // adc: adc::Adc<pac::ADC1>,
+        // adc_pin: gpio::gpioa::PA3<gpio::Analog>,
+        /// Motor high pwm channel. Controls forward duty cycle.
+        motor_high: MotorHighChannel,
+
+        /// Motor low pwm channel. Controls reverse duty cycle.
+        motor_low: MotorLowChannel,
+
+        /// Pwm channel thats converted to a constant voltage used to set chop current
+        motor_current_limit: CurrentLimitChannel,
+
+        /// Gpio pin that signals motor controller faults when low.
+        /// Polled by `motor_update`, since PA4 is on an exti line `exti9_5` never sees
+        fault_pin: FaultPin,
+
+        /// Gpio output pin that allows us to set the sleep mode of the
+        /// motor controller chip. If we set this output low, the motor controller
+        /// chip will be put into sleep mode
+        sleep_pin: SleepPin,
+
+        /// Gpio input that signals an overcurrent event.
+        /// Will trigger an interrupt when pulled low
+        over_current_pin: OverCurrentPin,
+
+        /// First status LED
+        status1: Status1,
+
+        /// Second status LED
+        status2: Status2,
+
+        /// Quadrature encoder on TIM2
+        encoder: Encoder<EncoderQei>,
+
+        /// Most recent position in rotations
+        #[init(0.0)]
+        position_now: f32,
+
+        /// Most recent velocity in rpm
+        #[init(0.0)]
+        velocity_now: f32,
+
+        /// Last heartbeat recieved. If we dont recieve a heartbeat for
+        /// `Config::heartbeat_timeout`, we stop the motor
+        #[init(None)]
+        last_heartbeat: Option<Instant>,
+
+        /// duty cycle setpoint
+        /// I made this an i16 instead of a float
+        /// because I want to avoid floating point operations
+        #[init(0)]
+        setpoint: i16,
+
+        /// Most recent duty cycle of our leader, see `Config::leader`
+        #[init(0)]
+        leader_duty: i16,
+
+        /// When we last recieved an update from our leader.
+        /// Cleared while we are not a follower, so a new leader starts out lost
+        #[init(None)]
+        last_leader_update: Option<Instant>,
+
+        /// Slew limiter for `setpoint`, see `Config::ramp_up`
+        #[init(Ramp::new())]
+        ramp: Ramp,
+
+        /// Software current limit, applied on top of the chopper limit
+        #[init(SmartCurrentLimit::new())]
+        smart_current_limit: SmartCurrentLimit,
+
+        /// Winding temperature estimate, fed by `handle_adc`
+        #[init(ThermalModel::new())]
+        thermal: ThermalModel,
+
+        /// When `handle_adc` last ran, to feed `thermal` the time between samples
+        #[init(None)]
+        last_adc: Option<Instant>,
+
+        /// Which setpoint the motor output follows
+        #[init(ControlMode::DutyCycle)]
+        control_mode: ControlMode,
+
+        /// Open loop setpoint in volts, see `ControlMode::Voltage`
+        #[init(0.0)]
+        voltage_setpoint: f32,
+
+        /// Most recent bus voltage in volts
+        #[init(0.0)]
+        bus_voltage: f32,
+
+        /// Most recent temperature of the microcontroller in degrees celsius
+        #[init(0.0)]
+        mcu_temperature: f32,
+
+        /// Closed loop current setpoint in amps.
+        /// The sign sets the direction, since the current sensor only measures magnitude
+        #[init(0.0)]
+        current_setpoint: f32,
+
+        /// Current loop controller state.
+        /// Its output is a fraction of full duty cycle
+        #[init(Pid::new(0.0, 1.0))]
+        current_pid: Pid,
+
+        /// Closed loop velocity setpoint in rpm
+        #[init(0.0)]
+        velocity_setpoint: f32,
+
+        /// Velocity loop controller state.
+        /// Its output is a fraction of full duty cycle
+        #[init(Pid::new(-1.0, 1.0))]
+        velocity_pid: Pid,
+
+        /// Closed loop position setpoint in rotations
+        #[init(0.0)]
+        position_setpoint: f32,
+
+        /// Position loop controller state.
+        /// Its output is the setpoint of the inner loop.
+        /// The output limits are set from `config` on every update
+        #[init(Pid::new(0.0, 0.0))]
+        position_pid: Pid,
+
+        /// Board configuration (current limit, inversion, idle mode, loop gains...).
+        /// Loaded from flash in `init`, and only written back on request
+        config: Config,
+
+        /// Flash peripheral, used to save `config`
+        flash: flash::Parts,
+
+        /// Most recent current value in amps
+        #[init(0.0)]
+        current_now: f32,
+
+        /// Duty cycle that actually reached the bridge, after the deadband, the idle mode and faults
+        #[init(0)]
+        applied_duty: i16,
+
+        /// What the bridge did with the motor in the last update
+        #[init(OutputState::Coast)]
+        output_state: OutputState,
+
+        /// Active and sticky faults. `motor_update` holds the output while
+        /// the action of a sticky fault asks for it
+        #[init(FaultManager::new())]
+        faults: FaultManager,
+
+        /// Fault history, kept in ram that survives resets
+        fault_log: &'static mut FaultLog,
+
+        /// When each status frame is due, see `send_update`
+        #[init(StatusSchedule::new())]
+        status_schedule: StatusSchedule,
+
+        /// Error state of the can peripheral, kept by `can_sce`
+        #[init(CanBusState::ErrorActive)]
+        can_state: CanBusState,
+
+        /// Last error the can peripheral saw on the bus
+        #[init(CanLastError::None)]
+        can_last_error: CanLastError,
+
+        /// Times we went bus off since boot
+        #[init(0)]
+        bus_off_count: u16,
+
+        /// When we last went bus off, to tell if the bus keeps failing
+        #[init(None)]
+        last_bus_off: Option<Instant>,
+
+        /// How long we wait before the next attempt to recover from bus off
+        #[init(BUS_OFF_BACKOFF_MIN_PD)]
+        bus_off_backoff: u32,
+
+        /// Set while the bus is timed out, so the timeout is logged once
+        #[init(false)]
+        can_timed_out: bool,
+
+        /// Set while the motor is stopped for a missing heartbeat, shown on the leds
+        #[init(false)]
+        heartbeat_lost: bool,
+
+        /// Resets the board unless `feed_watchdog` keeps feeding it
+        watchdog: IndependentWatchdog,
+
+        /// Set by `motor_update` every time it runs, and cleared when the watchdog is fed
+        #[init(false)]
+        motor_alive: bool,
+
+        /// Set by `can_tx` every time it runs, and cleared when the watchdog is fed
+        #[init(false)]
+        can_alive: bool,
+    }
+
+    /// Initialization function
+    /// Here we initialize hardware peripherals and setup software variables
+    #[init(schedule=[led_update, motor_update, can_tx, send_update, feed_watchdog], spawn=[queue_tx_frame])]
+    fn init(cx: init::Context) -> init::LateResources {
+        // Create tx queue
+        let can_tx_queue = BinaryHeap::new();
+
+        // grow our can memory pool
+        CanFramePool::grow(
+            cortex_m::singleton!(: [u8; CAN_QUEUE_BYTES] = [0; CAN_QUEUE_BYTES]).unwrap(),
+        );
+
+        // take peripheral and device instances
+        let mut peripherals = cx.core;
+        let device: stm32f1xx_hal::stm32::Peripherals = cx.device;
+
+        // https://github.com/probe-rs/probe-rs/issues/350#issuecomment-742825415
+        device.DBGMCU.cr.modify(|_, w| {
+            w.dbg_sleep().set_bit();
+            w.dbg_standby().set_bit();
+            w.dbg_stop().set_bit()
+        });
+        device.RCC.ahbenr.modify(|_, w| w.dma1en().enabled());
+        device.RCC.ahbenr.modify(|_, w| w.dma2en().enabled());
+
+        // take seperate peripheral instances for future initialization purposes
+        let mut flash = device.FLASH.constrain();
+        let mut rcc = device.RCC.constrain();
+        let mut afio = device.AFIO.constrain(&mut rcc.apb2);
+        let mut debug = device.DBGMCU;
+        let exti = device.EXTI;
+
+        // configure the system clocks
+        let clocks = rcc
+            .cfgr
+            .use_hse(HSE_CLOCK_MHZ.mhz())
+            .sysclk(SYS_CLOCK_MHZ.mhz())
+            .hclk(SYS_CLOCK_MHZ.mhz())
+            .pclk1((SYS_CLOCK_MHZ / 2).mhz())
+            .pclk2(SYS_CLOCK_MHZ.mhz())
+            .adcclk(5.mhz())
+            .freeze(&mut flash.acr);
+
+        // load the saved config, falling back to defaults if there isnt a valid one
+        let config = {
+            let writer = flash.writer(flash::SectorSize::Sz1K, flash::FlashSize::Sz64K);
+            config::load(&writer).unwrap_or_else(|e| {
+                defmt::warn!("Could not load config: {:?}, using defaults", e);
+                Config::default()
+            })
+        };
+        defmt::info!("Config: {:?}", config);
+
+        let (fault_log, log_kept) = fault_log::take();
+        defmt::info!(
+            "Boot {=u16}, {=usize} records in the fault log",
+            fault_log.boot(),
+            fault_log.len()
+        );
+
+        // the bootloader read the reset flags before its own reset into us
+        let reset_cause = ResetCause::from_flags(bootloader::reset_flags(), log_kept);
+        defmt::info!("Reset cause: {:?}", reset_cause);
+        if reset_cause == ResetCause::Watchdog {
+            fault_log::push(fault_log, FaultEvent::WatchdogReset);
+        }
+
+        // take gpio instances
+        let mut gpioa = device.GPIOA.split(&mut rcc.apb2);
+        let mut gpiob = device.GPIOB.split(&mut rcc.apb2);
+
+        // initalize motor sleep pin, pull this low before motor does something weird
+        let mut sleep_pin = gpioa
+            .pa6
+            .into_push_pull_output_with_state(&mut gpioa.crl, gpio::State::Low);
+        sleep_pin.set_speed(&mut gpioa.crl, gpio::IOPinSpeed::Mhz2); // save some power
+
+        // get the system can id from the dip switches
+        #[allow(unused_must_use)]
+        let can_id = {
+            let mut id = 0_u16;
+            let mut pins =
+                heapless::Vec::<gpio::Pxx<gpio::Input<gpio::PullDown>>, consts::U8>::new();
+
+            // disable jtag. If we dont do this, we will have issues
+            let (_, pb3, pb4) = afio.mapr.disable_jtag(gpioa.pa15, gpiob.pb3, gpiob.pb4);
+
+            // put all can_id pins in a vec
+            pins.push(gpiob.pb0.into_pull_down_input(&mut gpiob.crl).downgrade());
+            pins.push(gpiob.pb1.into_pull_down_input(&mut gpiob.crl).downgrade());
+            pins.push(gpiob.pb2.into_pull_down_input(&mut gpiob.crl).downgrade());
+            pins.push(gpiob.pb7.into_pull_down_input(&mut gpiob.crl).downgrade());
+            pins.push(gpiob.pb6.into_pull_down_input(&mut gpiob.crl).downgrade());
+            pins.push(gpiob.pb5.into_pull_down_input(&mut gpiob.crl).downgrade());
+            pins.push(pb4.into_pull_down_input(&mut gpiob.crl).downgrade());
+            pins.push(pb3.into_pull_down_input(&mut gpiob.crl).downgrade());
+
+            // interate over the pins and shift values as we go
+            for (shift, pin) in pins.iter().enumerate() {
+                id |= (pin.is_high().unwrap() as u16) << (shift as u16);
+            }
+            pins.clear(); // just to make sure this happens.
+            id
+        };
+
+        defmt::info!("Can Id: {=u16}", can_id);
+
+        if can_id as u8 == bmc_protocol::BROADCAST {
+            defmt::warn!("Can id is the broadcast id, every board will think frames are for it");
+        }
+
+        // the filter id: our device type and manufacturer, any api, and the board id
+        let device_id = bmc_protocol::IdFields::new(0, 0, can_id as u8).raw();
+
+        // create can peripheral instance
+        let can = can::Can::new(device.CAN1, &mut rcc.apb1, device.USB);
+
+        // assign pins to can interface
+        {
+            let tx_pin = gpiob.pb9.into_alternate_push_pull(&mut gpiob.crh);
+            let rx_pin = gpiob.pb8.into_floating_input(&mut gpiob.crh);
+
+            can.assign_pins((tx_pin, rx_pin), &mut afio.mapr);
+        }
+
+        // create bxcan can instance
+        let mut can = bxcan::Can::new(can);
+
+        // set can config
+        can.configure(|config| {
+            config.set_bit_timing(CAN_CONFIG);
+
+            // we dont need these at alll. Dont bother
+            config.set_silent(false);
+            config.set_loopback(false);
+        });
+
+        // configure filters with our settings.
+        // we only care about the device bits of the id, the api bits pick the frame
+        {
+            let can_id_mask = ExtendedId::new(bmc_protocol::DEVICE_MASK).unwrap();
+            let ext_id = ExtendedId::new(device_id).unwrap();
+            let broadcast_id =
+                ExtendedId::new(bmc_protocol::IdFields::new(0, 0, bmc_protocol::BROADCAST).raw())
+                    .unwrap();
+
+            // the filters cant change once the can peripheral is split, so we take every
+            // group setpoint and every `Update`, and check the group and the leader in
+            // `handle_rx_frame`. That way a new `follow_leader` works without a reset
+            let api_mask = ExtendedId::new(bmc_protocol::API_MASK).unwrap();
+            let group_id = ExtendedId::new(
+                bmc_protocol::IdFields::new(
+                    bmc_protocol::api::CLASS_CONTROL,
+                    bmc_protocol::api::GROUP_SETPOINT,
+                    0,
+                )
+                .raw(),
+            )
+            .unwrap();
+
+            let update_id = ExtendedId::new(
+                bmc_protocol::IdFields::new(
+                    bmc_protocol::api::CLASS_STATUS,
+                    bmc_protocol::api::UPDATE,
+                    0,
+                )
+                .raw(),
+            )
+            .unwrap();
+
+            can.modify_filters()
+                .enable_bank(0, Mask32::frames_with_ext_id(ext_id, can_id_mask))
+                .enable_bank(1, Mask32::frames_with_ext_id(broadcast_id, can_id_mask))
+                .enable_bank(2, Mask32::frames_with_ext_id(group_id, api_mask))
+                .enable_bank(3, Mask32::frames_with_ext_id(update_id, api_mask));
+        }
+
+        // enable desired interrupts.
+        // The last error code interrupt is left off, alone on the bus it would fire for every
+        // frame we retry. Automatic bus off recovery stays off too, `recover_can` backs off instead
+        use bxcan::Interrupts;
+        can.enable_interrupts(
+            Interrupts::FIFO0_MESSAGE_PENDING
+                | Interrupts::FIFO1_MESSAGE_PENDING
+                | Interrupts::ERROR_WARNING
+                | Interrupts::ERROR_PASSIVE
+                | Interrupts::BUS_OFF
+                | Interrupts::ERROR,
+        );
+
+        // enable can interface
+        // this would block, so we must tell the program this is ok
+        nb::block!(can.enable()).unwrap();
+
+        // wrap can id again
+        let can_id = bmc_protocol::Id::Extended(device_id);
+
+        // split can peripheral into tx and rx
+        let (can_tx, can_rx) = can.split();
+
+        // create the pwm channels
+        let (_, mut motor_high, mut motor_low, mut motor_current_limit) = {
+            let tim_pins = (
+                gpioa.pa8.into_alternate_push_pull(&mut gpioa.crh),
+                gpioa.pa9.into_alternate_push_pull(&mut gpioa.crh),
+                gpioa.pa10.into_alternate_push_pull(&mut gpioa.crh),
+                gpioa.pa11.into_alternate_push_pull(&mut gpioa.crh),
+            );
+
+            let mut tim = timer::Timer::tim1(device.TIM1, &clocks, &mut rcc.apb2);
+
+            // make sure the pwm timers still run during debug, or else the motors will stop
+            tim.stop_in_debug(&mut debug, false);
+
+            tim.pwm::<stm32f1xx_hal::timer::Tim1NoRemap, _, _, _>(
+                tim_pins,
+                &mut afio.mapr,
+                100.khz(),
+            )
+            .split()
+        };
+
+        // enable pwm channels
+        motor_high.enable();
+        motor_low.enable();
+        motor_current_limit.enable();
+
+        // set duty of all 3 channels to 0 for now
+        motor_high.set_duty(0);
+        motor_low.set_duty(0);
+        motor_current_limit.set_duty(0);
+
+        // setup dma transfer for current sensing circuit
+        let adc_buf = {
+            // split a dma channel
+            let mut dma_ch = device.DMA1.split(&mut rcc.ahb).1;
+            dma_ch.listen(dma::Event::HalfTransfer);
+            dma_ch.listen(dma::Event::TransferComplete);
+
+            // get our desired analog pins
+            let pins = AdcPins(
+                gpioa.pa3.into_analog(&mut gpioa.crl),
+                gpioa.pa2.into_analog(&mut gpioa.crl),
+            );
+
+            // setup adc for fast, continous operation.
+            // Sample times and the scan sequence are set by `SetChannels`
+            let mut adc = adc::Adc::adc1(device.ADC1, &mut rcc.apb2, clocks);
+            adc.set_align(adc::Align::Right); // TODO: Check if this is correct
+
+            // get singleton buffer and start dma
+            let buf = cortex_m::singleton!(: [AdcBuf ; 2] = [[0 ; ADC_BUF_LEN] ; 2]).unwrap();
+
+            // start circular read
+            let adc_dma = adc.with_scan_dma(pins, dma_ch);
+            adc_dma.circ_read(buf)
+        };
+
+        // setup TIM2 in encoder mode
+        let encoder = {
+            let pins = (gpioa.pa0, gpioa.pa1);
+            let tim = timer::Timer::tim2(device.TIM2, &clocks, &mut rcc.apb1);
+            let qei =
+                tim.qei::<timer::Tim2NoRemap, _>(pins, &mut afio.mapr, qei::QeiOptions::default());
+            Encoder::new(qei)
+        };
+
+        // create fault and overcurrent pins
+        let fault_pin = gpioa.pa4.into_floating_input(&mut gpioa.crl);
+        let mut over_current_pin = gpioa.pa5.into_floating_input(&mut gpioa.crl);
+
+        // setup over current interrupt
+        over_current_pin.make_interrupt_source(&mut afio);
+        over_current_pin.trigger_on_edge(&exti, gpio::Edge::FALLING); // this has an external pullup
+        over_current_pin.enable_interrupt(&exti);
+
+        // take motor driver out of sleep mode
+        sleep_pin.set_high().unwrap();
+
+        // crate status leds
+        let status1 = status::StatusLed::new_with_mode(
+            gpiob.pb10.into_push_pull_output(&mut gpiob.crh),
+            status::LedMode::FlashSlow,
+        );
+        let status2 = status::StatusLed::new_with_mode(
+            gpiob.pb11.into_push_pull_output(&mut gpiob.crh),
+            status::LedMode::FlashSlow,
+        );
+
+        // reset if the motor or can tasks stop running, see `feed_watchdog`
+        let mut watchdog = IndependentWatchdog::new(device.IWDG);
+        watchdog.stop_on_debug(&debug, true);
+        watchdog.start(WATCHDOG_TIMEOUT_MS.ms());
+
+        // announce ourselves before any status frame
+        cx.spawn
+            .queue_tx_frame(OutgoingFrame::Boot {
+                reset_cause,
+                boot: fault_log.boot(),
+            })
+            .unwrap();
+
+        // enable cycle counter
+        peripherals.DCB.enable_trace();
+        DWT::unlock();
+        peripherals.DWT.enable_cycle_counter();
+
+        // start periodic functions
+        let now = cx.start;
+        cx.schedule
+            .led_update(now + LED_UPDATE_PD.cycles())
+            .unwrap();
+        cx.schedule
+            .motor_update(now + MOTOR_UPDATE_PD.cycles())
+            .unwrap();
+        cx.schedule.can_tx(now + CAN_TX_PD.cycles()).unwrap();
+        cx.schedule
+            .send_update(now + STATUS_TICK_PD.cycles())
+            .unwrap();
+        cx.schedule
+            .feed_watchdog(now + WATCHDOG_FEED_PD.cycles())
+            .unwrap();
+
+        defmt::trace!("End of init");
+
+        init::LateResources {
+            can_id,
+            can_tx_queue,
+            can_tx,
+            can_rx,
+            adc_buf,
+            motor_high,
+            motor_low,
+            motor_current_limit,
+            fault_pin,
+            over_current_pin,
+            sleep_pin,
+            status1,
+            status2,
+            encoder,
+            config,
+            flash,
+            fault_log,
+            watchdog,
+        }
+    }
+
+    /// idle function. Does nothing for now
+    #[idle()]
+    fn idle(_cx: idle::Context) -> ! {
+        loop {
+            cortex_m::asm::nop();
+            // cortex_m::asm::wfi();
+        }
+    }
+
+    /// Queues the status frames that are due, each at its rate in `config`
+    #[task(priority = 2, schedule = [send_update], spawn = [queue_tx_frame], resources = [status_schedule, applied_duty, output_state, current_now, bus_voltage, mcu_temperature, position_now, velocity_now, faults, thermal, config, can_tx_queue, can_state, can_last_error, bus_off_count])]
+    fn send_update(mut cx: send_update::Context) {
+        defmt::trace!("Send update");
+
+        // get resources
+        let schedule = cx.resources.status_schedule;
+        let current_now = cx.resources.current_now.lock(|cn| *cn);
+        let bus_voltage = cx.resources.bus_voltage.lock(|v| *v);
+        let position = cx.resources.position_now.lock(|p| *p);
+        let velocity = cx.resources.velocity_now.lock(|v| *v);
+        let faults = cx.resources.faults.lock(|f| *f);
+        let config = cx.resources.config.lock(|c| *c);
+        let (temperature, derating) = cx
+            .resources
+            .thermal
+            .lock(|t| (t.temperature(), t.derating(&config)));
+        let applied_duty = cx.resources.applied_duty.lock(|ad| *ad);
+        let output_state = cx.resources.output_state.lock(|os| *os);
+        let mcu_temperature = cx.resources.mcu_temperature.lock(|t| *t);
+        let uptime = fault_log::UPTIME_MS.load(core::sync::atomic::Ordering::Relaxed) / 1000;
+        let tx_queue_depth = cx.resources.can_tx_queue.lock(|q| q.len());
+        let bus_off_count = cx.resources.bus_off_count.lock(|c| *c);
+        let can_state = cx.resources.can_state.lock(|s| *s);
+        let can_last_error = cx.resources.can_last_error.lock(|e| *e);
+        // the error counters are read only, so this cannot upset the can driver
+        let esr = unsafe { (*pac::CAN1::ptr()).esr.read() };
+
+        schedule.advance(STATUS_TICK_MS);
+        for &status in StatusFrame::ALL.iter() {
+            // nothing goes out while we are bus off, and old status would only fill the queue
+            if !schedule.due(&config, status) || can_state == CanBusState::BusOff {
+                continue;
+            }
+            let frame = match status {
+                // followers mirror this, so it is the output after a stop, not the setpoint
+                StatusFrame::Update => OutgoingFrame::Update {
+                    current_now,
+                    duty_now: applied_duty,
+                },
+                StatusFrame::Encoder => OutgoingFrame::Encoder { position, velocity },
+                StatusFrame::BusVoltage => OutgoingFrame::BusVoltage(bus_voltage),
+                StatusFrame::MotorTemperature if config.motor_temp_limit > 0.0 => {
+                    OutgoingFrame::MotorTemperature {
+                        temperature,
+                        derating,
+                    }
+                }
+                // keep reminding the host until the faults are cleared
+                StatusFrame::Faults if !faults.sticky().is_empty() => OutgoingFrame::Faults {
+                    active: faults.active(),
+                    sticky: faults.sticky(),
+                },
+                StatusFrame::SystemStatus => OutgoingFrame::SystemStatus {
+                    active: faults.active(),
+                    sticky: faults.sticky(),
+                    mcu_temperature: mcu_temperature as i8,
+                    uptime,
+                },
+                StatusFrame::CanStatus => OutgoingFrame::CanStatus {
+                    tx_errors: esr.tec().bits(),
+                    rx_errors: esr.rec().bits(),
+                    bus_off_count,
+                    tx_queue_depth: tx_queue_depth as u8,
+                    state: can_state,
+                    last_error: can_last_error,
+                },
+                StatusFrame::Output => OutgoingFrame::Output {
+                    duty: applied_duty,
+                    state: output_state,
+                },
+                _ => continue,
+            };
+            let _ = cx
+                .spawn
+                .queue_tx_frame(frame)
+                .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+        }
+
+        // schedule this task again
+        cx.schedule
+            .send_update(Instant::now() + STATUS_TICK_PD.cycles())
+            .unwrap();
+    }
+
+    #[task(priority = 9, binds = EXTI9_5, spawn=[queue_tx_frame], resources = [over_current_pin, current_now, config, faults, fault_log])]
+    fn exti9_5(mut cx: exti9_5::Context) {
+        defmt::trace!("Exti95");
+
+        // check overcurrent interrupt
+        let overcurrent = cx.resources.over_current_pin.lock(|pin| {
+            let pending = pin.check_interrupt();
+            pin.clear_interrupt_pending_bit();
+            pending
+        });
+        if !overcurrent {
+            return;
+        }
+        defmt::debug!("Overcurrent interrupt");
+
+        // the driver chops at the pwm frequency, so only the first edge since a clear is reported.
+        // `motor_update` drops the active bit once the pin goes high again
+        let (latched, faults) = cx.resources.faults.lock(|f| {
+            let latched = f.set_active(Fault::Overcurrent, true);
+            (latched, *f)
+        });
+        if !latched {
+            return;
+        }
+        cx.resources
+            .fault_log
+            .lock(|log| fault_log::push(log, FaultEvent::Overcurrent));
+
+        // get nesessary variables
+        let current_now = cx.resources.current_now.lock(|c| *c);
+        let current_limit = cx.resources.config.lock(|c| c.current_limit) as f32;
+
+        // push overcurrent frame
+        let _ = cx
+            .spawn
+            .queue_tx_frame(OutgoingFrame::Overcurrent {
+                current_now,
+                current_limit,
+            })
+            .unwrap_or_else(|_| defmt::warn!("Could not queue Frame"));
+        let _ = cx
+            .spawn
+            .queue_tx_frame(OutgoingFrame::Faults {
+                active: faults.active(),
+                sticky: faults.sticky(),
+            })
+            .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+    }
+
+    /// motor update periodic task
+    /// this runs at a high rate
+    /// we set duty cycles and current limit here
+    #[task(priority = 10, schedule = [motor_update], spawn = [queue_tx_frame], resources = [last_heartbeat, motor_low, motor_high, motor_current_limit, setpoint, leader_duty, last_leader_update, voltage_setpoint, bus_voltage, config, applied_duty, output_state, control_mode, current_setpoint, current_pid, current_now, encoder, position_now, velocity_now, velocity_setpoint, velocity_pid, position_setpoint, position_pid, ramp, smart_current_limit, thermal, faults, fault_pin, over_current_pin, sleep_pin, fault_log, heartbeat_lost, motor_alive])]
+    fn motor_update(cx: motor_update::Context) {
+        defmt::trace!("MotorUpdate");
+        fault_log::UPTIME_MS.fetch_add(MOTOR_UPDATE_MS, core::sync::atomic::Ordering::Relaxed);
+        // set pwm signals for setpoint and current limit
+        let last_heartbeat = *cx.resources.last_heartbeat;
+
+        let motor_low = cx.resources.motor_low;
+        let motor_high = cx.resources.motor_high;
+        let motor_current_limit = cx.resources.motor_current_limit;
+
+        let current_pid = cx.resources.current_pid;
+        let velocity_pid = cx.resources.velocity_pid;
+        let position_pid = cx.resources.position_pid;
+        let ramp = cx.resources.ramp;
+        let smart_current_limit = cx.resources.smart_current_limit;
+        let encoder = cx.resources.encoder;
+        let faults = cx.resources.faults;
+
+        let config = *cx.resources.config;
+        let inverted = config.inverted;
+        let idle_mode = config.idle_mode;
+        let current_limit = config.current_limit;
+        let current_now = *cx.resources.current_now;
+        let control_mode = *cx.resources.control_mode;
+        let inner_loop = config.position_inner_loop;
+
+        // sample the encoder. Sensor readings are inverted along with the output
+        // so the closed loops see the same direction they are commanding
+        encoder.update(MOTOR_UPDATE_DT);
+        let sensor_sign = if inverted { -1.0 } else { 1.0 };
+        let cpr = config.encoder_cpr as f32;
+        let position_now = sensor_sign * encoder.position() as f32 / cpr;
+        let velocity_now = sensor_sign * encoder.velocity() * 60.0 / cpr;
+        *cx.resources.position_now = position_now;
+        *cx.resources.velocity_now = velocity_now;
+
+        // sample the fault lines, both are active low with external pullups.
+        // The overcurrent edge is latched by `exti9_5`, here we only see it end
+        let driver_fault = cx.resources.fault_pin.is_low().unwrap();
+        if faults.set_active(Fault::DriverFault, driver_fault) {
+            defmt::warn!("Motor driver fault");
+            fault_log::push(cx.resources.fault_log, FaultEvent::DriverFault);
+            let _ = cx
+                .spawn
+                .queue_tx_frame(OutgoingFrame::Error(ErrorCode::MotorDriverFault))
+                .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+            let _ = cx
+                .spawn
+                .queue_tx_frame(OutgoingFrame::Faults {
+                    active: faults.active(),
+                    sticky: faults.sticky(),
+                })
+                .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+        }
+        if cx.resources.over_current_pin.is_high().unwrap() {
+            faults.set_active(Fault::Overcurrent, false);
+        }
+        let thermal = cx.resources.thermal;
+        if faults.set_active(Fault::Overheat, thermal.derating(&config) < 1.0) {
+            defmt::warn!("Motor overheating, derating current");
+            fault_log::push(cx.resources.fault_log, FaultEvent::Overheat);
+            let _ = cx
+                .spawn
+                .queue_tx_frame(OutgoingFrame::Faults {
+                    active: faults.active(),
+                    sticky: faults.sticky(),
+                })
+                .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+        }
+
+        // a follower mirrors its leader, and stops once the leader goes quiet.
+        // Only losing a leader we had is a fault, a follower may boot before its leader
+        let following = config.leader().is_some();
+        if !following {
+            *cx.resources.last_leader_update = None;
+        }
+        let last_leader_update = *cx.resources.last_leader_update;
+        let leader_timed_out = last_leader_update
+            .map(|last| last.elapsed() > millis(config.leader_timeout).cycles())
+            .unwrap_or(false);
+        let leader_lost = following && (last_leader_update.is_none() || leader_timed_out);
+        if faults.set_active(Fault::LeaderLoss, following && leader_timed_out) {
+            defmt::warn!("Leader lost");
+            fault_log::push(cx.resources.fault_log, FaultEvent::LeaderLoss);
+            let _ = cx
+                .spawn
+                .queue_tx_frame(OutgoingFrame::Faults {
+                    active: faults.active(),
+                    sticky: faults.sticky(),
+                })
+                .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+        }
+        let fault_action = faults.action(&config);
+        let inhibited = fault_action != FaultAction::Warn;
+
+        // reset every loop that isnt in control of the output this cycle.
+        // In position mode the selected inner loop stays active.
+        // While a fault holds the output, no loop is in control
+        let (current_active, velocity_active) = match control_mode {
+            _ if inhibited || following => (false, false),
+            ControlMode::DutyCycle | ControlMode::Voltage => (false, false),
+            ControlMode::Current => (true, false),
+            ControlMode::Velocity => (false, true),
+            ControlMode::Position => (
+                inner_loop == PositionInnerLoop::Current,
+                inner_loop == PositionInnerLoop::Velocity,
+            ),
+        };
+        if !current_active {
+            current_pid.reset();
+        }
+        if !velocity_active {
+            velocity_pid.reset();
+        }
+        if inhibited || following || control_mode != ControlMode::Position {
+            position_pid.reset();
+        }
+
+        // the leader already ramped and inverted its output, `inverted` flips it again for us
+        let setpoint = match control_mode {
+            _ if inhibited || leader_lost => 0,
+            _ if following => *cx.resources.leader_duty,
+            ControlMode::DutyCycle => ramp.update(
+                *cx.resources.setpoint,
+                config.ramp_up,
+                config.ramp_down,
+                MOTOR_UPDATE_DT,
+            ),
+            // compensated before the ramp, so a sagging bus does not make the ramp jump
+            ControlMode::Voltage => ramp.update(
+                voltage_duty(*cx.resources.voltage_setpoint, *cx.resources.bus_voltage),
+                config.ramp_up,
+                config.ramp_down,
+                MOTOR_UPDATE_DT,
+            ),
+            ControlMode::Current => current_loop(
+                current_pid,
+                &config.current_gains,
+                *cx.resources.current_setpoint,
+                current_now,
+                current_limit,
+            ),
+            ControlMode::Velocity => velocity_loop(
+                velocity_pid,
+                &config.velocity_gains,
+                *cx.resources.velocity_setpoint,
+                velocity_now,
+            ),
+            ControlMode::Position => {
+                let limit = config.position_output_limit;
+                position_pid.set_output_limits(-limit, limit);
+
+                // inside the tolerance band the error is treated as zero,
+                // so the integrator holds and the output settles
+                let error = *cx.resources.position_setpoint - position_now;
+                let error = if error.abs() < config.position_tolerance {
+                    0.0
+                } else {
+                    error
+                };
+
+                let output = position_pid.update(&config.position_gains, error, MOTOR_UPDATE_DT);
+
+                match inner_loop {
+                    PositionInnerLoop::Velocity => {
+                        velocity_loop(velocity_pid, &config.velocity_gains, output, velocity_now)
+                    }
+                    PositionInnerLoop::Current => current_loop(
+                        current_pid,
+                        &config.current_gains,
+                        output,
+                        current_now,
+                        current_limit,
+                    ),
+                }
+            }
+        };
+        // only the open loop setpoints are ramped, the closed loops shape their own output.
+        // Following them lets a switch to an open loop mode ramp from where the output is
+        let open_loop =
+            !following && matches!(control_mode, ControlMode::DutyCycle | ControlMode::Voltage);
+        if inhibited || !open_loop {
+            ramp.reset(setpoint);
+        }
+        // scale back while the measured current is over the software limit,
+        // derated as the motor heats up.
+        // The chopper still cuts in at `current_limit`, whichever is lower wins
+        let setpoint = if inhibited {
+            smart_current_limit.reset();
+            setpoint
+        } else {
+            let limit = thermal.limit(&config, SmartCurrentLimit::limit(&config, velocity_now));
+            smart_current_limit.update(limit, setpoint, current_now, MOTOR_UPDATE_DT)
+        };
+        let setpoint = setpoint * (if inverted { -1 } else { 1 });
+
+        let max_duty = motor_low.get_max_duty() as u16;
+
+        // followers need heartbeats too, so they stop even if the host only loses the leader.
+        // The motor stays stopped until the first heartbeat, but only losing one we had is a fault
+        let heartbeat_timeout = config.heartbeat_timeout();
+        let heartbeat_timed_out = match (heartbeat_timeout, last_heartbeat) {
+            (Some(timeout_ms), Some(last)) => last.elapsed() > millis(timeout_ms).cycles(),
+            _ => false,
+        };
+        let heartbeat_lost =
+            heartbeat_timeout.is_some() && (last_heartbeat.is_none() || heartbeat_timed_out);
+        if faults.set_active(Fault::HeartbeatLoss, heartbeat_timed_out) {
+            defmt::warn!("Heartbeat lost");
+            fault_log::push(cx.resources.fault_log, FaultEvent::HeartbeatLoss);
+            // the host that sent the last command is gone. Drop it, so the motor does not
+            // start again on its own once heartbeats return, and the host has to send a new one.
+            // A position setpoint holds where we are instead
+            *cx.resources.setpoint = 0;
+            *cx.resources.current_setpoint = 0.0;
+            *cx.resources.velocity_setpoint = 0.0;
+            *cx.resources.voltage_setpoint = 0.0;
+            *cx.resources.position_setpoint = position_now;
+            let _ = cx
+                .spawn
+                .queue_tx_frame(OutgoingFrame::Faults {
+                    active: faults.active(),
+                    sticky: faults.sticky(),
+                })
+                .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+        }
+        *cx.resources.heartbeat_lost = heartbeat_lost;
+        // the output is idle, so ramp up again once the heartbeat is back
+        if heartbeat_lost {
+            ramp.reset(0);
+        }
+        let mut stop = heartbeat_lost;
+
+        if setpoint.abs() < DEFAULT_MOTOR_DEADBAND {
+            stop = true;
+        };
+
+        // the sleeping driver leaves its outputs floating, so the motor coasts
+        let idle_mode = match fault_action {
+            FaultAction::Warn => idle_mode,
+            FaultAction::Coast | FaultAction::Disable => IdleMode::Coast,
+            FaultAction::Brake => IdleMode::Brake,
+        };
+        let sleep_pin = cx.resources.sleep_pin;
+        if fault_action == FaultAction::Disable {
+            sleep_pin.set_low().unwrap();
+        } else {
+            sleep_pin.set_high().unwrap();
+        }
+
+        *cx.resources.applied_duty = if stop { 0 } else { setpoint };
+        *cx.resources.output_state = if fault_action == FaultAction::Disable {
+            OutputState::Sleep
+        } else if !stop {
+            OutputState::Drive
+        } else if idle_mode == IdleMode::Coast {
+            OutputState::Coast
+        } else {
+            OutputState::Brake
+        };
+
+        // make sure this works
+        if !stop {
+            let internal_set = setpoint.unsigned_abs();
+            if setpoint >= 0 {
+                motor_low.set_duty(0);
+                motor_high.set_duty(internal_set);
+            } else {
+                motor_low.set_duty(internal_set);
+                motor_high.set_duty(0);
+            }
+        } else {
+            let internal_set = if idle_mode == IdleMode::Coast {
+                0
+            } else {
+                max_duty
+            };
+            motor_low.set_duty(internal_set);
+            motor_high.set_duty(internal_set);
+        }
+
+        // set current limit
+        // I wish we could avoid using floating point here
+        // this is from the datasheet
+        let vref =
+            ((current_limit as f32 * AMP_GAIN * R_SENSE_VAL) + V_OFF) * CURRENT_EXTERNAL_SCALE;
+
+        let pwm_val = motor_current_limit.get_max_duty() as f32 * (vref / 3.3);
+
+        motor_current_limit.set_duty(pwm_val as u16);
+
+        *cx.resources.motor_alive = true;
+
+        // schedule this task again
+        cx.schedule
+            .motor_update(Instant::now() + MOTOR_UPDATE_PD.cycles())
+            .unwrap();
+    }
+
+    #[task(priority = 10, binds = DMA1_CHANNEL1, resources=[adc_buf, current_now, bus_voltage, mcu_temperature, config, thermal, last_adc])]
+    fn handle_adc(cx: handle_adc::Context) {
+        defmt::trace!("Reading adc");
+
+        let mut val: f32 = 0.0;
+        let mut bus_val: f32 = 0.0;
+        let mut temp_val: f32 = 0.0;
+
+        // samples go round the channels in `AdcPins`
+        const SAMPLES: f32 = (ADC_BUF_LEN / ADC_CHANNELS) as f32;
+        match cx.resources.adc_buf.peek(|b, _| {
+            let (current_sum, bus_sum, temp_sum) = b
+                .chunks_exact(ADC_CHANNELS)
+                .fold((0_u32, 0_u32, 0_u32), |(c, v, t), s| {
+                    (c + s[0] as u32, v + s[1] as u32, t + s[2] as u32)
+                });
+            val = current_sum as f32 / SAMPLES;
+            bus_val = bus_sum as f32 / SAMPLES;
+            temp_val = temp_sum as f32 / SAMPLES;
+        }) {
+            Ok(_) => {}
+            Err(_) => {
+                defmt::warn!("DMA overrun");
+                // let adc = unsafe { &*stm32f1xx_hal::pac::ADC1::ptr() };
+                return;
+            }
+        }
+
+        defmt::trace!("Actually reading dma");
+
+        // We use floats here because the accuracy matters to an extent
+        let volts = val * ADC_VOLTS_PER_COUNT;
+
+        // this comes from the data sheet
+        let current: f32 = ((volts - V_OFF) / (R_SENSE_VAL * AMP_GAIN)) / CURRENT_EXTERNAL_SCALE;
+
+        defmt::info!("Motor current: {=f32}", current);
+
+        *cx.resources.current_now = current;
+        *cx.resources.bus_voltage = bus_val * ADC_VOLTS_PER_COUNT * BUS_VOLTAGE_SCALE;
+        *cx.resources.mcu_temperature =
+            (MCU_TEMP_V25 - temp_val * ADC_VOLTS_PER_COUNT) / MCU_TEMP_SLOPE + 25.0;
+
+        // half buffers take a fixed time to fill, but measuring it saves working out the adc clock
+        let now = Instant::now();
+        if let Some(last) = cx.resources.last_adc.replace(now) {
+            let dt = now.duration_since(last).as_cycles() as f32 / SYS_CLOCK_HZ as f32;
+            cx.resources
+                .thermal
+                .update(cx.resources.config, current, dt);
+        }
+
+        // NOTE:
+        // we should really do something that takes the vref into account
+        // such as: let x = adc_val * 1200 / adc.read_vref();
+        // but i dont know how to get the vref value through the dma
+        // for now we will just do it the dumb way
+    }
+
+    #[task(priority = 5, capacity = 32, resources=[can_tx_queue, can_id])]
+    fn queue_tx_frame(cx: queue_tx_frame::Context, frame: OutgoingFrame) {
+        let mut queue = cx.resources.can_tx_queue;
+        let id = *cx.resources.can_id;
+
+        match queue.push(allocate_tx_frame(can_types::to_bxcan(
+            &frame.into_with_id(id),
+        ))) {
+            Ok(..) => {}
+            Err(_) => {
+                defmt::warn!("Can Tx queue is out of space");
+            }
+        }
+    }
+
+    #[task(priority = 5, capacity = 32, resources=[can_tx_queue, last_can_rx, config, setpoint, leader_duty, last_leader_update, last_heartbeat, control_mode, current_setpoint, velocity_setpoint, position_setpoint, voltage_setpoint, applied_duty, faults, fault_log], spawn=[save_config, queue_tx_frame] )]
+    fn handle_rx_frame(mut cx: handle_rx_frame::Context, frame: Frame) {
+        use bmc_protocol::IncomingFrame;
+        use bmc_protocol::IncomingFrame::*;
+        use core::convert::TryFrom;
+
+        let last_rx = cx.resources.last_can_rx;
+        let frame = can_types::from_bxcan(&frame);
+
+        match IncomingFrame::try_from(frame) {
+            Ok(Setpoint(setpoint)) => {
+                defmt::info!("Setting setpoint to {=i16}", setpoint);
+                cx.resources.setpoint.lock(|s| *s = setpoint);
+                cx.resources
+                    .control_mode
+                    .lock(|m| *m = ControlMode::DutyCycle);
+            }
+            Ok(HeartBeat) => {
+                defmt::info!("Heartbeat...");
+                cx.resources
+                    .last_heartbeat
+                    .lock(|lh| *lh = Some(Instant::now()));
+            }
+            Ok(GroupSetpoint(setpoints)) => {
+                let group = bmc_protocol::board_id(frame.id());
+                let setpoint = cx
+                    .resources
+                    .config
+                    .lock(|c| c.group().and_then(|g| g.pick(group, &setpoints)));
+                // every group setpoint passes our filters, most are for other groups
+                if let Some(setpoint) = setpoint {
+                    cx.resources.setpoint.lock(|s| *s = setpoint);
+                    cx.resources
+                        .control_mode
+                        .lock(|m| *m = ControlMode::DutyCycle);
+                }
+            }
+            Ok(SetParam(id, value)) => {
+                defmt::info!("Setting {:?} to {:?}", id, value);
+                let value = cx.resources.config.lock(|c| {
+                    if let Err(e) = c.set(id, value) {
+                        defmt::warn!("Rejected {:?}: {:?}", id, e);
+                    }
+                    c.get(id)
+                });
+                // echo what we hold now, so the host can tell if the value was taken
+                let _ = cx
+                    .spawn
+                    .queue_tx_frame(OutgoingFrame::ParamValue(id, value))
+                    .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+            }
+            Ok(GetParam(id)) => {
+                let value = cx.resources.config.lock(|c| c.get(id));
+                let _ = cx
+                    .spawn
+                    .queue_tx_frame(OutgoingFrame::ParamValue(id, value))
+                    .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+            }
+            Ok(Stop) => {
+                defmt::info!("Stopping motor (setpoint = 0)");
+                cx.resources.setpoint.lock(|s| *s = 0);
+                cx.resources
+                    .control_mode
+                    .lock(|m| *m = ControlMode::DutyCycle);
+            }
+            Ok(SetCurrentSetpoint(amps)) => {
+                defmt::info!("Setting current setpoint to {=f32} amps", amps);
+                cx.resources.current_setpoint.lock(|cs| *cs = amps);
+                cx.resources
+                    .control_mode
+                    .lock(|m| *m = ControlMode::Current);
+            }
+            Ok(SetVelocitySetpoint(rpm)) => {
+                defmt::info!("Setting velocity setpoint to {=f32} rpm", rpm);
+                cx.resources.velocity_setpoint.lock(|vs| *vs = rpm);
+                cx.resources
+                    .control_mode
+                    .lock(|m| *m = ControlMode::Velocity);
+            }
+            Ok(SetVoltageSetpoint(volts)) => {
+                defmt::info!("Setting voltage setpoint to {=f32} volts", volts);
+                cx.resources.voltage_setpoint.lock(|vs| *vs = volts);
+                cx.resources
+                    .control_mode
+                    .lock(|m| *m = ControlMode::Voltage);
+            }
+            Ok(SetPositionSetpoint(rotations)) => {
+                defmt::info!("Setting position setpoint to {=f32} rotations", rotations);
+                cx.resources.position_setpoint.lock(|ps| *ps = rotations);
+                cx.resources
+                    .control_mode
+                    .lock(|m| *m = ControlMode::Position);
+            }
+            Ok(SaveConfig) => {
+                // erasing flash stalls the cpu, so dont let that happen with the motor running
+                if cx.resources.applied_duty.lock(|ad| *ad) != 0 {
+                    defmt::warn!("Refusing to save config while the motor is running");
+                } else {
+                    defmt::info!("Saving config");
+                    cx.spawn.save_config().unwrap();
+                }
+            }
+            Ok(EnterBootloader) => {
+                if cx.resources.applied_duty.lock(|ad| *ad) != 0 {
+                    defmt::warn!("Refusing to enter the bootloader while the motor is running");
+                } else {
+                    defmt::info!("Entering bootloader");
+                    bootloader::enter();
+                }
+            }
+            Ok(RestoreDefaults) => {
+                // new limits, gains or inversion must not reach a running motor either
+                if cx.resources.applied_duty.lock(|ad| *ad) != 0 {
+                    defmt::warn!("Refusing to restore defaults while the motor is running");
+                } else {
+                    defmt::info!("Restoring default config");
+                    cx.resources.config.lock(|c| *c = Config::default());
+                    cx.spawn.save_config().unwrap();
+                }
+            }
+            Ok(ClearFaults) => {
+                // the output comes back with whatever setpoint it had before the fault
+                let faults = cx.resources.faults.lock(|f| {
+                    f.clear();
+                    *f
+                });
+                defmt::info!("Clearing faults, still active: {:?}", faults.active());
+                let _ = cx
+                    .spawn
+                    .queue_tx_frame(OutgoingFrame::Faults {
+                        active: faults.active(),
+                        sticky: faults.sticky(),
+                    })
+                    .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+            }
+            Ok(GetFaultLog(index)) => {
+                let record = cx.resources.fault_log.lock(|log| log.get(index as usize));
+                let _ = cx
+                    .spawn
+                    .queue_tx_frame(OutgoingFrame::FaultLogEntry { index, record })
+                    .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+            }
+            Ok(ClearFaultLog) => {
+                defmt::info!("Clearing fault log");
+                cx.resources.fault_log.lock(|log| log.clear());
+            }
+            Ok(QueryVersion) => {
+                let _ = cx
+                    .spawn
+                    .queue_tx_frame(OutgoingFrame::Version {
+                        protocol: bmc_protocol::PROTOCOL_VERSION,
+                        firmware: firmware_version(),
+                    })
+                    .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+            }
+            Err(e) => match OutgoingFrame::try_from(frame) {
+                // every board's updates pass our filters, only our leader's count
+                Ok(OutgoingFrame::Update { duty_now, .. }) => {
+                    let from = bmc_protocol::board_id(frame.id());
+                    if cx.resources.config.lock(|c| c.leader()) == Some(from) {
+                        cx.resources.leader_duty.lock(|ld| *ld = duty_now);
+                        cx.resources
+                            .last_leader_update
+                            .lock(|lu| *lu = Some(Instant::now()));
+                    }
+                    // another board's status says nothing about our link to the host
+                    return;
+                }
+                // hosts probe with frames newer firmware understands, so this must not be fatal
+                _ => defmt::warn!("Could not decode frame: {:?}", e),
+            },
+        };
+
+        *last_rx = Some(Instant::now());
+        rtic::pend(Interrupt::USB_HP_CAN_TX);
+    }
+
+    /// Writes the current config to flash.
+    /// This runs at the lowest priority since erasing the page takes a while
+    #[task(priority = 1, resources = [config, flash])]
+    fn save_config(mut cx: save_config::Context) {
+        let config = cx.resources.config.lock(|c| *c);
+        let mut writer = cx
+            .resources
+            .flash
+            .writer(flash::SectorSize::Sz1K, flash::FlashSize::Sz64K);
+
+        match config::save(&config, &mut writer) {
+            Ok(()) => defmt::info!("Config saved"),
+            Err(e) => defmt::error!("Could not save config: {:?}", e),
+        }
+    }
+
+    #[task(priority=5, binds = USB_LP_CAN_RX0, resources=[can_rx], spawn=[handle_rx_frame])]
+    fn can_rx0(cx: can_rx0::Context) {
+        let rx = cx.resources.can_rx;
+
+        loop {
+            match rx.receive() {
+                Ok(frame) => {
+                    cx.spawn.handle_rx_frame(frame).unwrap();
+                }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => {}
+            }
+        }
+    }
+
+    #[task(priority=5, binds = CAN_RX1)]
+    fn can_rx1(_: can_rx1::Context) {
+        rtic::pend(Interrupt::USB_LP_CAN_RX0);
+    }
+
+    #[task(priority = 4, schedule=[can_tx], resources = [can_tx, can_tx_queue, last_can_rx, can_timed_out, can_state, fault_log, can_alive])]
+    fn can_tx(mut cx: can_tx::Context) {
+        let tx = cx.resources.can_tx;
+        let mut tx_queue = cx.resources.can_tx_queue;
+        let mut last_rx = cx.resources.last_can_rx;
+
+        let last = last_rx.lock(|rx| *rx);
+        let can_ok = if let Some(t) = last {
+            !(t.elapsed() > CAN_TIMEOUT.cycles())
+        } else {
+            false
+        };
+
+        // a bus we never heard from has not timed out
+        let timed_out = last.is_some() && !can_ok;
+        if timed_out && !*cx.resources.can_timed_out {
+            defmt::warn!("Canbus timed out");
+            cx.resources
+                .fault_log
+                .lock(|log| fault_log::push(log, FaultEvent::CanTimeout));
+        }
+        *cx.resources.can_timed_out = timed_out;
+
+        // the peripheral only interrupts when the error state gets worse,
+        // so we have `can_sce` look again until it is back to normal
+        if *cx.resources.can_state != CanBusState::ErrorActive {
+            rtic::pend(Interrupt::CAN_SCE);
+        }
+
+        tx.clear_interrupt_flags();
+
+        if !can_ok {
+            defmt::debug!("Canbus timeout, waiting for recieved frame before tx");
+        }
+
+        tx_queue.lock(|tx_queue| {
+            while let Some(frame) = tx_queue.peek() {
+                match tx.transmit(&frame.0) {
+                    Ok(None) => {
+                        use core::ops::Deref;
+                        let sent_frame = tx_queue.pop();
+                        defmt::info!("Sent Frame: {:?}", sent_frame.unwrap().deref().0);
+                    }
+                    Ok(Some(pending_frame)) => {
+                        tx_queue.pop();
+                        tx_queue.push(allocate_tx_frame(pending_frame)).unwrap();
+                    }
+                    Err(nb::Error::WouldBlock) => break,
+                    Err(nb::Error::Other(never)) => match never {},
+                }
+            }
+        });
+
+        *cx.resources.can_alive = true;
+
+        cx.schedule
+            .can_tx(Instant::now() + CAN_TX_PD.cycles())
+            .unwrap();
+    }
+
+    /// Feeds the watchdog, but only if `motor_update` and `can_tx` both ran since the last time.
+    /// If either stops, or something starves this task, the watchdog resets the board
+    /// and the motor stops with it
+    #[task(priority = 1, schedule = [feed_watchdog], resources = [watchdog, motor_alive, can_alive])]
+    fn feed_watchdog(mut cx: feed_watchdog::Context) {
+        let motor_alive = cx
+            .resources
+            .motor_alive
+            .lock(|alive| core::mem::replace(alive, false));
+        let can_alive = cx
+            .resources
+            .can_alive
+            .lock(|alive| core::mem::replace(alive, false));
+
+        if motor_alive && can_alive {
+            cx.resources.watchdog.feed();
+        } else {
+            defmt::error!(
+                "Not feeding the watchdog, motor update ran: {=bool}, can tx ran: {=bool}",
+                motor_alive,
+                can_alive
+            );
+        }
+
+        cx.schedule
+            .feed_watchdog(Instant::now() + WATCHDOG_FEED_PD.cycles())
+            .unwrap();
+    }
+
+    /// Tracks the error state of the can peripheral.
+    /// Reports a worse state with an `ErrorCode::CanError` frame, and schedules `recover_can` after bus off
+    #[task(priority = 4, binds = CAN_SCE, schedule = [recover_can], spawn = [queue_tx_frame], resources = [can_state, can_last_error, bus_off_count, last_bus_off, bus_off_backoff, fault_log])]
+    fn can_sce(mut cx: can_sce::Context) {
+        use core::convert::TryFrom;
+
+        // bxcan does not expose the error state once the peripheral is split.
+        // We only touch the error interrupt bits here, which the driver never uses
+        let can = unsafe { &*pac::CAN1::ptr() };
+        let esr = can.esr.read();
+        can.msr.write(|w| w.erri().set_bit());
+
+        let warning = esr.ewgf().bit_is_set();
+        let passive = esr.epvf().bit_is_set();
+        let bus_off = esr.boff().bit_is_set();
+
+        // the flags stay set while the counters are high, and every error would interrupt again.
+        // Mask the states we are in, `can_tx` has us look again until they clear
+        can.ier.modify(|_, w| {
+            w.ewgie()
+                .bit(!warning)
+                .epvie()
+                .bit(!passive)
+                .bofie()
+                .bit(!bus_off)
+        });
+
+        let state = if bus_off {
+            CanBusState::BusOff
+        } else if passive {
+            CanBusState::ErrorPassive
+        } else if warning {
+            CanBusState::ErrorWarning
+        } else {
+            CanBusState::ErrorActive
+        };
+        // 7 means no error since software wrote it, which we never do
+        *cx.resources.can_last_error =
+            CanLastError::try_from(esr.lec().bits()).unwrap_or(CanLastError::None);
+
+        let last_state = core::mem::replace(cx.resources.can_state, state);
+        if state == last_state {
+            return;
+        }
+        defmt::info!(
+            "Can error state {:?}, tx errors: {=u8}, rx errors: {=u8}",
+            state,
+            esr.tec().bits(),
+            esr.rec().bits()
+        );
+
+        // the frame waits in the queue until we are back on the bus
+        if state > last_state && state >= CanBusState::ErrorPassive {
+            let _ = cx
+                .spawn
+                .queue_tx_frame(OutgoingFrame::Error(ErrorCode::CanError))
+                .unwrap_or_else(|_| defmt::warn!("Could not queue frame"));
+        }
+
+        if state == CanBusState::BusOff {
+            defmt::warn!("Can bus off");
+            *cx.resources.bus_off_count = cx.resources.bus_off_count.saturating_add(1);
+            cx.resources
+                .fault_log
+                .lock(|log| fault_log::push(log, FaultEvent::CanBusOff));
+
+            // a bus that keeps failing gets longer and longer to settle
+            let now = Instant::now();
+            let backoff = cx.resources.bus_off_backoff;
+            *backoff = match *cx.resources.last_bus_off {
+                Some(t) if now.duration_since(t) < BUS_OFF_BACKOFF_RESET_PD.cycles() => {
+                    (*backoff * 2).min(BUS_OFF_BACKOFF_MAX_PD)
+                }
+                _ => BUS_OFF_BACKOFF_MIN_PD,
+            };
+            *cx.resources.last_bus_off = Some(now);
+
+            cx.schedule
+                .recover_can(now + backoff.cycles())
+                .unwrap_or_else(|_| defmt::warn!("Could not schedule can recovery"));
+        }
+    }
+
+    /// Brings the can peripheral back from bus off. It rejoins the bus once it has seen
+    /// 128 idle periods, and `can_sce` sees the state improve
+    #[task(priority = 4, schedule = [recover_can], resources = [bus_off_backoff])]
+    fn recover_can(cx: recover_can::Context) {
+        // bxcan cannot do this once the peripheral is split.
+        // Entering and leaving initialization mode keeps the bit timing and the filters
+        let can = unsafe { &*pac::CAN1::ptr() };
+        if can.esr.read().boff().bit_is_clear() {
+            return;
+        }
+
+        defmt::info!("Recovering from can bus off");
+        can.mcr.modify(|_, w| w.inrq().set_bit());
+        let start = Instant::now();
+        while can.msr.read().inak().bit_is_clear() {
+            if start.elapsed() > CAN_INIT_TIMEOUT_PD.cycles() {
+                break;
+            }
+        }
+        let entered = can.msr.read().inak().bit_is_set();
+        can.mcr.modify(|_, w| w.inrq().clear_bit());
+
+        if !entered {
+            defmt::warn!("Can peripheral did not enter initialization, trying again");
+            cx.schedule
+                .recover_can(Instant::now() + cx.resources.bus_off_backoff.cycles())
+                .unwrap_or_else(|_| defmt::warn!("Could not schedule can recovery"));
+        }
+    }
+
+    #[task(priority = 5, resources = [status1, status2, heartbeat_lost], schedule=[led_update])]
+    fn led_update(mut cx: led_update::Context) {
+        defmt::trace!("Updating leds");
+        let status1 = cx.resources.status1;
+        let status2 = cx.resources.status2;
+
+        // a missing heartbeat is the usual reason the motor does not move
+        if cx.resources.heartbeat_lost.lock(|lost| *lost) {
+            status2.flash_fast();
+        } else {
+            status2.flash_slow();
+        }
+
+        status1.update();
+        status2.update();
+
+        cx.schedule
+            .led_update(Instant::now() + LED_UPDATE_PD.cycles())
+            .unwrap();
+    }
+
+    #[allow(non_snake_case)]
+    extern "C" {
+        fn USART1();
+        fn USART2();
+
+        fn UART4();
+        fn UART5();
+
+        fn ADC3();
+
+        fn SPI2();
+        fn SPI3();
+
+        fn EXTI1();
+        fn EXTI2();
+    }
+};
+
+#[panic_handler]
+#[inline(never)]
+fn my_panic(info: &core::panic::PanicInfo) -> ! {
+    use defmt::Debug2Format;
+
+    fault_log::push_panic();
+
+    defmt::error!(
+        "Panic: \"{:?}\" \nin file {=str} at line {=u32}",
+        Debug2Format(info.message().unwrap()),
+        info.location().unwrap().file(),
+        info.location().unwrap().line()
+    );
+    // the watchdog resets us from here
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+#[defmt::panic_handler]
+fn defmt_panic() -> ! {
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+#[lang = "eh_personality"]
+extern "C" fn eh_personality() {}
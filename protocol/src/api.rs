@@ -0,0 +1,72 @@
+//! Api class and index of every frame.
+//!
+//! Classes 0x0-0x7 are sent to a board, classes 0x8-0xF by a board. Lower classes win
+//! arbitration, so control frames go first.
+
+/// Setpoints and stopping
+pub const CLASS_CONTROL: u8 = 0x0;
+pub const SETPOINT: u8 = 0x0;
+pub const CURRENT_SETPOINT: u8 = 0x1;
+pub const VELOCITY_SETPOINT: u8 = 0x2;
+pub const POSITION_SETPOINT: u8 = 0x3;
+pub const STOP: u8 = 0x4;
+/// Sent to a group number instead of a board id
+pub const GROUP_SETPOINT: u8 = 0x5;
+/// Open loop output in volts, compensated for the bus voltage
+pub const VOLTAGE_SETPOINT: u8 = 0x6;
+
+/// Parameters, which are kept in flash by `SAVE_CONFIG`
+pub const CLASS_CONFIG: u8 = 0x1;
+pub const SET_PARAM: u8 = 0x0;
+pub const GET_PARAM: u8 = 0x1;
+pub const SAVE_CONFIG: u8 = 0x2;
+pub const RESTORE_DEFAULTS: u8 = 0x3;
+
+/// Housekeeping
+pub const CLASS_SYSTEM: u8 = 0x2;
+pub const HEARTBEAT: u8 = 0x0;
+pub const QUERY_VERSION: u8 = 0x1;
+pub const ENTER_BOOTLOADER: u8 = 0x2;
+pub const CLEAR_FAULTS: u8 = 0x3;
+pub const GET_FAULT_LOG: u8 = 0x4;
+pub const CLEAR_FAULT_LOG: u8 = 0x5;
+
+/// Firmware updates, only the bootloader answers these. See `BootCommand`
+pub const CLASS_BOOTLOADER: u8 = 0x3;
+pub const BOOT_STAY: u8 = 0x0;
+pub const BEGIN_UPDATE: u8 = 0x1;
+pub const WRITE_DATA: u8 = 0x2;
+pub const FINISH_UPDATE: u8 = 0x3;
+pub const BOOT_APP: u8 = 0x4;
+
+/// Periodic frames sent by a board
+pub const CLASS_STATUS: u8 = 0x8;
+pub const UPDATE: u8 = 0x0;
+pub const ENCODER: u8 = 0x1;
+pub const VERSION: u8 = 0x2;
+/// Answer to `SET_PARAM` and `GET_PARAM`
+pub const PARAM_VALUE: u8 = 0x3;
+pub const MOTOR_TEMPERATURE: u8 = 0x4;
+pub const BUS_VOLTAGE: u8 = 0x5;
+/// Fault bits, mcu temperature and uptime
+pub const SYSTEM_STATUS: u8 = 0x6;
+/// Bus health as the board sees it
+pub const CAN_STATUS: u8 = 0x7;
+/// What actually reaches the motor
+pub const OUTPUT: u8 = 0x8;
+/// Sent once after every reset
+pub const BOOT: u8 = 0x9;
+
+/// Faults reported by a board
+pub const CLASS_FAULT: u8 = 0x9;
+pub const ERROR: u8 = 0x0;
+pub const OVERCURRENT: u8 = 0x1;
+/// Active and sticky fault bits
+pub const FAULTS: u8 = 0x2;
+/// Answer to `GET_FAULT_LOG`
+pub const FAULT_LOG_ENTRY: u8 = 0x3;
+
+/// Answers of the bootloader
+pub const CLASS_BOOTLOADER_STATUS: u8 = 0xA;
+/// Answer to every bootloader command, see `BootStatus`
+pub const BOOT_STATUS: u8 = 0x0;
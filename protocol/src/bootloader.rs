@@ -0,0 +1,164 @@
+//! Frames of the CAN bootloader.
+//!
+//! The bootloader uses the same 29 bit ids as the application, in their own api classes,
+//! so a host talks to a board the same way whichever image is running. It only listens to
+//! its own board id, not to `BROADCAST`, since an update is always for a single board.
+//!
+//! The application's `IncomingFrame::EnterBootloader` is also taken as `BootCommand::Stay`,
+//! so a host can send it without knowing which image is running.
+//! Every command is answered with a `BootStatus`.
+
+use core::convert::{TryFrom, TryInto};
+
+use crate::api::*;
+use crate::frame::{api, with_api, CanFrame, Id};
+use crate::{FrameConversionError, IntoWithId};
+
+/// Frames sent to a board that runs the bootloader
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootCommand {
+    /// Stay in the bootloader instead of booting the application
+    Stay,
+    /// Erase room for an image of `len` bytes
+    BeginUpdate { len: u32 },
+    /// One word of the image. Words have to arrive in order
+    WriteData { index: u16, word: [u8; 4] },
+    /// Check the image against the crc32 of all of it, see `crc32`
+    FinishUpdate { crc: u32 },
+    /// Boot the application, if it is valid
+    Boot,
+}
+
+/// Answer of the bootloader to every `BootCommand`
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootStatus {
+    /// We are staying in the bootloader. The value is the state of the application image:
+    /// 0 valid, 1 without a descriptor but plausible, 2 missing or corrupt
+    Ready(u8),
+    /// The application region was erased and is ready for data
+    Erased,
+    /// The value is the index of the next word we expect
+    Ack(u16),
+    /// The image crc matched and the image is marked bootable
+    ImageValid,
+    /// The image crc did not match
+    ImageInvalid,
+    /// A command failed
+    Error,
+}
+
+impl TryFrom<CanFrame> for BootCommand {
+    type Error = FrameConversionError;
+    fn try_from(frame: CanFrame) -> Result<BootCommand, Self::Error> {
+        let dlc = frame.dlc();
+
+        let (class, index) = api(frame.id()).ok_or(FrameConversionError::InvalidIdFormat)?;
+        if frame.is_remote_frame() {
+            return Err(FrameConversionError::FrameType);
+        }
+
+        let data = frame.data();
+        match (class, index) {
+            (CLASS_BOOTLOADER, BOOT_STAY) => Ok(BootCommand::Stay),
+            (CLASS_SYSTEM, ENTER_BOOTLOADER) => Ok(BootCommand::Stay),
+            (CLASS_BOOTLOADER, BEGIN_UPDATE) => {
+                check_frame_size!(4, dlc);
+                Ok(BootCommand::BeginUpdate {
+                    len: u32::from_le_bytes(data[0..4].try_into().unwrap()),
+                })
+            }
+            (CLASS_BOOTLOADER, WRITE_DATA) => {
+                check_frame_size!(6, dlc);
+                Ok(BootCommand::WriteData {
+                    index: u16::from_le_bytes(data[0..2].try_into().unwrap()),
+                    word: data[2..6].try_into().unwrap(),
+                })
+            }
+            (CLASS_BOOTLOADER, FINISH_UPDATE) => {
+                check_frame_size!(4, dlc);
+                Ok(BootCommand::FinishUpdate {
+                    crc: u32::from_le_bytes(data[0..4].try_into().unwrap()),
+                })
+            }
+            (CLASS_BOOTLOADER, BOOT_APP) => Ok(BootCommand::Boot),
+            (class, index) => Err(FrameConversionError::InvalidApi { class, index }),
+        }
+    }
+}
+
+impl IntoWithId<CanFrame> for BootCommand {
+    fn into_with_id(self, id: Id) -> CanFrame {
+        let mut bytes = [0_u8; 8];
+        let (index, len) = match self {
+            BootCommand::Stay => (BOOT_STAY, 0),
+            BootCommand::BeginUpdate { len } => {
+                bytes[0..4].copy_from_slice(&len.to_le_bytes());
+                (BEGIN_UPDATE, 4)
+            }
+            BootCommand::WriteData { index, word } => {
+                bytes[0..2].copy_from_slice(&index.to_le_bytes());
+                bytes[2..6].copy_from_slice(&word);
+                (WRITE_DATA, 6)
+            }
+            BootCommand::FinishUpdate { crc } => {
+                bytes[0..4].copy_from_slice(&crc.to_le_bytes());
+                (FINISH_UPDATE, 4)
+            }
+            BootCommand::Boot => (BOOT_APP, 0),
+        };
+
+        CanFrame::new_data(with_api(id, CLASS_BOOTLOADER, index), &bytes[..len]).unwrap()
+    }
+}
+
+impl TryFrom<CanFrame> for BootStatus {
+    type Error = FrameConversionError;
+    fn try_from(frame: CanFrame) -> Result<BootStatus, FrameConversionError> {
+        let dlc = frame.dlc();
+
+        match api(frame.id()).ok_or(FrameConversionError::InvalidIdFormat)? {
+            (CLASS_BOOTLOADER_STATUS, BOOT_STATUS) => {}
+            (class, index) => return Err(FrameConversionError::InvalidApi { class, index }),
+        }
+        if frame.is_remote_frame() {
+            return Err(FrameConversionError::FrameType);
+        }
+
+        let data = frame.data();
+        check_frame_size!(5, dlc);
+        let value = u32::from_le_bytes(data[1..5].try_into().unwrap());
+        match data[0] {
+            0x0 => Ok(BootStatus::Ready(value as u8)),
+            0x1 => Ok(BootStatus::Erased),
+            0x2 => Ok(BootStatus::Ack(value as u16)),
+            0x3 => Ok(BootStatus::ImageValid),
+            0x4 => Ok(BootStatus::ImageInvalid),
+            0x5 => Ok(BootStatus::Error),
+            _ => Err(FrameConversionError::InvalidFrame(
+                "Unknown bootloader status",
+            )),
+        }
+    }
+}
+
+/// Encodes a status with the code in the first byte and a `u32` value after it
+impl IntoWithId<CanFrame> for BootStatus {
+    fn into_with_id(self, id: Id) -> CanFrame {
+        let (code, value) = match self {
+            BootStatus::Ready(state) => (0x0, state as u32),
+            BootStatus::Erased => (0x1, 0),
+            BootStatus::Ack(next) => (0x2, next as u32),
+            BootStatus::ImageValid => (0x3, 0),
+            BootStatus::ImageInvalid => (0x4, 0),
+            BootStatus::Error => (0x5, 0),
+        };
+
+        let mut bytes = [0_u8; 5];
+        bytes[0] = code;
+        bytes[1..5].copy_from_slice(&value.to_le_bytes());
+
+        CanFrame::new_data(with_api(id, CLASS_BOOTLOADER_STATUS, BOOT_STATUS), &bytes).unwrap()
+    }
+}
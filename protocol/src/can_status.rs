@@ -0,0 +1,68 @@
+use core::convert::TryFrom;
+
+/// Error state of a can controller, from its error counters. Later states are worse
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum CanBusState {
+    /// Both error counters are below 96
+    ErrorActive = 0,
+    /// An error counter reached 96, the bus is noisy
+    ErrorWarning = 1,
+    /// An error counter reached 128. The board still sends, but may no longer flag errors
+    ErrorPassive = 2,
+    /// The transmit error counter went past 255 and the board left the bus until it recovers
+    BusOff = 3,
+}
+
+impl TryFrom<u8> for CanBusState {
+    type Error = u8;
+
+    fn try_from(raw: u8) -> Result<Self, Self::Error> {
+        match raw {
+            0 => Ok(CanBusState::ErrorActive),
+            1 => Ok(CanBusState::ErrorWarning),
+            2 => Ok(CanBusState::ErrorPassive),
+            3 => Ok(CanBusState::BusOff),
+            _ => Err(raw),
+        }
+    }
+}
+
+/// Kind of the last error a can controller saw on the bus.
+/// The values are those of the LEC field of the bxcan error status register
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum CanLastError {
+    /// No error since the last frame went through
+    None = 0,
+    /// More than 5 equal bits in a row
+    Stuff = 1,
+    /// A fixed format part of a frame was wrong
+    Form = 2,
+    /// Nobody acknowledged a frame we sent, usually because we are alone on the bus
+    Acknowledge = 3,
+    /// We sent a recessive bit but read a dominant one
+    BitRecessive = 4,
+    /// We sent a dominant bit but read a recessive one
+    BitDominant = 5,
+    Crc = 6,
+}
+
+impl TryFrom<u8> for CanLastError {
+    type Error = u8;
+
+    fn try_from(raw: u8) -> Result<Self, Self::Error> {
+        match raw {
+            0 => Ok(CanLastError::None),
+            1 => Ok(CanLastError::Stuff),
+            2 => Ok(CanLastError::Form),
+            3 => Ok(CanLastError::Acknowledge),
+            4 => Ok(CanLastError::BitRecessive),
+            5 => Ok(CanLastError::BitDominant),
+            6 => Ok(CanLastError::Crc),
+            _ => Err(raw),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Config block, the layout a `Config` is kept in flash with.
+//!
+//! The firmware stores the block in the last page of flash, here it is only bytes,
+//! so the layout can be tested on a host. The block layout is:
+//!
+//! | offset      | size | field                                |
+//! |-------------|------|--------------------------------------|
+//! | 0           | 4    | magic (`CONFIG_MAGIC`)               |
+//! | 4           | 2    | version of the firmware that wrote it|
+//! | 6           | 2    | payload length in bytes              |
+//! | 8           | len  | payload                              |
+//! | 8 + len     | 4    | crc32 of everything before it        |
+//!
+//! All values are little endian.
+//!
+//! Since version 3 the payload is a list of parameters, each a `u16` id from
+//! `param` followed by its raw `u32` value. Parameters missing
+//! from the payload keep their default value, and ones this firmware does not
+//! know are skipped, so adding a parameter needs no migration. Versions 1 and 2
+//! held the fields in a fixed order and are still read. Blocks written by a
+//! newer layout than this one are ignored.
+
+use core::convert::TryFrom;
+
+use crate::param::PARAMS;
+use crate::{
+    crc32, Config, IdleMode, ParamId, ParamValue, PidGains, PositionInnerLoop, GROUP_SLOTS,
+};
+
+/// Marks the start of a valid config block
+pub const CONFIG_MAGIC: u32 = 0x4343_4D42; // "BMCC"
+
+/// Version of the config layout written by `encode_config`
+pub const CONFIG_VERSION: u16 = 3;
+
+const HEADER_LEN: usize = 8;
+const CRC_LEN: usize = 4;
+
+/// Bytes of one parameter in the payload: a `u16` id and a `u32` value
+const PARAM_LEN: usize = 6;
+
+/// Largest block `encode_config` writes
+pub const MAX_CONFIG_BLOCK_LEN: usize = HEADER_LEN + PARAMS.len() * PARAM_LEN + CRC_LEN;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigBlockError {
+    /// No block has been written yet, or the bytes hold something else
+    BadMagic,
+    /// The block was written by a newer firmware
+    UnknownVersion(u16),
+    /// The payload runs past the end of the bytes we have
+    BadLength(u16),
+    BadCrc,
+}
+
+/// Writes `config` as a block of the current version, and returns the length of the block
+pub fn encode_config(config: &Config, block: &mut [u8; MAX_CONFIG_BLOCK_LEN]) -> usize {
+    let mut w = Writer {
+        buf: &mut block[..],
+        pos: HEADER_LEN,
+    };
+    for param in PARAMS {
+        w.u16(param.id as u16);
+        w.u32(config.get(param.id).to_raw());
+    }
+    let crc_start = w.pos;
+    let len = crc_start - HEADER_LEN;
+
+    block[0..4].copy_from_slice(&CONFIG_MAGIC.to_le_bytes());
+    block[4..6].copy_from_slice(&CONFIG_VERSION.to_le_bytes());
+    block[6..8].copy_from_slice(&(len as u16).to_le_bytes());
+
+    let crc = crc32(&block[..crc_start]);
+    block[crc_start..crc_start + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+    crc_start + CRC_LEN
+}
+
+/// Reads a block of any version up to ours from the start of `block`.
+/// Bytes after the block are ignored, so this can be handed a whole flash page
+pub fn decode_config(block: &[u8]) -> Result<Config, ConfigBlockError> {
+    if block.len() < HEADER_LEN {
+        return Err(ConfigBlockError::BadMagic);
+    }
+
+    let magic = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+    if magic != CONFIG_MAGIC {
+        return Err(ConfigBlockError::BadMagic);
+    }
+
+    let version = u16::from_le_bytes([block[4], block[5]]);
+    if version > CONFIG_VERSION {
+        return Err(ConfigBlockError::UnknownVersion(version));
+    }
+
+    let len = u16::from_le_bytes([block[6], block[7]]);
+    let crc_start = HEADER_LEN + len as usize;
+    if crc_start + CRC_LEN > block.len() {
+        return Err(ConfigBlockError::BadLength(len));
+    }
+
+    let stored_crc = u32::from_le_bytes([
+        block[crc_start],
+        block[crc_start + 1],
+        block[crc_start + 2],
+        block[crc_start + 3],
+    ]);
+    if crc32(&block[..crc_start]) != stored_crc {
+        return Err(ConfigBlockError::BadCrc);
+    }
+
+    let payload = &block[HEADER_LEN..crc_start];
+    if version < 3 {
+        Ok(decode_positional(payload))
+    } else {
+        Ok(decode(payload))
+    }
+}
+
+/// Deserializes a version 3 payload.
+/// Parameters this firmware does not know are skipped, and values it would not accept
+/// over CAN keep their defaults
+fn decode(payload: &[u8]) -> Config {
+    let mut c = Config::default();
+    let mut r = Reader {
+        buf: payload,
+        pos: 0,
+    };
+
+    while let (Some(id), Some(raw)) = (r.u16(), r.u32()) {
+        let id = match ParamId::try_from(id) {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+        if let Some(value) = ParamValue::from_raw(id.info().ty, raw) {
+            let _ = c.set(id, value);
+        }
+    }
+    c
+}
+
+/// Deserializes a version 1 or 2 payload, which held the fields in a fixed order.
+/// Fields missing from the end of an older payload keep their defaults
+fn decode_positional(payload: &[u8]) -> Config {
+    let mut c = Config::default();
+    let mut r = Reader {
+        buf: payload,
+        pos: 0,
+    };
+
+    c.current_limit = r.u8().unwrap_or(c.current_limit);
+    c.inverted = r.u8().map(|v| v != 0).unwrap_or(c.inverted);
+    c.idle_mode = r
+        .u8()
+        .map(|v| {
+            if v == 0 {
+                IdleMode::Coast
+            } else {
+                IdleMode::Brake
+            }
+        })
+        .unwrap_or(c.idle_mode);
+    c.encoder_cpr = r.u16().filter(|&v| v != 0).unwrap_or(c.encoder_cpr);
+    c.current_gains = r.gains().unwrap_or(c.current_gains);
+    c.velocity_gains = r.gains().unwrap_or(c.velocity_gains);
+    c.position_gains = r.gains().unwrap_or(c.position_gains);
+    c.position_tolerance = r.f32().unwrap_or(c.position_tolerance);
+    c.position_output_limit = r.f32().unwrap_or(c.position_output_limit);
+    c.position_inner_loop = r
+        .u8()
+        .map(|v| {
+            if v == 0 {
+                PositionInnerLoop::Velocity
+            } else {
+                PositionInnerLoop::Current
+            }
+        })
+        .unwrap_or(c.position_inner_loop);
+    // added in version 2. A slot past the end means no group
+    if let (Some(group), Some(slot)) = (r.u8(), r.u8()) {
+        c.group_number = group;
+        c.group_slot = slot.min(GROUP_SLOTS as u8);
+    }
+    c
+}
+
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buf[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+
+    fn u16(&mut self, val: u16) {
+        self.bytes(&val.to_le_bytes());
+    }
+
+    fn u32(&mut self, val: u32) {
+        self.bytes(&val.to_le_bytes());
+    }
+}
+
+/// Reads values off the front of a payload.
+/// Every read returns `None` once the payload runs out
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let end = self.pos + N;
+        if end > self.buf.len() {
+            return None;
+        }
+        let mut out = [0; N];
+        out.copy_from_slice(&self.buf[self.pos..end]);
+        self.pos = end;
+        Some(out)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes::<1>().map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.bytes().map(f32::from_le_bytes)
+    }
+
+    fn gains(&mut self) -> Option<PidGains> {
+        Some(PidGains::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+}
@@ -0,0 +1,53 @@
+use core::convert::TryFrom;
+
+/// The loop the position loop feeds into
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PositionInnerLoop {
+    /// Position loop output is a velocity setpoint in rpm
+    Velocity,
+    /// Position loop output is a current setpoint in amps
+    Current,
+}
+
+/// What the H-bridge does with the motor, see `OutgoingFrame::Output`
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum OutputState {
+    /// Driving the motor at the applied duty cycle
+    Drive = 0,
+    /// Stopped with the motor leads floating
+    Coast = 1,
+    /// Stopped with the motor leads shorted
+    Brake = 2,
+    /// The driver is asleep, which also coasts the motor
+    Sleep = 3,
+}
+
+impl TryFrom<u8> for OutputState {
+    type Error = u8;
+
+    fn try_from(raw: u8) -> Result<Self, Self::Error> {
+        match raw {
+            0 => Ok(OutputState::Drive),
+            1 => Ok(OutputState::Coast),
+            2 => Ok(OutputState::Brake),
+            3 => Ok(OutputState::Sleep),
+            _ => Err(raw),
+        }
+    }
+}
+
+/// Bus voltages below this are treated as no supply, so voltage setpoints output nothing
+pub const MIN_BUS_VOLTAGE: f32 = 4.0;
+
+/// Duty cycle that puts `volts` across the motor from a bus at `bus_voltage`,
+/// clamped to full scale when the bus is too low to supply that much
+pub fn voltage_duty(volts: f32, bus_voltage: f32) -> i16 {
+    if bus_voltage.is_nan() || bus_voltage < MIN_BUS_VOLTAGE {
+        return 0;
+    }
+    let duty = (volts / bus_voltage).clamp(-1.0, 1.0);
+    (duty * i16::MAX as f32) as i16
+}
@@ -0,0 +1,14 @@
+/// Standard CRC-32 (IEEE 802.3, reflected, as used by zlib).
+/// The firmware checks its config block with it, the bootloader its application image,
+/// and host tools compute the image crc with the same algorithm
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
@@ -0,0 +1,71 @@
+use crate::{Config, Pid, PidGains};
+
+/// Gains of the limiter loop. Its error is in amps below the limit and its output is
+/// the fraction of duty cycle taken away, so 10 amps over cuts a fifth of the duty right
+/// away and all of it within 20ms if the current stays there
+const LIMIT_GAINS: PidGains = PidGains::new(0.02, 5.0, 0.0);
+
+/// Software current limit based on the measured current.
+///
+/// The limit goes from `Config::smart_current_stall` at standstill to
+/// `Config::smart_current_free` at `Config::smart_current_rpm`, since a stalled motor
+/// heats up at currents it could take while spinning. While the current is over the limit,
+/// the duty cycle is scaled back smoothly instead of chopped like the hardware limit does.
+/// A stall limit of 0 turns it off
+pub struct SmartCurrentLimit {
+    pid: Pid,
+}
+
+impl SmartCurrentLimit {
+    pub const fn new() -> Self {
+        Self {
+            pid: Pid::new(-1.0, 0.0),
+        }
+    }
+
+    /// The limit in amps at a velocity in rpm, `None` if the limit is off
+    pub fn limit(config: &Config, velocity: f32) -> Option<f32> {
+        let stall = config.smart_current_stall;
+        if stall <= 0.0 {
+            return None;
+        }
+        // a free limit of 0 means the same limit at every speed
+        let free = if config.smart_current_free > 0.0 {
+            config.smart_current_free
+        } else {
+            stall
+        };
+        let speed = if config.smart_current_rpm > 0.0 {
+            (velocity.abs() / config.smart_current_rpm).min(1.0)
+        } else {
+            1.0
+        };
+        Some(stall + (free - stall) * speed)
+    }
+
+    /// Forgets any cut back, so the next update starts from the full duty cycle
+    pub fn reset(&mut self) {
+        self.pid.reset();
+    }
+
+    /// Scales back a duty cycle while `current` in amps is over `limit`, usually from `limit`
+    /// and derated by `ThermalModel::limit`
+    pub fn update(&mut self, limit: Option<f32>, duty: i16, current: f32, dt: f32) -> i16 {
+        match limit {
+            Some(limit) => {
+                let cut = self.pid.update(&LIMIT_GAINS, limit - current, dt);
+                (duty as f32 * (1.0 + cut)) as i16
+            }
+            None => {
+                self.pid.reset();
+                duty
+            }
+        }
+    }
+}
+
+impl Default for SmartCurrentLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,30 @@
+use core::convert::TryFrom;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum ErrorCode {
+    None = 0,
+    MotorDriverFault = 1,
+    CanError = 2,
+    Other = 3,
+}
+
+impl From<ErrorCode> for u8 {
+    fn from(code: ErrorCode) -> u8 {
+        code as u8
+    }
+}
+
+impl TryFrom<u8> for ErrorCode {
+    type Error = u8;
+    fn try_from(raw: u8) -> Result<ErrorCode, u8> {
+        match raw {
+            0 => Ok(ErrorCode::None),
+            1 => Ok(ErrorCode::MotorDriverFault),
+            2 => Ok(ErrorCode::CanError),
+            3 => Ok(ErrorCode::Other),
+            _ => Err(raw),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Fault tracking.
+//!
+//! Every fault has an active bit, set while the condition is present, and a sticky bit,
+//! set when it first appears and kept until `IncomingFrame::ClearFaults`. A clear only
+//! drops faults that are no longer active. Each fault has a `FaultAction` parameter, and
+//! the strongest action of the sticky faults holds the output until the faults are cleared,
+//! so a fault cannot quietly come and go while the motor keeps driving.
+
+use crate::Config;
+
+/// A fault condition. The value is its bit in `Faults`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Fault {
+    /// The motor driver pulled its fault line low
+    DriverFault = 0,
+    /// The driver is chopping the output at the current limit
+    Overcurrent = 1,
+    /// The thermal model is derating the current, see `ThermalModel`
+    Overheat = 2,
+    /// The leader of a follower sent no update for `Config::leader_timeout`, see `Config::leader`
+    LeaderLoss = 3,
+    /// No heartbeat arrived for `Config::heartbeat_timeout`
+    HeartbeatLoss = 4,
+}
+
+impl Fault {
+    /// Every fault, in bit order
+    pub const ALL: [Fault; 5] = [
+        Fault::DriverFault,
+        Fault::Overcurrent,
+        Fault::Overheat,
+        Fault::LeaderLoss,
+        Fault::HeartbeatLoss,
+    ];
+
+    const fn bit(self) -> u16 {
+        1 << self as u8
+    }
+}
+
+/// A set of faults, one bit per `Fault`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Faults(pub u16);
+
+impl Faults {
+    pub const NONE: Faults = Faults(0);
+
+    pub fn contains(self, fault: Fault) -> bool {
+        self.0 & fault.bit() != 0
+    }
+
+    pub fn insert(&mut self, fault: Fault) {
+        self.0 |= fault.bit();
+    }
+
+    pub fn remove(&mut self, fault: Fault) {
+        self.0 &= !fault.bit();
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Every fault in the set
+    pub fn iter(self) -> impl Iterator<Item = Fault> {
+        Fault::ALL
+            .iter()
+            .copied()
+            .filter(move |&f| self.contains(f))
+    }
+}
+
+/// What the board does while a fault is sticky. Later variants win over earlier ones
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FaultAction {
+    /// Only report the fault
+    Warn,
+    /// Let the motor coast
+    Coast,
+    /// Short the motor leads
+    Brake,
+    /// Put the motor driver to sleep, which also coasts the motor
+    Disable,
+}
+
+/// Active and sticky faults of a board
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FaultManager {
+    active: Faults,
+    sticky: Faults,
+}
+
+impl FaultManager {
+    pub const fn new() -> Self {
+        Self {
+            active: Faults::NONE,
+            sticky: Faults::NONE,
+        }
+    }
+
+    /// Faults present right now
+    pub fn active(&self) -> Faults {
+        self.active
+    }
+
+    /// Faults seen since the last clear
+    pub fn sticky(&self) -> Faults {
+        self.sticky
+    }
+
+    /// Records whether a fault is present.
+    /// Returns true if it just latched, so callers report a fault once per clear
+    /// no matter how often the condition comes and goes
+    pub fn set_active(&mut self, fault: Fault, active: bool) -> bool {
+        let was_sticky = self.sticky.contains(fault);
+        if active {
+            self.active.insert(fault);
+            self.sticky.insert(fault);
+        } else {
+            self.active.remove(fault);
+        }
+        active && !was_sticky
+    }
+
+    /// Drops every sticky fault that is no longer active
+    pub fn clear(&mut self) {
+        self.sticky = self.active;
+    }
+
+    /// The strongest action of the sticky faults, `FaultAction::Warn` if there are none
+    pub fn action(&self, config: &Config) -> FaultAction {
+        self.sticky
+            .iter()
+            .map(|f| config.fault_action(f))
+            .max()
+            .unwrap_or(FaultAction::Warn)
+    }
+}
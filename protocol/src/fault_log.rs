@@ -0,0 +1,193 @@
+//! Fault history.
+//!
+//! The firmware keeps a `FaultLog` in RAM that neither the bootloader nor the application
+//! initializes, so it survives resets (but not power loss) and tells what happened before a
+//! motor cut out. Hosts read it one record at a time with `IncomingFrame::GetFaultLog`
+//! and empty it with `IncomingFrame::ClearFaultLog`.
+
+use core::convert::TryFrom;
+
+/// Number of records the log holds. Older records are overwritten
+pub const FAULT_LOG_LEN: usize = 16;
+
+/// Marks a log that was written by this layout
+const FAULT_LOG_MAGIC: u32 = 0x474F_4C46; // "FLOG"
+
+/// Bytes of one record in the log, the same as on the wire after the index
+const RECORD_LEN: usize = 7;
+
+/// Something worth remembering after a reset
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum FaultEvent {
+    DriverFault = 0,
+    Overcurrent = 1,
+    /// No frame was received for a while
+    CanTimeout = 2,
+    HeartbeatLoss = 3,
+    Panic = 4,
+    Overheat = 5,
+    LeaderLoss = 6,
+    /// The can peripheral left the bus after too many errors
+    CanBusOff = 7,
+    /// The watchdog reset the board, logged on the next boot
+    WatchdogReset = 8,
+}
+
+impl TryFrom<u8> for FaultEvent {
+    type Error = u8;
+
+    fn try_from(raw: u8) -> Result<Self, Self::Error> {
+        match raw {
+            0 => Ok(FaultEvent::DriverFault),
+            1 => Ok(FaultEvent::Overcurrent),
+            2 => Ok(FaultEvent::CanTimeout),
+            3 => Ok(FaultEvent::HeartbeatLoss),
+            4 => Ok(FaultEvent::Panic),
+            5 => Ok(FaultEvent::Overheat),
+            6 => Ok(FaultEvent::LeaderLoss),
+            7 => Ok(FaultEvent::CanBusOff),
+            8 => Ok(FaultEvent::WatchdogReset),
+            _ => Err(raw),
+        }
+    }
+}
+
+/// One entry of the fault log
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FaultRecord {
+    pub event: FaultEvent,
+    /// Boot the event happened in, see `FaultLog::boot`
+    pub boot: u16,
+    /// Milliseconds since that boot
+    pub time_ms: u32,
+}
+
+impl FaultRecord {
+    pub(crate) fn to_bytes(self) -> [u8; RECORD_LEN] {
+        let mut bytes = [0; RECORD_LEN];
+        bytes[0] = self.event as u8;
+        bytes[1..3].copy_from_slice(&self.boot.to_le_bytes());
+        bytes[3..7].copy_from_slice(&self.time_ms.to_le_bytes());
+        bytes
+    }
+
+    /// `None` if the event is unknown
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<FaultRecord> {
+        Some(FaultRecord {
+            event: FaultEvent::try_from(bytes[0]).ok()?,
+            boot: u16::from_le_bytes([bytes[1], bytes[2]]),
+            time_ms: u32::from_le_bytes([bytes[3], bytes[4], bytes[5], bytes[6]]),
+        })
+    }
+}
+
+/// Ring buffer of fault records.
+///
+/// Only plain integers are stored, since the memory may hold anything after a power up.
+/// `recover` checks the magic and checksum before trusting the contents
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct FaultLog {
+    magic: u32,
+    check: u32,
+    boot: u16,
+    /// Index of the next record to write
+    head: u8,
+    len: u8,
+    records: [[u8; RECORD_LEN]; FAULT_LOG_LEN],
+}
+
+impl FaultLog {
+    pub const fn new() -> Self {
+        Self {
+            magic: 0,
+            check: 0,
+            boot: 0,
+            head: 0,
+            len: 0,
+            records: [[0; RECORD_LEN]; FAULT_LOG_LEN],
+        }
+    }
+
+    /// Keeps a log that survived a reset, or starts an empty one if the memory
+    /// holds something else, and counts a boot. Call once at startup.
+    /// Returns whether the log survived
+    pub fn recover(&mut self) -> bool {
+        let kept = self.magic == FAULT_LOG_MAGIC
+            && self.check == self.checksum()
+            && (self.head as usize) < FAULT_LOG_LEN
+            && self.len as usize <= FAULT_LOG_LEN;
+        if !kept {
+            *self = Self::new();
+            self.magic = FAULT_LOG_MAGIC;
+        }
+        self.boot = self.boot.wrapping_add(1);
+        self.check = self.checksum();
+        kept
+    }
+
+    /// Number of boots counted by `recover`, which tells records of this boot from older ones
+    pub fn boot(&self) -> u16 {
+        self.boot
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Records an event of this boot, overwriting the oldest record once the log is full
+    pub fn push(&mut self, event: FaultEvent, time_ms: u32) {
+        let record = FaultRecord {
+            event,
+            boot: self.boot,
+            time_ms,
+        };
+        self.records[self.head as usize] = record.to_bytes();
+        self.head = ((self.head as usize + 1) % FAULT_LOG_LEN) as u8;
+        self.len = (self.len as usize + 1).min(FAULT_LOG_LEN) as u8;
+        self.check = self.checksum();
+    }
+
+    /// A record, counting back from the newest at index 0
+    pub fn get(&self, index: usize) -> Option<FaultRecord> {
+        if index >= self.len() {
+            return None;
+        }
+        let slot = (self.head as usize + FAULT_LOG_LEN - 1 - index) % FAULT_LOG_LEN;
+        FaultRecord::from_bytes(&self.records[slot])
+    }
+
+    /// Drops every record. The boot count is kept
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+        self.check = self.checksum();
+    }
+
+    /// FNV-1a over everything but the magic and the checksum itself
+    fn checksum(&self) -> u32 {
+        let mut hash = 0x811C_9DC5_u32;
+        let mut add = |byte: u8| {
+            hash ^= byte as u32;
+            hash = hash.wrapping_mul(0x0100_0193);
+        };
+        self.boot.to_le_bytes().iter().for_each(|&b| add(b));
+        add(self.head);
+        add(self.len);
+        self.records.iter().flatten().for_each(|&b| add(b));
+        hash
+    }
+}
+
+impl Default for FaultLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
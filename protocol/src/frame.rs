@@ -0,0 +1,192 @@
+/// Can identifier
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Id {
+    /// 11 bit identifier
+    Standard(u16),
+    /// 29 bit identifier
+    Extended(u32),
+}
+
+/// Minimal can frame, so this crate does not depend on a particular can driver.
+/// The firmware converts these to and from `bxcan` frames, host tools to and from socketcan frames
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CanFrame {
+    id: Id,
+    remote: bool,
+    dlc: u8,
+    data: [u8; 8],
+}
+
+impl CanFrame {
+    /// Creates a data frame. Returns `None` if `data` is longer than 8 bytes
+    pub fn new_data(id: Id, data: &[u8]) -> Option<CanFrame> {
+        if data.len() > 8 {
+            return None;
+        }
+        let mut bytes = [0; 8];
+        bytes[..data.len()].copy_from_slice(data);
+        Some(CanFrame {
+            id,
+            remote: false,
+            dlc: data.len() as u8,
+            data: bytes,
+        })
+    }
+
+    /// Creates a remote frame. Returns `None` if `dlc` is larger than 8
+    pub fn new_remote(id: Id, dlc: u8) -> Option<CanFrame> {
+        if dlc > 8 {
+            return None;
+        }
+        Some(CanFrame {
+            id,
+            remote: true,
+            dlc,
+            data: [0; 8],
+        })
+    }
+
+    #[inline]
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    #[inline]
+    pub fn is_remote_frame(&self) -> bool {
+        self.remote
+    }
+
+    #[inline]
+    pub fn dlc(&self) -> u8 {
+        self.dlc
+    }
+
+    /// Payload of a data frame. Remote frames have no payload
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        if self.remote {
+            &[]
+        } else {
+            &self.data[..self.dlc as usize]
+        }
+    }
+}
+
+/// Device type of a motor controller, as assigned by the FRC CAN spec
+pub const DEVICE_TYPE: u8 = 2;
+
+/// Manufacturer code for team use in the FRC CAN spec. We are not a registered vendor
+pub const MANUFACTURER: u8 = 8;
+
+const DEVICE_TYPE_SHIFT: u32 = 24;
+const MANUFACTURER_SHIFT: u32 = 16;
+const API_CLASS_SHIFT: u32 = 12;
+const API_INDEX_SHIFT: u32 = 8;
+
+/// Bits of an extended id that select a single board: device type, manufacturer and device number.
+/// Boards filter on these and leave the api bits open
+pub const DEVICE_MASK: u32 = 0x1FFF_00FF;
+
+/// Bits of an extended id that select a frame on any board: everything but the device number
+pub const API_MASK: u32 = 0x1FFF_FF00;
+
+/// Device number every board listens to, on top of its own.
+/// Boards must not use it as their id, so the dip switches cannot all be on
+pub const BROADCAST: u8 = 0xFF;
+
+/// Fields of a 29 bit id.
+///
+/// | bits  | field        |
+/// |-------|--------------|
+/// | 24-28 | device type  |
+/// | 16-23 | manufacturer |
+/// | 12-15 | api class    |
+/// | 8-11  | api index    |
+/// | 0-7   | device       |
+///
+/// The device type and manufacturer sit where the FRC CAN spec puts them, so we can
+/// share a bus with FRC devices. The rest differs: the device number is 8 bits so every
+/// dip switch setting is a valid board, which leaves 8 bits of api instead of 10.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IdFields {
+    pub device_type: u8,
+    pub manufacturer: u8,
+    pub api_class: u8,
+    pub api_index: u8,
+    pub device: u8,
+}
+
+impl IdFields {
+    /// Id of one of our boards. `api_class` and `api_index` are truncated to 4 bits
+    pub const fn new(api_class: u8, api_index: u8, device: u8) -> IdFields {
+        IdFields {
+            device_type: DEVICE_TYPE,
+            manufacturer: MANUFACTURER,
+            api_class: api_class & 0xF,
+            api_index: api_index & 0xF,
+            device,
+        }
+    }
+
+    pub const fn from_raw(raw: u32) -> IdFields {
+        IdFields {
+            device_type: ((raw >> DEVICE_TYPE_SHIFT) & 0x1F) as u8,
+            manufacturer: (raw >> MANUFACTURER_SHIFT) as u8,
+            api_class: ((raw >> API_CLASS_SHIFT) & 0xF) as u8,
+            api_index: ((raw >> API_INDEX_SHIFT) & 0xF) as u8,
+            device: raw as u8,
+        }
+    }
+
+    pub const fn raw(&self) -> u32 {
+        ((self.device_type as u32 & 0x1F) << DEVICE_TYPE_SHIFT)
+            | ((self.manufacturer as u32) << MANUFACTURER_SHIFT)
+            | ((self.api_class as u32 & 0xF) << API_CLASS_SHIFT)
+            | ((self.api_index as u32 & 0xF) << API_INDEX_SHIFT)
+            | self.device as u32
+    }
+
+    /// Whether the device type and manufacturer are ours
+    pub const fn is_ours(&self) -> bool {
+        self.device_type == DEVICE_TYPE && self.manufacturer == MANUFACTURER
+    }
+}
+
+/// Id that addresses a board, with the api bits left at zero.
+/// This is what `IntoWithId` expects, and what the firmware filters on.
+/// Pass `BROADCAST` to address every board, or a group number for `IncomingFrame::GroupSetpoint`
+pub const fn device_id(device: u8) -> Id {
+    Id::Extended(IdFields::new(0, 0, device).raw())
+}
+
+/// Board id a frame was sent to or from.
+/// Both our extended ids and the standard ids of protocol version 2 keep it in the low 8 bits
+pub fn board_id(id: Id) -> u8 {
+    match id {
+        Id::Standard(raw) => raw as u8,
+        Id::Extended(raw) => raw as u8,
+    }
+}
+
+/// Builds the id of an api for the board addressed by `id`
+pub(crate) fn with_api(id: Id, api_class: u8, api_index: u8) -> Id {
+    Id::Extended(IdFields::new(api_class, api_index, board_id(id)).raw())
+}
+
+/// Api class and index of a frame from one of our boards, or `None` for any other id
+pub(crate) fn api(id: Id) -> Option<(u8, u8)> {
+    match id {
+        Id::Extended(raw) => {
+            let fields = IdFields::from_raw(raw);
+            if fields.is_ours() {
+                Some((fields.api_class, fields.api_index))
+            } else {
+                None
+            }
+        }
+        Id::Standard(_) => None,
+    }
+}
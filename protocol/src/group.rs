@@ -0,0 +1,26 @@
+/// Number of boards one `IncomingFrame::GroupSetpoint` carries setpoints for
+pub const GROUP_SLOTS: usize = 4;
+
+/// Membership of a board in a group.
+///
+/// A group setpoint frame is sent to the group number instead of a board id,
+/// and every member takes the setpoint in its slot. Groups have their own api,
+/// so group numbers do not collide with board ids.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GroupSlot {
+    pub group: u8,
+    /// Index of our setpoint in the frame, below `GROUP_SLOTS`
+    pub slot: u8,
+}
+
+impl GroupSlot {
+    /// Our setpoint from a group frame sent to `group`, or `None` if it is not our group
+    pub fn pick(&self, group: u8, setpoints: &[i16; GROUP_SLOTS]) -> Option<i16> {
+        if group == self.group {
+            setpoints.get(self.slot as usize).copied()
+        } else {
+            None
+        }
+    }
+}
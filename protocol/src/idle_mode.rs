@@ -0,0 +1,7 @@
+/// Enum representing idle behavior
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IdleMode {
+    Brake,
+    Coast,
+}
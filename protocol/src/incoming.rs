@@ -0,0 +1,233 @@
+use core::convert::{TryFrom, TryInto};
+
+use crate::api::*;
+use crate::frame::{api, with_api, CanFrame, Id};
+use crate::param::{ParamId, ParamValue};
+use crate::{IntoWithId, GROUP_SLOTS};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum FrameConversionError {
+    FrameType,
+    InvalidFrame(&'static str),
+    TooShort { minimum: u8, actual: u8 },
+    InvalidIdFormat,
+    InvalidApi { class: u8, index: u8 },
+    InvalidParam { id: u16 },
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for FrameConversionError {
+    fn format(&self, f: defmt::Formatter) {
+        use FrameConversionError::*;
+        match self {
+            FrameType => {
+                defmt::write!(f, "Frame type is incorrect (remote vs data frames)");
+            }
+            InvalidFrame(s) => {
+                defmt::write!(f, "Invalid Frame: {=str}", s);
+            }
+            TooShort { minimum, actual } => {
+                defmt::write!(
+                    f,
+                    "Data frame too short. Recieved dlc = {=u8}, requires length {=u8}",
+                    actual,
+                    minimum
+                );
+            }
+            InvalidIdFormat => {
+                defmt::write!(f, "Not an extended id of one of our boards");
+            }
+            InvalidApi { class, index } => {
+                defmt::write!(f, "Invalid api class {=u8} index {=u8}", class, index);
+            }
+            InvalidParam { id } => {
+                defmt::write!(f, "Invalid parameter id {=u16}", id);
+            }
+        }
+    }
+}
+
+/// Frames sent to a board
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IncomingFrame {
+    Setpoint(i16),
+    HeartBeat,
+    Stop,
+    SetCurrentSetpoint(f32),
+    SetVelocitySetpoint(f32),
+    SetPositionSetpoint(f32),
+    /// Output in volts. The board scales it by the bus voltage it measures,
+    /// so the motor sees the same voltage as the battery drains
+    SetVoltageSetpoint(f32),
+    SaveConfig,
+    RestoreDefaults,
+    EnterBootloader,
+    /// Asks the board for an `OutgoingFrame::Version`
+    QueryVersion,
+    /// Duty cycle setpoints for every slot of a group.
+    /// Sent to the group number instead of a board id
+    GroupSetpoint([i16; GROUP_SLOTS]),
+    /// Sets a parameter. The board answers with `OutgoingFrame::ParamValue`
+    SetParam(ParamId, ParamValue),
+    /// Asks the board for an `OutgoingFrame::ParamValue`
+    GetParam(ParamId),
+    /// Drops every sticky fault that is no longer active, see `FaultManager`
+    ClearFaults,
+    /// Asks for an `OutgoingFrame::FaultLogEntry`, counting back from the newest record at 0
+    GetFaultLog(u8),
+    /// Empties the fault log
+    ClearFaultLog,
+}
+
+impl TryFrom<CanFrame> for IncomingFrame {
+    type Error = FrameConversionError;
+    fn try_from(frame: CanFrame) -> Result<IncomingFrame, Self::Error> {
+        let dlc = frame.dlc();
+
+        // NOTE: The device bits should already be correct since we have filters
+        let (class, index) = api(frame.id()).ok_or(FrameConversionError::InvalidIdFormat)?;
+        if frame.is_remote_frame() {
+            return Err(FrameConversionError::FrameType);
+        }
+
+        let data = frame.data();
+        match (class, index) {
+            (CLASS_CONTROL, SETPOINT) => {
+                check_frame_size!(2, dlc);
+                let value = i16::from_le_bytes(data[0..2].try_into().unwrap());
+                Ok(IncomingFrame::Setpoint(value))
+            }
+            (CLASS_CONTROL, CURRENT_SETPOINT) => {
+                check_frame_size!(4, dlc);
+                let value = f32::from_le_bytes(data[0..4].try_into().unwrap());
+                Ok(IncomingFrame::SetCurrentSetpoint(value))
+            }
+            (CLASS_CONTROL, VELOCITY_SETPOINT) => {
+                check_frame_size!(4, dlc);
+                let value = f32::from_le_bytes(data[0..4].try_into().unwrap());
+                Ok(IncomingFrame::SetVelocitySetpoint(value))
+            }
+            (CLASS_CONTROL, POSITION_SETPOINT) => {
+                check_frame_size!(4, dlc);
+                let value = f32::from_le_bytes(data[0..4].try_into().unwrap());
+                Ok(IncomingFrame::SetPositionSetpoint(value))
+            }
+            (CLASS_CONTROL, VOLTAGE_SETPOINT) => {
+                check_frame_size!(4, dlc);
+                let value = f32::from_le_bytes(data[0..4].try_into().unwrap());
+                Ok(IncomingFrame::SetVoltageSetpoint(value))
+            }
+            (CLASS_CONTROL, STOP) => Ok(IncomingFrame::Stop),
+            (CLASS_CONTROL, GROUP_SETPOINT) => {
+                check_frame_size!(2 * GROUP_SLOTS as u8, dlc);
+                let mut setpoints = [0; GROUP_SLOTS];
+                for (setpoint, bytes) in setpoints.iter_mut().zip(data.chunks_exact(2)) {
+                    *setpoint = i16::from_le_bytes(bytes.try_into().unwrap());
+                }
+                Ok(IncomingFrame::GroupSetpoint(setpoints))
+            }
+            (CLASS_CONFIG, SET_PARAM) => {
+                check_frame_size!(6, dlc);
+                let (id, value) = decode_param(data)?;
+                Ok(IncomingFrame::SetParam(id, value))
+            }
+            (CLASS_CONFIG, GET_PARAM) => {
+                check_frame_size!(2, dlc);
+                let id = u16::from_le_bytes(data[0..2].try_into().unwrap());
+                let id = ParamId::try_from(id)
+                    .map_err(|id| FrameConversionError::InvalidParam { id })?;
+                Ok(IncomingFrame::GetParam(id))
+            }
+            (CLASS_CONFIG, SAVE_CONFIG) => Ok(IncomingFrame::SaveConfig),
+            (CLASS_CONFIG, RESTORE_DEFAULTS) => Ok(IncomingFrame::RestoreDefaults),
+            (CLASS_SYSTEM, HEARTBEAT) => Ok(IncomingFrame::HeartBeat),
+            (CLASS_SYSTEM, QUERY_VERSION) => Ok(IncomingFrame::QueryVersion),
+            // the bootloader treats this same frame as a request to stay resident
+            (CLASS_SYSTEM, ENTER_BOOTLOADER) => Ok(IncomingFrame::EnterBootloader),
+            (CLASS_SYSTEM, CLEAR_FAULTS) => Ok(IncomingFrame::ClearFaults),
+            (CLASS_SYSTEM, GET_FAULT_LOG) => {
+                check_frame_size!(1, dlc);
+                Ok(IncomingFrame::GetFaultLog(data[0]))
+            }
+            (CLASS_SYSTEM, CLEAR_FAULT_LOG) => Ok(IncomingFrame::ClearFaultLog),
+            (class, index) => Err(FrameConversionError::InvalidApi { class, index }),
+        }
+    }
+}
+
+/// Decodes the id and value of a parameter frame. The value must fit the type of the parameter
+pub(crate) fn decode_param(data: &[u8]) -> Result<(ParamId, ParamValue), FrameConversionError> {
+    let id = u16::from_le_bytes(data[0..2].try_into().unwrap());
+    let id = ParamId::try_from(id).map_err(|id| FrameConversionError::InvalidParam { id })?;
+    let raw = u32::from_le_bytes(data[2..6].try_into().unwrap());
+    let value = ParamValue::from_raw(id.info().ty, raw).ok_or(
+        FrameConversionError::InvalidFrame("Parameter value does not fit its type"),
+    )?;
+    Ok((id, value))
+}
+
+pub(crate) fn encode_param(id: ParamId, value: ParamValue) -> [u8; 6] {
+    let mut bytes = [0; 6];
+    bytes[0..2].copy_from_slice(&(id as u16).to_le_bytes());
+    bytes[2..6].copy_from_slice(&value.to_raw().to_le_bytes());
+    bytes
+}
+
+/// Encodes a frame for a board. This is the inverse of the `TryFrom<CanFrame>` impl,
+/// and is what host tools use to talk to the firmware
+impl IntoWithId<CanFrame> for IncomingFrame {
+    fn into_with_id(self, id: Id) -> CanFrame {
+        let mut bytes = [0_u8; 8];
+        let (class, index, len) = match self {
+            IncomingFrame::Setpoint(value) => {
+                bytes[0..2].copy_from_slice(&value.to_le_bytes());
+                (CLASS_CONTROL, SETPOINT, 2)
+            }
+            IncomingFrame::SetCurrentSetpoint(value) => {
+                bytes[0..4].copy_from_slice(&value.to_le_bytes());
+                (CLASS_CONTROL, CURRENT_SETPOINT, 4)
+            }
+            IncomingFrame::SetVelocitySetpoint(value) => {
+                bytes[0..4].copy_from_slice(&value.to_le_bytes());
+                (CLASS_CONTROL, VELOCITY_SETPOINT, 4)
+            }
+            IncomingFrame::SetPositionSetpoint(value) => {
+                bytes[0..4].copy_from_slice(&value.to_le_bytes());
+                (CLASS_CONTROL, POSITION_SETPOINT, 4)
+            }
+            IncomingFrame::SetVoltageSetpoint(value) => {
+                bytes[0..4].copy_from_slice(&value.to_le_bytes());
+                (CLASS_CONTROL, VOLTAGE_SETPOINT, 4)
+            }
+            IncomingFrame::Stop => (CLASS_CONTROL, STOP, 0),
+            IncomingFrame::GroupSetpoint(setpoints) => {
+                for (bytes, setpoint) in bytes.chunks_exact_mut(2).zip(setpoints.iter()) {
+                    bytes.copy_from_slice(&setpoint.to_le_bytes());
+                }
+                (CLASS_CONTROL, GROUP_SETPOINT, 2 * GROUP_SLOTS)
+            }
+            IncomingFrame::SetParam(id, value) => {
+                bytes[0..6].copy_from_slice(&encode_param(id, value));
+                (CLASS_CONFIG, SET_PARAM, 6)
+            }
+            IncomingFrame::GetParam(id) => {
+                bytes[0..2].copy_from_slice(&(id as u16).to_le_bytes());
+                (CLASS_CONFIG, GET_PARAM, 2)
+            }
+            IncomingFrame::SaveConfig => (CLASS_CONFIG, SAVE_CONFIG, 0),
+            IncomingFrame::RestoreDefaults => (CLASS_CONFIG, RESTORE_DEFAULTS, 0),
+            IncomingFrame::HeartBeat => (CLASS_SYSTEM, HEARTBEAT, 0),
+            IncomingFrame::QueryVersion => (CLASS_SYSTEM, QUERY_VERSION, 0),
+            IncomingFrame::EnterBootloader => (CLASS_SYSTEM, ENTER_BOOTLOADER, 0),
+            IncomingFrame::ClearFaults => (CLASS_SYSTEM, CLEAR_FAULTS, 0),
+            IncomingFrame::GetFaultLog(index) => {
+                bytes[0] = index;
+                (CLASS_SYSTEM, GET_FAULT_LOG, 1)
+            }
+            IncomingFrame::ClearFaultLog => (CLASS_SYSTEM, CLEAR_FAULT_LOG, 0),
+        };
+
+        CanFrame::new_data(with_api(id, class, index), &bytes[..len]).unwrap()
+    }
+}
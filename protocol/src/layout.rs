@@ -0,0 +1,530 @@
+//! Byte layout of every frame, for tools that decode frames without this crate.
+//!
+//! `IncomingFrame::layout` and `OutgoingFrame::layout` map every variant to its
+//! entry here, and the tests check the encoders against it, so the layout cannot
+//! drift from the code. `bmc-dbc` turns it into a DBC file.
+
+use crate::api::{self, CLASS_CONFIG, CLASS_CONTROL, CLASS_FAULT, CLASS_STATUS, CLASS_SYSTEM};
+use crate::{IdFields, IncomingFrame, OutgoingFrame};
+
+/// Which way a frame travels
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Sent by a host to a board
+    ToBoard,
+    /// Sent by a board
+    FromBoard,
+}
+
+/// How the bytes of a signal are interpreted. Every value is little endian
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SignalType {
+    Unsigned,
+    Signed,
+    Float,
+}
+
+/// One value in a frame
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Signal {
+    pub name: &'static str,
+    /// Offset of the first byte in the payload
+    pub offset: u8,
+    /// Size in bytes
+    pub len: u8,
+    pub ty: SignalType,
+    pub unit: &'static str,
+    /// Names of enumerated values
+    pub values: &'static [(u32, &'static str)],
+}
+
+/// Layout of one kind of frame
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Message {
+    pub name: &'static str,
+    pub direction: Direction,
+    pub api_class: u8,
+    pub api_index: u8,
+    pub dlc: u8,
+    pub signals: &'static [Signal],
+}
+
+impl Message {
+    /// Extended id of this message for a board
+    pub fn id(&self, board: u8) -> u32 {
+        IdFields::new(self.api_class, self.api_index, board).raw()
+    }
+}
+
+const fn signal(name: &'static str, offset: u8, len: u8, ty: SignalType) -> Signal {
+    Signal {
+        name,
+        offset,
+        len,
+        ty,
+        unit: "",
+        values: &[],
+    }
+}
+
+const fn with_unit(mut signal: Signal, unit: &'static str) -> Signal {
+    signal.unit = unit;
+    signal
+}
+
+const fn with_values(mut signal: Signal, values: &'static [(u32, &'static str)]) -> Signal {
+    signal.values = values;
+    signal
+}
+
+/// The direction follows from the api class, see `api`
+const fn message(
+    name: &'static str,
+    api_class: u8,
+    api_index: u8,
+    dlc: u8,
+    signals: &'static [Signal],
+) -> Message {
+    Message {
+        name,
+        direction: if api_class < CLASS_STATUS {
+            Direction::ToBoard
+        } else {
+            Direction::FromBoard
+        },
+        api_class,
+        api_index,
+        dlc,
+        signals,
+    }
+}
+
+use SignalType::*;
+
+/// Names of every parameter in `param::PARAMS`
+const PARAM_IDS: &[(u32, &str)] = &[
+    (0x00, "current_limit"),
+    (0x01, "inverted"),
+    (0x02, "idle_mode"),
+    (0x03, "encoder_cpr"),
+    (0x04, "ramp_up"),
+    (0x05, "ramp_down"),
+    (0x06, "smart_current_stall"),
+    (0x07, "smart_current_free"),
+    (0x08, "smart_current_rpm"),
+    (0x10, "current_kp"),
+    (0x11, "current_ki"),
+    (0x12, "current_kd"),
+    (0x13, "velocity_kp"),
+    (0x14, "velocity_ki"),
+    (0x15, "velocity_kd"),
+    (0x16, "position_kp"),
+    (0x17, "position_ki"),
+    (0x18, "position_kd"),
+    (0x20, "position_tolerance"),
+    (0x21, "position_output_limit"),
+    (0x22, "position_inner_loop"),
+    (0x30, "group"),
+    (0x31, "group_slot"),
+    (0x32, "follow_leader"),
+    (0x33, "heartbeat_timeout"),
+    (0x34, "leader_timeout"),
+    (0x40, "driver_fault_action"),
+    (0x41, "overcurrent_action"),
+    (0x42, "overheat_action"),
+    (0x43, "leader_loss_action"),
+    (0x44, "heartbeat_loss_action"),
+    (0x50, "motor_resistance"),
+    (0x51, "motor_thermal_resistance"),
+    (0x52, "motor_thermal_time"),
+    (0x53, "motor_temp_limit"),
+    (0x60, "update_rate"),
+    (0x61, "encoder_rate"),
+    (0x62, "bus_voltage_rate"),
+    (0x63, "motor_temperature_rate"),
+    (0x64, "faults_rate"),
+    (0x65, "system_status_rate"),
+    (0x66, "can_status_rate"),
+    (0x67, "output_rate"),
+];
+
+const FAULT_EVENTS: &[(u32, &str)] = &[
+    (0, "DriverFault"),
+    (1, "Overcurrent"),
+    (2, "CanTimeout"),
+    (3, "HeartbeatLoss"),
+    (4, "Panic"),
+    (5, "Overheat"),
+    (6, "LeaderLoss"),
+    (7, "CanBusOff"),
+    (8, "WatchdogReset"),
+    (0xFF, "NoRecord"),
+];
+
+const OUTPUT_STATES: &[(u32, &str)] = &[(0, "Drive"), (1, "Coast"), (2, "Brake"), (3, "Sleep")];
+
+const CAN_BUS_STATES: &[(u32, &str)] = &[
+    (0, "ErrorActive"),
+    (1, "ErrorWarning"),
+    (2, "ErrorPassive"),
+    (3, "BusOff"),
+];
+
+const CAN_LAST_ERRORS: &[(u32, &str)] = &[
+    (0, "None"),
+    (1, "Stuff"),
+    (2, "Form"),
+    (3, "Acknowledge"),
+    (4, "BitRecessive"),
+    (5, "BitDominant"),
+    (6, "Crc"),
+];
+
+const RESET_CAUSES: &[(u32, &str)] = &[
+    (0, "PowerOn"),
+    (1, "BrownOut"),
+    (2, "Pin"),
+    (3, "Software"),
+    (4, "Watchdog"),
+    (5, "WindowWatchdog"),
+    (6, "LowPower"),
+    (7, "Unknown"),
+];
+
+const ERROR_CODES: &[(u32, &str)] = &[
+    (0, "None"),
+    (1, "MotorDriverFault"),
+    (2, "CanError"),
+    (3, "Other"),
+];
+
+pub const SETPOINT: Message = message(
+    "Setpoint",
+    CLASS_CONTROL,
+    api::SETPOINT,
+    2,
+    &[signal("Setpoint", 0, 2, Signed)],
+);
+pub const SET_CURRENT_SETPOINT: Message = message(
+    "SetCurrentSetpoint",
+    CLASS_CONTROL,
+    api::CURRENT_SETPOINT,
+    4,
+    &[with_unit(signal("CurrentSetpoint", 0, 4, Float), "A")],
+);
+pub const SET_VELOCITY_SETPOINT: Message = message(
+    "SetVelocitySetpoint",
+    CLASS_CONTROL,
+    api::VELOCITY_SETPOINT,
+    4,
+    &[with_unit(signal("VelocitySetpoint", 0, 4, Float), "rpm")],
+);
+pub const SET_POSITION_SETPOINT: Message = message(
+    "SetPositionSetpoint",
+    CLASS_CONTROL,
+    api::POSITION_SETPOINT,
+    4,
+    &[with_unit(signal("PositionSetpoint", 0, 4, Float), "rot")],
+);
+pub const STOP: Message = message("Stop", CLASS_CONTROL, api::STOP, 0, &[]);
+pub const SET_VOLTAGE_SETPOINT: Message = message(
+    "SetVoltageSetpoint",
+    CLASS_CONTROL,
+    api::VOLTAGE_SETPOINT,
+    4,
+    &[with_unit(signal("VoltageSetpoint", 0, 4, Float), "V")],
+);
+/// The device number of this message is a group number, not a board id
+pub const GROUP_SETPOINT: Message = message(
+    "GroupSetpoint",
+    CLASS_CONTROL,
+    api::GROUP_SETPOINT,
+    8,
+    &[
+        signal("Slot0Setpoint", 0, 2, Signed),
+        signal("Slot1Setpoint", 2, 2, Signed),
+        signal("Slot2Setpoint", 4, 2, Signed),
+        signal("Slot3Setpoint", 6, 2, Signed),
+    ],
+);
+pub const SET_PARAM: Message = message(
+    "SetParam",
+    CLASS_CONFIG,
+    api::SET_PARAM,
+    6,
+    &[
+        with_values(signal("ParamId", 0, 2, Unsigned), PARAM_IDS),
+        signal("ParamValue", 2, 4, Unsigned),
+    ],
+);
+pub const GET_PARAM: Message = message(
+    "GetParam",
+    CLASS_CONFIG,
+    api::GET_PARAM,
+    2,
+    &[with_values(signal("ParamId", 0, 2, Unsigned), PARAM_IDS)],
+);
+pub const SAVE_CONFIG: Message = message("SaveConfig", CLASS_CONFIG, api::SAVE_CONFIG, 0, &[]);
+pub const RESTORE_DEFAULTS: Message = message(
+    "RestoreDefaults",
+    CLASS_CONFIG,
+    api::RESTORE_DEFAULTS,
+    0,
+    &[],
+);
+pub const HEARTBEAT: Message = message("HeartBeat", CLASS_SYSTEM, api::HEARTBEAT, 0, &[]);
+pub const QUERY_VERSION: Message =
+    message("QueryVersion", CLASS_SYSTEM, api::QUERY_VERSION, 0, &[]);
+pub const ENTER_BOOTLOADER: Message = message(
+    "EnterBootloader",
+    CLASS_SYSTEM,
+    api::ENTER_BOOTLOADER,
+    0,
+    &[],
+);
+pub const CLEAR_FAULTS: Message = message("ClearFaults", CLASS_SYSTEM, api::CLEAR_FAULTS, 0, &[]);
+pub const GET_FAULT_LOG: Message = message(
+    "GetFaultLog",
+    CLASS_SYSTEM,
+    api::GET_FAULT_LOG,
+    1,
+    &[signal("FaultLogIndex", 0, 1, Unsigned)],
+);
+pub const CLEAR_FAULT_LOG: Message =
+    message("ClearFaultLog", CLASS_SYSTEM, api::CLEAR_FAULT_LOG, 0, &[]);
+
+pub const UPDATE: Message = message(
+    "Update",
+    CLASS_STATUS,
+    api::UPDATE,
+    6,
+    &[
+        signal("DutyNow", 0, 2, Signed),
+        with_unit(signal("CurrentNow", 2, 4, Float), "A"),
+    ],
+);
+pub const ENCODER: Message = message(
+    "Encoder",
+    CLASS_STATUS,
+    api::ENCODER,
+    8,
+    &[
+        with_unit(signal("Position", 0, 4, Float), "rot"),
+        with_unit(signal("Velocity", 4, 4, Float), "rpm"),
+    ],
+);
+pub const VERSION: Message = message(
+    "Version",
+    CLASS_STATUS,
+    api::VERSION,
+    4,
+    &[
+        signal("ProtocolVersion", 0, 1, Unsigned),
+        signal("FirmwareMajor", 1, 1, Unsigned),
+        signal("FirmwareMinor", 2, 1, Unsigned),
+        signal("FirmwarePatch", 3, 1, Unsigned),
+    ],
+);
+/// The value is a raw `u32`, interpret it with the type in `param::PARAMS`
+pub const PARAM_VALUE: Message = message(
+    "ParamValue",
+    CLASS_STATUS,
+    api::PARAM_VALUE,
+    6,
+    &[
+        with_values(signal("ParamId", 0, 2, Unsigned), PARAM_IDS),
+        signal("ParamValue", 2, 4, Unsigned),
+    ],
+);
+pub const MOTOR_TEMPERATURE: Message = message(
+    "MotorTemperature",
+    CLASS_STATUS,
+    api::MOTOR_TEMPERATURE,
+    8,
+    &[
+        with_unit(signal("MotorTemperature", 0, 4, Float), "degC"),
+        signal("CurrentDerating", 4, 4, Float),
+    ],
+);
+pub const BUS_VOLTAGE: Message = message(
+    "BusVoltage",
+    CLASS_STATUS,
+    api::BUS_VOLTAGE,
+    4,
+    &[with_unit(signal("BusVoltage", 0, 4, Float), "V")],
+);
+/// One bit per `Fault`, like `FAULTS`
+pub const SYSTEM_STATUS: Message = message(
+    "SystemStatus",
+    CLASS_STATUS,
+    api::SYSTEM_STATUS,
+    8,
+    &[
+        signal("StatusActiveFaults", 0, 2, Unsigned),
+        signal("StatusStickyFaults", 2, 2, Unsigned),
+        with_unit(signal("McuTemperature", 4, 1, Signed), "degC"),
+        with_unit(signal("Uptime", 5, 3, Unsigned), "s"),
+    ],
+);
+pub const CAN_STATUS: Message = message(
+    "CanStatus",
+    CLASS_STATUS,
+    api::CAN_STATUS,
+    7,
+    &[
+        signal("TxErrors", 0, 1, Unsigned),
+        signal("RxErrors", 1, 1, Unsigned),
+        signal("BusOffCount", 2, 2, Unsigned),
+        signal("TxQueueDepth", 4, 1, Unsigned),
+        with_values(signal("CanBusState", 5, 1, Unsigned), CAN_BUS_STATES),
+        with_values(signal("CanLastError", 6, 1, Unsigned), CAN_LAST_ERRORS),
+    ],
+);
+pub const OUTPUT: Message = message(
+    "Output",
+    CLASS_STATUS,
+    api::OUTPUT,
+    3,
+    &[
+        signal("AppliedDuty", 0, 2, Signed),
+        with_values(signal("OutputState", 2, 1, Unsigned), OUTPUT_STATES),
+    ],
+);
+/// The boot count is the one in `FaultBoot`
+pub const BOOT: Message = message(
+    "Boot",
+    CLASS_STATUS,
+    api::BOOT,
+    3,
+    &[
+        with_values(signal("ResetCause", 0, 1, Unsigned), RESET_CAUSES),
+        signal("BootCount", 1, 2, Unsigned),
+    ],
+);
+pub const ERROR: Message = message(
+    "Error",
+    CLASS_FAULT,
+    api::ERROR,
+    1,
+    &[with_values(
+        signal("ErrorCode", 0, 1, Unsigned),
+        ERROR_CODES,
+    )],
+);
+pub const OVERCURRENT: Message = message(
+    "Overcurrent",
+    CLASS_FAULT,
+    api::OVERCURRENT,
+    8,
+    &[
+        with_unit(signal("OvercurrentNow", 0, 4, Float), "A"),
+        with_unit(signal("OvercurrentLimit", 4, 4, Float), "A"),
+    ],
+);
+/// One bit per `Fault`
+pub const FAULTS: Message = message(
+    "Faults",
+    CLASS_FAULT,
+    api::FAULTS,
+    4,
+    &[
+        signal("ActiveFaults", 0, 2, Unsigned),
+        signal("StickyFaults", 2, 2, Unsigned),
+    ],
+);
+/// Time is in milliseconds since boot number `FaultBoot`
+pub const FAULT_LOG_ENTRY: Message = message(
+    "FaultLogEntry",
+    CLASS_FAULT,
+    api::FAULT_LOG_ENTRY,
+    8,
+    &[
+        signal("FaultLogIndex", 0, 1, Unsigned),
+        with_values(signal("FaultEvent", 1, 1, Unsigned), FAULT_EVENTS),
+        signal("FaultBoot", 2, 2, Unsigned),
+        with_unit(signal("FaultTime", 4, 4, Unsigned), "ms"),
+    ],
+);
+
+/// Every message, in id order
+pub const MESSAGES: &[Message] = &[
+    SETPOINT,
+    SET_CURRENT_SETPOINT,
+    SET_VELOCITY_SETPOINT,
+    SET_POSITION_SETPOINT,
+    STOP,
+    GROUP_SETPOINT,
+    SET_VOLTAGE_SETPOINT,
+    SET_PARAM,
+    GET_PARAM,
+    SAVE_CONFIG,
+    RESTORE_DEFAULTS,
+    HEARTBEAT,
+    QUERY_VERSION,
+    ENTER_BOOTLOADER,
+    CLEAR_FAULTS,
+    GET_FAULT_LOG,
+    CLEAR_FAULT_LOG,
+    UPDATE,
+    ENCODER,
+    VERSION,
+    PARAM_VALUE,
+    MOTOR_TEMPERATURE,
+    BUS_VOLTAGE,
+    SYSTEM_STATUS,
+    CAN_STATUS,
+    OUTPUT,
+    BOOT,
+    ERROR,
+    OVERCURRENT,
+    FAULTS,
+    FAULT_LOG_ENTRY,
+];
+
+impl IncomingFrame {
+    /// Layout of this frame on the wire
+    pub fn layout(&self) -> &'static Message {
+        use IncomingFrame::*;
+        match self {
+            HeartBeat => &HEARTBEAT,
+            Stop => &STOP,
+            Setpoint(_) => &SETPOINT,
+            SetCurrentSetpoint(_) => &SET_CURRENT_SETPOINT,
+            SetVelocitySetpoint(_) => &SET_VELOCITY_SETPOINT,
+            SetPositionSetpoint(_) => &SET_POSITION_SETPOINT,
+            SetVoltageSetpoint(_) => &SET_VOLTAGE_SETPOINT,
+            SaveConfig => &SAVE_CONFIG,
+            RestoreDefaults => &RESTORE_DEFAULTS,
+            EnterBootloader => &ENTER_BOOTLOADER,
+            QueryVersion => &QUERY_VERSION,
+            GroupSetpoint(_) => &GROUP_SETPOINT,
+            SetParam(..) => &SET_PARAM,
+            GetParam(_) => &GET_PARAM,
+            ClearFaults => &CLEAR_FAULTS,
+            GetFaultLog(_) => &GET_FAULT_LOG,
+            ClearFaultLog => &CLEAR_FAULT_LOG,
+        }
+    }
+}
+
+impl OutgoingFrame {
+    /// Layout of this frame on the wire
+    pub fn layout(&self) -> &'static Message {
+        match self {
+            OutgoingFrame::Error(_) => &ERROR,
+            OutgoingFrame::Update { .. } => &UPDATE,
+            OutgoingFrame::Overcurrent { .. } => &OVERCURRENT,
+            OutgoingFrame::Encoder { .. } => &ENCODER,
+            OutgoingFrame::Version { .. } => &VERSION,
+            OutgoingFrame::ParamValue(..) => &PARAM_VALUE,
+            OutgoingFrame::Faults { .. } => &FAULTS,
+            OutgoingFrame::FaultLogEntry { .. } => &FAULT_LOG_ENTRY,
+            OutgoingFrame::MotorTemperature { .. } => &MOTOR_TEMPERATURE,
+            OutgoingFrame::BusVoltage(_) => &BUS_VOLTAGE,
+            OutgoingFrame::SystemStatus { .. } => &SYSTEM_STATUS,
+            OutgoingFrame::CanStatus { .. } => &CAN_STATUS,
+            OutgoingFrame::Output { .. } => &OUTPUT,
+            OutgoingFrame::Boot { .. } => &BOOT,
+        }
+    }
+}
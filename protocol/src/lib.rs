@@ -0,0 +1,109 @@
+//! CAN protocol of the brushed motor controller.
+//!
+//! This crate is `no_std` so the firmware and host tools share a single definition
+//! of every frame. Enable the `defmt` feature to log protocol types from firmware.
+//! The PID controller, the setpoint `Ramp`, the `SmartCurrentLimit`, the motor `ThermalModel` and
+//! the board `Config` live here too, so the simulator runs the same loops and holds the same
+//! settings as the firmware. Every setting is a parameter in `param`, and `config_block` is
+//! how the firmware keeps them in flash.
+//!
+//! Frames use 29 bit ids laid out like the FRC CAN spec: a device type and manufacturer,
+//! then an api class and index that select the frame, then the board id set by the dip
+//! switches. See `IdFields` for the exact bits and `api` for every class and index.
+//! Other devices can share the bus, since everything with a different device type or
+//! manufacturer is ignored.
+//!
+//! Faults latch until `IncomingFrame::ClearFaults`, see `FaultManager`, and are
+//! remembered across resets in a `FaultLog`.
+//!
+//! Every board also obeys frames sent to `BROADCAST`, and boards in a group share
+//! `IncomingFrame::GroupSetpoint` frames sent to the group number, see `GroupSlot`.
+//! A follower mirrors the `OutgoingFrame::Update` frames of another board, see `Config::leader`.
+//!
+//! Boards send their status frames on their own, each at its own rate, see `StatusSchedule`.
+//! After every reset they announce themselves with an `OutgoingFrame::Boot`, see `ResetCause`.
+//! The bootloader speaks the same ids, with its own `BootCommand` and `BootStatus` frames.
+//!
+//! # Wire format (protocol version 4)
+//!
+//! Every multi byte value is little endian, no matter which machine encodes it.
+//! `f32` values are IEEE 754 single precision. `layout` lists the offset and type
+//! of every value in every frame.
+//!
+//! Version 1 used the native byte order of the encoder. That happened to be little
+//! endian on every board and host we had, but it was never specified.
+//! Versions 1 and 2 used 11 bit ids, with the command in bits 8-10.
+//! Version 3 had one frame per setting, version 4 replaced them with the parameter frames.
+//!
+//! Hosts should send `IncomingFrame::QueryVersion` before anything else, and only
+//! talk to boards that answer with an `OutgoingFrame::Version` carrying
+//! `PROTOCOL_VERSION`. Boards older than version 3 do not understand our ids and never answer.
+#![no_std]
+
+macro_rules! check_frame_size {
+    ($req:expr, $actual:expr) => {
+        if $actual < $req {
+            return Err($crate::FrameConversionError::TooShort {
+                actual: $actual,
+                minimum: $req,
+            });
+        }
+    };
+}
+
+pub mod api;
+mod bootloader;
+mod can_status;
+pub mod config_block;
+mod control;
+mod crc;
+mod current_limit;
+mod error_codes;
+mod fault;
+mod fault_log;
+mod frame;
+mod group;
+mod idle_mode;
+mod incoming;
+pub mod layout;
+mod outgoing;
+pub mod param;
+mod pid;
+mod ramp;
+mod reset_cause;
+mod status;
+mod thermal;
+
+pub use bootloader::{BootCommand, BootStatus};
+pub use can_status::{CanBusState, CanLastError};
+pub use config_block::{decode_config, encode_config, ConfigBlockError, MAX_CONFIG_BLOCK_LEN};
+pub use control::{voltage_duty, OutputState, PositionInnerLoop, MIN_BUS_VOLTAGE};
+pub use crc::crc32;
+pub use current_limit::SmartCurrentLimit;
+pub use error_codes::ErrorCode;
+pub use fault::{Fault, FaultAction, FaultManager, Faults};
+pub use fault_log::{FaultEvent, FaultLog, FaultRecord, FAULT_LOG_LEN};
+pub use frame::{
+    board_id, device_id, CanFrame, Id, IdFields, API_MASK, BROADCAST, DEVICE_MASK, DEVICE_TYPE,
+    MANUFACTURER,
+};
+pub use group::{GroupSlot, GROUP_SLOTS};
+pub use idle_mode::IdleMode;
+pub use incoming::{FrameConversionError, IncomingFrame};
+pub use outgoing::OutgoingFrame;
+pub use param::{Config, ParamId, ParamValue};
+pub use pid::{Pid, PidGains};
+pub use ramp::Ramp;
+pub use reset_cause::ResetCause;
+pub use status::{StatusFrame, StatusSchedule, MAX_STATUS_RATE, STATUS_FRAMES};
+pub use thermal::{ThermalModel, AMBIENT_TEMPERATURE};
+
+/// Version of the wire format described by this crate.
+/// Bump this whenever an existing frame changes meaning or layout
+pub const PROTOCOL_VERSION: u8 = 4;
+
+/// Conversion of a message into a frame for the board addressed by an id, usually from `device_id`.
+/// The api bits of the id are filled in by the message
+pub trait IntoWithId<T> {
+    fn into_with_id(self, id: Id) -> T;
+}
@@ -0,0 +1,343 @@
+use core::convert::{TryFrom, TryInto};
+
+use crate::api::*;
+use crate::can_status::{CanBusState, CanLastError};
+use crate::control::OutputState;
+use crate::error_codes::ErrorCode;
+use crate::fault::Faults;
+use crate::fault_log::FaultRecord;
+use crate::frame::{api, with_api, CanFrame, Id};
+use crate::incoming::{decode_param, encode_param, FrameConversionError};
+use crate::param::{ParamId, ParamValue};
+use crate::reset_cause::ResetCause;
+use crate::IntoWithId;
+
+/// Frames sent by a board
+#[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OutgoingFrame {
+    Update {
+        current_now: f32,
+        /// Applied duty cycle, 0 while the motor is stopped
+        duty_now: i16,
+    },
+    Overcurrent {
+        current_now: f32,
+        current_limit: f32,
+    },
+    Error(ErrorCode),
+    Encoder {
+        position: f32,
+        velocity: f32,
+    },
+    /// Answer to `IncomingFrame::QueryVersion`
+    Version {
+        /// Wire format the board speaks, see `PROTOCOL_VERSION`
+        protocol: u8,
+        /// Firmware version as major, minor, patch
+        firmware: [u8; 3],
+    },
+    /// Answer to `IncomingFrame::SetParam` and `IncomingFrame::GetParam`,
+    /// with the value the board holds after the request
+    ParamValue(ParamId, ParamValue),
+    /// Fault bits, sent when a fault appears and periodically while any are sticky
+    Faults {
+        active: Faults,
+        sticky: Faults,
+    },
+    /// Answer to `IncomingFrame::GetFaultLog`, `None` past the oldest record
+    FaultLogEntry {
+        index: u8,
+        record: Option<FaultRecord>,
+    },
+    /// Estimate of the `ThermalModel`, sent periodically while `Config::motor_temp_limit` is set
+    MotorTemperature {
+        /// Winding temperature in degrees celsius
+        temperature: f32,
+        /// Fraction of the current limit still allowed
+        derating: f32,
+    },
+    /// Measured bus voltage in volts
+    BusVoltage(f32),
+    /// Health of the board itself
+    SystemStatus {
+        active: Faults,
+        sticky: Faults,
+        /// Temperature of the microcontroller in degrees celsius
+        mcu_temperature: i8,
+        /// Seconds since boot. Only 24 bits go on the wire, which is over 190 days
+        uptime: u32,
+    },
+    /// Health of the can bus as the board sees it
+    CanStatus {
+        /// Transmit and receive error counters of the can peripheral
+        tx_errors: u8,
+        rx_errors: u8,
+        /// Times the board went bus off since boot
+        bus_off_count: u16,
+        /// Frames waiting to be sent
+        tx_queue_depth: u8,
+        state: CanBusState,
+        last_error: CanLastError,
+    },
+    /// The output that reaches the motor, after inversion, the deadband, the idle mode and faults.
+    /// The duty cycle is the same as in `Update`, this adds what the bridge does with it
+    Output {
+        /// Applied duty cycle, 0 unless `state` is `OutputState::Drive`
+        duty: i16,
+        state: OutputState,
+    },
+    /// Sent once after every reset, before any status frame
+    Boot {
+        reset_cause: ResetCause,
+        /// Boots counted by the `FaultLog`, which tells the records of this boot
+        boot: u16,
+    },
+}
+
+/// Largest uptime a `SystemStatus` frame can carry
+const MAX_UPTIME: u32 = 0xFF_FFFF;
+
+/// Event byte of a `FaultLogEntry` without a record
+const NO_RECORD: u8 = 0xFF;
+
+impl IntoWithId<CanFrame> for OutgoingFrame {
+    fn into_with_id(self, id: Id) -> CanFrame {
+        let mut bytes = [0_u8; 8];
+        let (class, index, len) = match self {
+            OutgoingFrame::Update {
+                current_now,
+                duty_now,
+            } => {
+                bytes[0..2].copy_from_slice(&duty_now.to_le_bytes());
+                bytes[2..6].copy_from_slice(&current_now.to_le_bytes());
+                (CLASS_STATUS, UPDATE, 6)
+            }
+            OutgoingFrame::Overcurrent {
+                current_now,
+                current_limit,
+            } => {
+                bytes[0..4].copy_from_slice(&current_now.to_le_bytes());
+                bytes[4..8].copy_from_slice(&current_limit.to_le_bytes());
+                (CLASS_FAULT, OVERCURRENT, 8)
+            }
+            OutgoingFrame::Error(code) => {
+                bytes[0] = code.into();
+                (CLASS_FAULT, ERROR, 1)
+            }
+            OutgoingFrame::Encoder { position, velocity } => {
+                bytes[0..4].copy_from_slice(&position.to_le_bytes());
+                bytes[4..8].copy_from_slice(&velocity.to_le_bytes());
+                (CLASS_STATUS, ENCODER, 8)
+            }
+            OutgoingFrame::Version { protocol, firmware } => {
+                bytes[0] = protocol;
+                bytes[1..4].copy_from_slice(&firmware);
+                (CLASS_STATUS, VERSION, 4)
+            }
+            OutgoingFrame::ParamValue(id, value) => {
+                bytes[0..6].copy_from_slice(&encode_param(id, value));
+                (CLASS_STATUS, PARAM_VALUE, 6)
+            }
+            OutgoingFrame::Faults { active, sticky } => {
+                bytes[0..2].copy_from_slice(&active.0.to_le_bytes());
+                bytes[2..4].copy_from_slice(&sticky.0.to_le_bytes());
+                (CLASS_FAULT, FAULTS, 4)
+            }
+            OutgoingFrame::FaultLogEntry { index, record } => {
+                bytes[0] = index;
+                match record {
+                    Some(record) => bytes[1..8].copy_from_slice(&record.to_bytes()),
+                    None => bytes[1] = NO_RECORD,
+                }
+                (CLASS_FAULT, FAULT_LOG_ENTRY, 8)
+            }
+            OutgoingFrame::MotorTemperature {
+                temperature,
+                derating,
+            } => {
+                bytes[0..4].copy_from_slice(&temperature.to_le_bytes());
+                bytes[4..8].copy_from_slice(&derating.to_le_bytes());
+                (CLASS_STATUS, MOTOR_TEMPERATURE, 8)
+            }
+            OutgoingFrame::BusVoltage(volts) => {
+                bytes[0..4].copy_from_slice(&volts.to_le_bytes());
+                (CLASS_STATUS, BUS_VOLTAGE, 4)
+            }
+            OutgoingFrame::SystemStatus {
+                active,
+                sticky,
+                mcu_temperature,
+                uptime,
+            } => {
+                bytes[0..2].copy_from_slice(&active.0.to_le_bytes());
+                bytes[2..4].copy_from_slice(&sticky.0.to_le_bytes());
+                bytes[4..5].copy_from_slice(&mcu_temperature.to_le_bytes());
+                bytes[5..8].copy_from_slice(&uptime.min(MAX_UPTIME).to_le_bytes()[..3]);
+                (CLASS_STATUS, SYSTEM_STATUS, 8)
+            }
+            OutgoingFrame::CanStatus {
+                tx_errors,
+                rx_errors,
+                bus_off_count,
+                tx_queue_depth,
+                state,
+                last_error,
+            } => {
+                bytes[0] = tx_errors;
+                bytes[1] = rx_errors;
+                bytes[2..4].copy_from_slice(&bus_off_count.to_le_bytes());
+                bytes[4] = tx_queue_depth;
+                bytes[5] = state as u8;
+                bytes[6] = last_error as u8;
+                (CLASS_STATUS, CAN_STATUS, 7)
+            }
+            OutgoingFrame::Output { duty, state } => {
+                bytes[0..2].copy_from_slice(&duty.to_le_bytes());
+                bytes[2] = state as u8;
+                (CLASS_STATUS, OUTPUT, 3)
+            }
+            OutgoingFrame::Boot { reset_cause, boot } => {
+                bytes[0] = reset_cause as u8;
+                bytes[1..3].copy_from_slice(&boot.to_le_bytes());
+                (CLASS_STATUS, BOOT, 3)
+            }
+        };
+
+        CanFrame::new_data(with_api(id, class, index), &bytes[..len]).unwrap()
+    }
+}
+
+/// Decodes a frame sent by a board. Used by host tools
+impl TryFrom<CanFrame> for OutgoingFrame {
+    type Error = FrameConversionError;
+    fn try_from(frame: CanFrame) -> Result<OutgoingFrame, FrameConversionError> {
+        let dlc = frame.dlc();
+
+        let (class, index) = api(frame.id()).ok_or(FrameConversionError::InvalidIdFormat)?;
+        if frame.is_remote_frame() {
+            return Err(FrameConversionError::FrameType);
+        }
+
+        let data = frame.data();
+        match (class, index) {
+            (CLASS_FAULT, ERROR) => {
+                check_frame_size!(1, dlc);
+                let code = ErrorCode::try_from(data[0])
+                    .map_err(|_| FrameConversionError::InvalidFrame("Unknown error code"))?;
+                Ok(OutgoingFrame::Error(code))
+            }
+            (CLASS_STATUS, UPDATE) => {
+                check_frame_size!(6, dlc);
+                Ok(OutgoingFrame::Update {
+                    duty_now: i16::from_le_bytes(data[0..2].try_into().unwrap()),
+                    current_now: f32::from_le_bytes(data[2..6].try_into().unwrap()),
+                })
+            }
+            (CLASS_FAULT, OVERCURRENT) => {
+                check_frame_size!(8, dlc);
+                Ok(OutgoingFrame::Overcurrent {
+                    current_now: f32::from_le_bytes(data[0..4].try_into().unwrap()),
+                    current_limit: f32::from_le_bytes(data[4..8].try_into().unwrap()),
+                })
+            }
+            (CLASS_STATUS, ENCODER) => {
+                check_frame_size!(8, dlc);
+                Ok(OutgoingFrame::Encoder {
+                    position: f32::from_le_bytes(data[0..4].try_into().unwrap()),
+                    velocity: f32::from_le_bytes(data[4..8].try_into().unwrap()),
+                })
+            }
+            (CLASS_STATUS, VERSION) => {
+                check_frame_size!(4, dlc);
+                Ok(OutgoingFrame::Version {
+                    protocol: data[0],
+                    firmware: [data[1], data[2], data[3]],
+                })
+            }
+            (CLASS_STATUS, PARAM_VALUE) => {
+                check_frame_size!(6, dlc);
+                let (id, value) = decode_param(data)?;
+                Ok(OutgoingFrame::ParamValue(id, value))
+            }
+            (CLASS_STATUS, MOTOR_TEMPERATURE) => {
+                check_frame_size!(8, dlc);
+                Ok(OutgoingFrame::MotorTemperature {
+                    temperature: f32::from_le_bytes(data[0..4].try_into().unwrap()),
+                    derating: f32::from_le_bytes(data[4..8].try_into().unwrap()),
+                })
+            }
+            (CLASS_STATUS, BUS_VOLTAGE) => {
+                check_frame_size!(4, dlc);
+                Ok(OutgoingFrame::BusVoltage(f32::from_le_bytes(
+                    data[0..4].try_into().unwrap(),
+                )))
+            }
+            (CLASS_STATUS, SYSTEM_STATUS) => {
+                check_frame_size!(8, dlc);
+                Ok(OutgoingFrame::SystemStatus {
+                    active: Faults(u16::from_le_bytes(data[0..2].try_into().unwrap())),
+                    sticky: Faults(u16::from_le_bytes(data[2..4].try_into().unwrap())),
+                    mcu_temperature: data[4] as i8,
+                    uptime: u32::from_le_bytes([data[5], data[6], data[7], 0]),
+                })
+            }
+            (CLASS_STATUS, CAN_STATUS) => {
+                check_frame_size!(7, dlc);
+                let state = CanBusState::try_from(data[5])
+                    .map_err(|_| FrameConversionError::InvalidFrame("Unknown can bus state"))?;
+                let last_error = CanLastError::try_from(data[6])
+                    .map_err(|_| FrameConversionError::InvalidFrame("Unknown can error"))?;
+                Ok(OutgoingFrame::CanStatus {
+                    tx_errors: data[0],
+                    rx_errors: data[1],
+                    bus_off_count: u16::from_le_bytes(data[2..4].try_into().unwrap()),
+                    tx_queue_depth: data[4],
+                    state,
+                    last_error,
+                })
+            }
+            (CLASS_STATUS, OUTPUT) => {
+                check_frame_size!(3, dlc);
+                let state = OutputState::try_from(data[2])
+                    .map_err(|_| FrameConversionError::InvalidFrame("Unknown output state"))?;
+                Ok(OutgoingFrame::Output {
+                    duty: i16::from_le_bytes(data[0..2].try_into().unwrap()),
+                    state,
+                })
+            }
+            (CLASS_STATUS, BOOT) => {
+                check_frame_size!(3, dlc);
+                let reset_cause = ResetCause::try_from(data[0])
+                    .map_err(|_| FrameConversionError::InvalidFrame("Unknown reset cause"))?;
+                Ok(OutgoingFrame::Boot {
+                    reset_cause,
+                    boot: u16::from_le_bytes(data[1..3].try_into().unwrap()),
+                })
+            }
+            (CLASS_FAULT, FAULTS) => {
+                check_frame_size!(4, dlc);
+                Ok(OutgoingFrame::Faults {
+                    active: Faults(u16::from_le_bytes(data[0..2].try_into().unwrap())),
+                    sticky: Faults(u16::from_le_bytes(data[2..4].try_into().unwrap())),
+                })
+            }
+            (CLASS_FAULT, FAULT_LOG_ENTRY) => {
+                check_frame_size!(8, dlc);
+                let record = if data[1] == NO_RECORD {
+                    None
+                } else {
+                    Some(
+                        FaultRecord::from_bytes(&data[1..8])
+                            .ok_or(FrameConversionError::InvalidFrame("Unknown fault event"))?,
+                    )
+                };
+                Ok(OutgoingFrame::FaultLogEntry {
+                    index: data[0],
+                    record,
+                })
+            }
+            (class, index) => Err(FrameConversionError::InvalidApi { class, index }),
+        }
+    }
+}
@@ -0,0 +1,867 @@
+//! Parameter table.
+//!
+//! Every setting of a board is a parameter with an id, a type, a range and a default.
+//! Hosts read parameters with `IncomingFrame::GetParam` and change them with
+//! `IncomingFrame::SetParam`. The board answers both with an `OutgoingFrame::ParamValue`
+//! carrying the value it holds afterwards, so a rejected value shows up as the old one.
+//!
+//! `Config` holds one value per parameter. The firmware keeps it in flash, the simulator in memory.
+//! To add a setting, give it an id in `ParamId`, an entry in `PARAMS`, and a field in `Config`.
+
+use core::convert::TryFrom;
+
+use crate::{
+    Fault, FaultAction, GroupSlot, IdleMode, PidGains, PositionInnerLoop, StatusFrame, BROADCAST,
+    GROUP_SLOTS, MAX_STATUS_RATE,
+};
+
+use ParamValue::*;
+
+/// Id of a parameter on the wire. Ids are grouped by area, with gaps for new parameters
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u16)]
+pub enum ParamId {
+    CurrentLimit = 0x00,
+    Inverted = 0x01,
+    IdleMode = 0x02,
+    EncoderCpr = 0x03,
+    RampUp = 0x04,
+    RampDown = 0x05,
+    SmartCurrentStall = 0x06,
+    SmartCurrentFree = 0x07,
+    SmartCurrentRpm = 0x08,
+    CurrentKp = 0x10,
+    CurrentKi = 0x11,
+    CurrentKd = 0x12,
+    VelocityKp = 0x13,
+    VelocityKi = 0x14,
+    VelocityKd = 0x15,
+    PositionKp = 0x16,
+    PositionKi = 0x17,
+    PositionKd = 0x18,
+    PositionTolerance = 0x20,
+    PositionOutputLimit = 0x21,
+    PositionInnerLoop = 0x22,
+    Group = 0x30,
+    GroupSlot = 0x31,
+    FollowLeader = 0x32,
+    HeartbeatTimeout = 0x33,
+    LeaderTimeout = 0x34,
+    DriverFaultAction = 0x40,
+    OvercurrentAction = 0x41,
+    OverheatAction = 0x42,
+    LeaderLossAction = 0x43,
+    HeartbeatLossAction = 0x44,
+    MotorResistance = 0x50,
+    MotorThermalResistance = 0x51,
+    MotorThermalTime = 0x52,
+    MotorTempLimit = 0x53,
+    UpdateRate = 0x60,
+    EncoderRate = 0x61,
+    BusVoltageRate = 0x62,
+    MotorTemperatureRate = 0x63,
+    FaultsRate = 0x64,
+    SystemStatusRate = 0x65,
+    CanStatusRate = 0x66,
+    OutputRate = 0x67,
+}
+
+impl ParamId {
+    /// Table entry of this parameter
+    pub fn info(self) -> &'static ParamInfo {
+        PARAMS.iter().find(|p| p.id == self).unwrap()
+    }
+}
+
+impl TryFrom<u16> for ParamId {
+    type Error = u16;
+
+    fn try_from(raw: u16) -> Result<Self, Self::Error> {
+        PARAMS
+            .iter()
+            .find(|p| p.id as u16 == raw)
+            .map(|p| p.id)
+            .ok_or(raw)
+    }
+}
+
+/// How a parameter value is stored
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ParamType {
+    Bool,
+    U8,
+    U16,
+    F32,
+}
+
+/// Value of a parameter. On the wire every value is a little endian `u32`,
+/// holding the integer or the bits of the float
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ParamValue {
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    F32(f32),
+}
+
+impl ParamValue {
+    pub fn ty(self) -> ParamType {
+        match self {
+            ParamValue::Bool(_) => ParamType::Bool,
+            ParamValue::U8(_) => ParamType::U8,
+            ParamValue::U16(_) => ParamType::U16,
+            ParamValue::F32(_) => ParamType::F32,
+        }
+    }
+
+    pub fn to_raw(self) -> u32 {
+        match self {
+            ParamValue::Bool(v) => v as u32,
+            ParamValue::U8(v) => v as u32,
+            ParamValue::U16(v) => v as u32,
+            ParamValue::F32(v) => v.to_bits(),
+        }
+    }
+
+    /// Interprets a raw value as `ty`. Returns `None` if it does not fit
+    pub fn from_raw(ty: ParamType, raw: u32) -> Option<ParamValue> {
+        match ty {
+            ParamType::Bool if raw <= 1 => Some(ParamValue::Bool(raw == 1)),
+            ParamType::U8 => u8::try_from(raw).ok().map(ParamValue::U8),
+            ParamType::U16 => u16::try_from(raw).ok().map(ParamValue::U16),
+            ParamType::F32 => Some(ParamValue::F32(f32::from_bits(raw))),
+            _ => None,
+        }
+    }
+
+    /// The value as a number, for range checks
+    pub fn as_f32(self) -> f32 {
+        match self {
+            ParamValue::Bool(v) => v as u8 as f32,
+            ParamValue::U8(v) => v as f32,
+            ParamValue::U16(v) => v as f32,
+            ParamValue::F32(v) => v,
+        }
+    }
+}
+
+/// Why a parameter was not set
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ParamError {
+    WrongType,
+    OutOfRange,
+}
+
+/// One entry of the parameter table
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParamInfo {
+    pub id: ParamId,
+    pub name: &'static str,
+    pub ty: ParamType,
+    pub unit: &'static str,
+    /// Smallest and largest accepted value, inclusive
+    pub min: f32,
+    pub max: f32,
+    pub default: ParamValue,
+    /// Names of enumerated values
+    pub values: &'static [(u32, &'static str)],
+}
+
+impl ParamInfo {
+    /// Checks the type and range of a value for this parameter
+    pub fn check(&self, value: ParamValue) -> Result<(), ParamError> {
+        if value.ty() != self.ty {
+            return Err(ParamError::WrongType);
+        }
+        // written this way around so NaN is out of range
+        let v = value.as_f32();
+        if v >= self.min && v <= self.max {
+            Ok(())
+        } else {
+            Err(ParamError::OutOfRange)
+        }
+    }
+}
+
+const fn param(
+    id: ParamId,
+    name: &'static str,
+    unit: &'static str,
+    min: f32,
+    max: f32,
+    default: ParamValue,
+) -> ParamInfo {
+    ParamInfo {
+        id,
+        name,
+        ty: match default {
+            ParamValue::Bool(_) => ParamType::Bool,
+            ParamValue::U8(_) => ParamType::U8,
+            ParamValue::U16(_) => ParamType::U16,
+            ParamValue::F32(_) => ParamType::F32,
+        },
+        unit,
+        min,
+        max,
+        default,
+        values: &[],
+    }
+}
+
+const fn with_values(mut info: ParamInfo, values: &'static [(u32, &'static str)]) -> ParamInfo {
+    info.values = values;
+    info
+}
+
+/// Rate of a status frame in hz
+const fn status_rate(id: ParamId, name: &'static str, default: u16) -> ParamInfo {
+    param(id, name, "Hz", 0.0, MAX_STATUS_RATE as f32, U16(default))
+}
+
+/// Largest gain we accept. Anything near this is a typo, but it keeps the loops finite
+const MAX_GAIN: f32 = 1.0e6;
+
+/// Highest current limit the current reference pwm can express, in amps
+const MAX_CURRENT_LIMIT: f32 = 59.0;
+
+/// Fastest ramp we accept. At 500hz anything faster reaches full scale in a single update
+const MAX_RAMP: f32 = 1000.0;
+
+const IDLE_MODES: &[(u32, &str)] = &[(0, "coast"), (1, "brake")];
+
+const INNER_LOOPS: &[(u32, &str)] = &[(0, "velocity"), (1, "current")];
+
+const GROUP_SLOT_VALUES: &[(u32, &str)] = &[(GROUP_SLOTS as u32, "none")];
+
+const LEADER_VALUES: &[(u32, &str)] = &[(BROADCAST as u32, "none")];
+
+const FAULT_ACTIONS: &[(u32, &str)] = &[(0, "warn"), (1, "coast"), (2, "brake"), (3, "disable")];
+
+/// Every parameter, in id order.
+///
+/// The current loop outputs a fraction of full duty cycle, so its kp is in 1/amps and ki in
+/// 1/(amps * seconds). The velocity loop does too, so its kp is in 1/rpm. The position loop outputs
+/// a setpoint for its inner loop, so with the velocity inner loop its kp is in rpm/rotation.
+pub const PARAMS: &[ParamInfo] = &[
+    param(
+        ParamId::CurrentLimit,
+        "current_limit",
+        "A",
+        0.0,
+        MAX_CURRENT_LIMIT,
+        U8(10),
+    ),
+    param(ParamId::Inverted, "inverted", "", 0.0, 1.0, Bool(false)),
+    with_values(
+        param(ParamId::IdleMode, "idle_mode", "", 0.0, 1.0, U8(0)),
+        IDLE_MODES,
+    ),
+    param(
+        ParamId::EncoderCpr,
+        "encoder_cpr",
+        "",
+        1.0,
+        65535.0,
+        U16(2048),
+    ),
+    param(ParamId::RampUp, "ramp_up", "1/s", 0.0, MAX_RAMP, F32(0.0)),
+    param(
+        ParamId::RampDown,
+        "ramp_down",
+        "1/s",
+        0.0,
+        MAX_RAMP,
+        F32(0.0),
+    ),
+    param(
+        ParamId::SmartCurrentStall,
+        "smart_current_stall",
+        "A",
+        0.0,
+        MAX_CURRENT_LIMIT,
+        F32(0.0),
+    ),
+    param(
+        ParamId::SmartCurrentFree,
+        "smart_current_free",
+        "A",
+        0.0,
+        MAX_CURRENT_LIMIT,
+        F32(0.0),
+    ),
+    param(
+        ParamId::SmartCurrentRpm,
+        "smart_current_rpm",
+        "rpm",
+        0.0,
+        1.0e5,
+        F32(5000.0),
+    ),
+    param(
+        ParamId::CurrentKp,
+        "current_kp",
+        "",
+        0.0,
+        MAX_GAIN,
+        F32(0.02),
+    ),
+    param(
+        ParamId::CurrentKi,
+        "current_ki",
+        "",
+        0.0,
+        MAX_GAIN,
+        F32(2.0),
+    ),
+    param(
+        ParamId::CurrentKd,
+        "current_kd",
+        "",
+        0.0,
+        MAX_GAIN,
+        F32(0.0),
+    ),
+    param(
+        ParamId::VelocityKp,
+        "velocity_kp",
+        "",
+        0.0,
+        MAX_GAIN,
+        F32(0.0005),
+    ),
+    param(
+        ParamId::VelocityKi,
+        "velocity_ki",
+        "",
+        0.0,
+        MAX_GAIN,
+        F32(0.01),
+    ),
+    param(
+        ParamId::VelocityKd,
+        "velocity_kd",
+        "",
+        0.0,
+        MAX_GAIN,
+        F32(0.0),
+    ),
+    param(
+        ParamId::PositionKp,
+        "position_kp",
+        "",
+        0.0,
+        MAX_GAIN,
+        F32(60.0),
+    ),
+    param(
+        ParamId::PositionKi,
+        "position_ki",
+        "",
+        0.0,
+        MAX_GAIN,
+        F32(0.0),
+    ),
+    param(
+        ParamId::PositionKd,
+        "position_kd",
+        "",
+        0.0,
+        MAX_GAIN,
+        F32(0.0),
+    ),
+    param(
+        ParamId::PositionTolerance,
+        "position_tolerance",
+        "rot",
+        0.0,
+        1000.0,
+        F32(0.01),
+    ),
+    param(
+        ParamId::PositionOutputLimit,
+        "position_output_limit",
+        "",
+        0.0,
+        1.0e5,
+        F32(1000.0),
+    ),
+    with_values(
+        param(
+            ParamId::PositionInnerLoop,
+            "position_inner_loop",
+            "",
+            0.0,
+            1.0,
+            U8(0),
+        ),
+        INNER_LOOPS,
+    ),
+    param(ParamId::Group, "group", "", 0.0, 255.0, U8(0)),
+    with_values(
+        param(
+            ParamId::GroupSlot,
+            "group_slot",
+            "",
+            0.0,
+            GROUP_SLOTS as f32,
+            U8(GROUP_SLOTS as u8),
+        ),
+        GROUP_SLOT_VALUES,
+    ),
+    with_values(
+        param(
+            ParamId::FollowLeader,
+            "follow_leader",
+            "",
+            0.0,
+            255.0,
+            U8(BROADCAST),
+        ),
+        LEADER_VALUES,
+    ),
+    // the longest time the cycle counter of the firmware can measure is about 30s
+    param(
+        ParamId::HeartbeatTimeout,
+        "heartbeat_timeout",
+        "ms",
+        0.0,
+        10_000.0,
+        U16(0),
+    ),
+    // a leader has to send updates more often than this, see `update_rate`
+    param(
+        ParamId::LeaderTimeout,
+        "leader_timeout",
+        "ms",
+        1.0,
+        10_000.0,
+        U16(200),
+    ),
+    with_values(
+        param(
+            ParamId::DriverFaultAction,
+            "driver_fault_action",
+            "",
+            0.0,
+            3.0,
+            U8(3),
+        ),
+        FAULT_ACTIONS,
+    ),
+    with_values(
+        param(
+            ParamId::OvercurrentAction,
+            "overcurrent_action",
+            "",
+            0.0,
+            3.0,
+            U8(0),
+        ),
+        FAULT_ACTIONS,
+    ),
+    with_values(
+        param(
+            ParamId::OverheatAction,
+            "overheat_action",
+            "",
+            0.0,
+            3.0,
+            U8(0),
+        ),
+        FAULT_ACTIONS,
+    ),
+    with_values(
+        param(
+            ParamId::LeaderLossAction,
+            "leader_loss_action",
+            "",
+            0.0,
+            3.0,
+            U8(0),
+        ),
+        FAULT_ACTIONS,
+    ),
+    with_values(
+        param(
+            ParamId::HeartbeatLossAction,
+            "heartbeat_loss_action",
+            "",
+            0.0,
+            3.0,
+            U8(0),
+        ),
+        FAULT_ACTIONS,
+    ),
+    param(
+        ParamId::MotorResistance,
+        "motor_resistance",
+        "ohm",
+        0.0,
+        100.0,
+        F32(0.14),
+    ),
+    param(
+        ParamId::MotorThermalResistance,
+        "motor_thermal_resistance",
+        "degC/W",
+        0.0,
+        1000.0,
+        F32(8.0),
+    ),
+    param(
+        ParamId::MotorThermalTime,
+        "motor_thermal_time",
+        "s",
+        1.0,
+        1.0e4,
+        F32(30.0),
+    ),
+    param(
+        ParamId::MotorTempLimit,
+        "motor_temp_limit",
+        "degC",
+        0.0,
+        250.0,
+        F32(0.0),
+    ),
+    // the frames boards used to send at a fixed 20hz keep that rate by default
+    status_rate(ParamId::UpdateRate, "update_rate", 20),
+    status_rate(ParamId::EncoderRate, "encoder_rate", 20),
+    status_rate(ParamId::BusVoltageRate, "bus_voltage_rate", 20),
+    status_rate(ParamId::MotorTemperatureRate, "motor_temperature_rate", 20),
+    status_rate(ParamId::FaultsRate, "faults_rate", 20),
+    status_rate(ParamId::SystemStatusRate, "system_status_rate", 10),
+    status_rate(ParamId::CanStatusRate, "can_status_rate", 10),
+    status_rate(ParamId::OutputRate, "output_rate", 20),
+];
+
+/// Every setting that survives a power cycle. Each field is a parameter in `PARAMS`,
+/// which also holds the defaults
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// current limit in amps
+    pub current_limit: u8,
+
+    /// if this value is set to true, all setpoints recieved are negated
+    pub inverted: bool,
+
+    /// used when motor setpoint is 0 or below a certain threshold
+    pub idle_mode: IdleMode,
+
+    /// Encoder resolution in counts per revolution (after quadrature decoding)
+    pub encoder_cpr: u16,
+
+    /// Open loop ramp rates in full scale duty cycle per second, 0 for no ramp.
+    /// `ramp_up` applies while the output moves away from zero, `ramp_down` towards it
+    pub ramp_up: f32,
+    pub ramp_down: f32,
+
+    /// Software current limit in amps at standstill and at `smart_current_rpm`,
+    /// see `SmartCurrentLimit`. A stall limit of 0 turns it off, a free limit of 0 uses the stall limit
+    pub smart_current_stall: f32,
+    pub smart_current_free: f32,
+    pub smart_current_rpm: f32,
+
+    pub current_gains: PidGains,
+    pub velocity_gains: PidGains,
+    pub position_gains: PidGains,
+
+    /// Position errors smaller than this (in rotations) are treated as on target
+    pub position_tolerance: f32,
+
+    /// Clamp on the position loop output, in units of the inner loop (rpm or amps)
+    pub position_output_limit: f32,
+
+    /// Which loop the position loop feeds into
+    pub position_inner_loop: PositionInnerLoop,
+
+    /// Group this board takes group setpoints from, see `group`
+    pub group_number: u8,
+
+    /// Slot in the group, or `GROUP_SLOTS` when the board is not in a group
+    pub group_slot: u8,
+
+    /// Board whose output this board mirrors, or `BROADCAST` when it is not a follower, see `leader`
+    pub follow_leader: u8,
+
+    /// The motor stops if no heartbeat arrives for this many milliseconds, 0 for never,
+    /// see `heartbeat_timeout`
+    pub heartbeat_timeout: u16,
+
+    /// A follower stops if its leader sends no update for this many milliseconds
+    pub leader_timeout: u16,
+
+    /// What the board does after the motor driver reports a fault, see `FaultManager`
+    pub driver_fault_action: FaultAction,
+
+    /// What the board does after the driver starts chopping at the current limit
+    pub overcurrent_action: FaultAction,
+
+    /// What the board does once the thermal model starts derating the current
+    pub overheat_action: FaultAction,
+
+    /// What the board does after its leader stops sending updates
+    pub leader_loss_action: FaultAction,
+
+    /// What the board does after heartbeats stop arriving
+    pub heartbeat_loss_action: FaultAction,
+
+    /// Thermal model of the motor, see `ThermalModel`. The defaults fit a 550 size motor.
+    /// A temperature limit of 0 turns derating off
+    pub motor_resistance: f32,
+    pub motor_thermal_resistance: f32,
+    pub motor_thermal_time: f32,
+    pub motor_temp_limit: f32,
+
+    /// Rates of the status frames in hz, 0 for off, see `status_rate`
+    pub update_rate: u16,
+    pub encoder_rate: u16,
+    pub bus_voltage_rate: u16,
+    pub motor_temperature_rate: u16,
+    pub faults_rate: u16,
+    pub system_status_rate: u16,
+    pub can_status_rate: u16,
+    pub output_rate: u16,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut config = Config {
+            current_limit: 0,
+            inverted: false,
+            idle_mode: IdleMode::Coast,
+            encoder_cpr: 1,
+            ramp_up: 0.0,
+            ramp_down: 0.0,
+            smart_current_stall: 0.0,
+            smart_current_free: 0.0,
+            smart_current_rpm: 0.0,
+            current_gains: PidGains::new(0.0, 0.0, 0.0),
+            velocity_gains: PidGains::new(0.0, 0.0, 0.0),
+            position_gains: PidGains::new(0.0, 0.0, 0.0),
+            position_tolerance: 0.0,
+            position_output_limit: 0.0,
+            position_inner_loop: PositionInnerLoop::Velocity,
+            group_number: 0,
+            group_slot: GROUP_SLOTS as u8,
+            follow_leader: BROADCAST,
+            heartbeat_timeout: 0,
+            leader_timeout: 0,
+            driver_fault_action: FaultAction::Warn,
+            overcurrent_action: FaultAction::Warn,
+            overheat_action: FaultAction::Warn,
+            leader_loss_action: FaultAction::Warn,
+            heartbeat_loss_action: FaultAction::Warn,
+            motor_resistance: 0.0,
+            motor_thermal_resistance: 0.0,
+            motor_thermal_time: 1.0,
+            motor_temp_limit: 0.0,
+            update_rate: 0,
+            encoder_rate: 0,
+            bus_voltage_rate: 0,
+            motor_temperature_rate: 0,
+            faults_rate: 0,
+            system_status_rate: 0,
+            can_status_rate: 0,
+            output_rate: 0,
+        };
+        for param in PARAMS {
+            config.set(param.id, param.default).unwrap();
+        }
+        config
+    }
+}
+
+impl Config {
+    /// Group membership, if the board is in a group
+    pub fn group(&self) -> Option<GroupSlot> {
+        if (self.group_slot as usize) < GROUP_SLOTS {
+            Some(GroupSlot {
+                group: self.group_number,
+                slot: self.group_slot,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The board this board follows, if it is a follower.
+    /// A follower mirrors the `duty_now` of the leader's `OutgoingFrame::Update` frames
+    pub fn leader(&self) -> Option<u8> {
+        if self.follow_leader != BROADCAST {
+            Some(self.follow_leader)
+        } else {
+            None
+        }
+    }
+
+    /// How long the board drives its motor after the last `IncomingFrame::HeartBeat`,
+    /// in milliseconds. `None` if it does not need heartbeats
+    pub fn heartbeat_timeout(&self) -> Option<u16> {
+        if self.heartbeat_timeout != 0 {
+            Some(self.heartbeat_timeout)
+        } else {
+            None
+        }
+    }
+
+    /// The configured reaction to a fault
+    pub fn fault_action(&self, fault: Fault) -> FaultAction {
+        match fault {
+            Fault::DriverFault => self.driver_fault_action,
+            Fault::Overcurrent => self.overcurrent_action,
+            Fault::Overheat => self.overheat_action,
+            Fault::LeaderLoss => self.leader_loss_action,
+            Fault::HeartbeatLoss => self.heartbeat_loss_action,
+        }
+    }
+
+    /// How often a status frame is sent, in hz. 0 means never
+    pub fn status_rate(&self, frame: StatusFrame) -> u16 {
+        match frame {
+            StatusFrame::Update => self.update_rate,
+            StatusFrame::Encoder => self.encoder_rate,
+            StatusFrame::BusVoltage => self.bus_voltage_rate,
+            StatusFrame::MotorTemperature => self.motor_temperature_rate,
+            StatusFrame::Faults => self.faults_rate,
+            StatusFrame::SystemStatus => self.system_status_rate,
+            StatusFrame::CanStatus => self.can_status_rate,
+            StatusFrame::Output => self.output_rate,
+        }
+    }
+
+    pub fn get(&self, id: ParamId) -> ParamValue {
+        match id {
+            ParamId::CurrentLimit => U8(self.current_limit),
+            ParamId::Inverted => Bool(self.inverted),
+            ParamId::IdleMode => U8(match self.idle_mode {
+                IdleMode::Coast => 0,
+                IdleMode::Brake => 1,
+            }),
+            ParamId::EncoderCpr => U16(self.encoder_cpr),
+            ParamId::RampUp => F32(self.ramp_up),
+            ParamId::RampDown => F32(self.ramp_down),
+            ParamId::SmartCurrentStall => F32(self.smart_current_stall),
+            ParamId::SmartCurrentFree => F32(self.smart_current_free),
+            ParamId::SmartCurrentRpm => F32(self.smart_current_rpm),
+            ParamId::CurrentKp => F32(self.current_gains.kp),
+            ParamId::CurrentKi => F32(self.current_gains.ki),
+            ParamId::CurrentKd => F32(self.current_gains.kd),
+            ParamId::VelocityKp => F32(self.velocity_gains.kp),
+            ParamId::VelocityKi => F32(self.velocity_gains.ki),
+            ParamId::VelocityKd => F32(self.velocity_gains.kd),
+            ParamId::PositionKp => F32(self.position_gains.kp),
+            ParamId::PositionKi => F32(self.position_gains.ki),
+            ParamId::PositionKd => F32(self.position_gains.kd),
+            ParamId::PositionTolerance => F32(self.position_tolerance),
+            ParamId::PositionOutputLimit => F32(self.position_output_limit),
+            ParamId::PositionInnerLoop => U8(match self.position_inner_loop {
+                PositionInnerLoop::Velocity => 0,
+                PositionInnerLoop::Current => 1,
+            }),
+            ParamId::Group => U8(self.group_number),
+            ParamId::GroupSlot => U8(self.group_slot),
+            ParamId::FollowLeader => U8(self.follow_leader),
+            ParamId::HeartbeatTimeout => U16(self.heartbeat_timeout),
+            ParamId::LeaderTimeout => U16(self.leader_timeout),
+            ParamId::DriverFaultAction => U8(self.driver_fault_action as u8),
+            ParamId::OvercurrentAction => U8(self.overcurrent_action as u8),
+            ParamId::OverheatAction => U8(self.overheat_action as u8),
+            ParamId::LeaderLossAction => U8(self.leader_loss_action as u8),
+            ParamId::HeartbeatLossAction => U8(self.heartbeat_loss_action as u8),
+            ParamId::MotorResistance => F32(self.motor_resistance),
+            ParamId::MotorThermalResistance => F32(self.motor_thermal_resistance),
+            ParamId::MotorThermalTime => F32(self.motor_thermal_time),
+            ParamId::MotorTempLimit => F32(self.motor_temp_limit),
+            ParamId::UpdateRate => U16(self.update_rate),
+            ParamId::EncoderRate => U16(self.encoder_rate),
+            ParamId::BusVoltageRate => U16(self.bus_voltage_rate),
+            ParamId::MotorTemperatureRate => U16(self.motor_temperature_rate),
+            ParamId::FaultsRate => U16(self.faults_rate),
+            ParamId::SystemStatusRate => U16(self.system_status_rate),
+            ParamId::CanStatusRate => U16(self.can_status_rate),
+            ParamId::OutputRate => U16(self.output_rate),
+        }
+    }
+
+    /// Sets a parameter after checking its type and range.
+    /// The config is unchanged if the value is rejected
+    pub fn set(&mut self, id: ParamId, value: ParamValue) -> Result<(), ParamError> {
+        id.info().check(value)?;
+        match (id, value) {
+            (ParamId::CurrentLimit, U8(v)) => self.current_limit = v,
+            (ParamId::Inverted, Bool(v)) => self.inverted = v,
+            (ParamId::IdleMode, U8(v)) => {
+                self.idle_mode = if v == 0 {
+                    IdleMode::Coast
+                } else {
+                    IdleMode::Brake
+                }
+            }
+            (ParamId::EncoderCpr, U16(v)) => self.encoder_cpr = v,
+            (ParamId::RampUp, F32(v)) => self.ramp_up = v,
+            (ParamId::RampDown, F32(v)) => self.ramp_down = v,
+            (ParamId::SmartCurrentStall, F32(v)) => self.smart_current_stall = v,
+            (ParamId::SmartCurrentFree, F32(v)) => self.smart_current_free = v,
+            (ParamId::SmartCurrentRpm, F32(v)) => self.smart_current_rpm = v,
+            (ParamId::CurrentKp, F32(v)) => self.current_gains.kp = v,
+            (ParamId::CurrentKi, F32(v)) => self.current_gains.ki = v,
+            (ParamId::CurrentKd, F32(v)) => self.current_gains.kd = v,
+            (ParamId::VelocityKp, F32(v)) => self.velocity_gains.kp = v,
+            (ParamId::VelocityKi, F32(v)) => self.velocity_gains.ki = v,
+            (ParamId::VelocityKd, F32(v)) => self.velocity_gains.kd = v,
+            (ParamId::PositionKp, F32(v)) => self.position_gains.kp = v,
+            (ParamId::PositionKi, F32(v)) => self.position_gains.ki = v,
+            (ParamId::PositionKd, F32(v)) => self.position_gains.kd = v,
+            (ParamId::PositionTolerance, F32(v)) => self.position_tolerance = v,
+            (ParamId::PositionOutputLimit, F32(v)) => self.position_output_limit = v,
+            (ParamId::PositionInnerLoop, U8(v)) => {
+                self.position_inner_loop = if v == 0 {
+                    PositionInnerLoop::Velocity
+                } else {
+                    PositionInnerLoop::Current
+                }
+            }
+            (ParamId::Group, U8(v)) => self.group_number = v,
+            (ParamId::GroupSlot, U8(v)) => self.group_slot = v,
+            (ParamId::FollowLeader, U8(v)) => self.follow_leader = v,
+            (ParamId::HeartbeatTimeout, U16(v)) => self.heartbeat_timeout = v,
+            (ParamId::LeaderTimeout, U16(v)) => self.leader_timeout = v,
+            (ParamId::DriverFaultAction, U8(v)) => self.driver_fault_action = fault_action(v),
+            (ParamId::OvercurrentAction, U8(v)) => self.overcurrent_action = fault_action(v),
+            (ParamId::OverheatAction, U8(v)) => self.overheat_action = fault_action(v),
+            (ParamId::LeaderLossAction, U8(v)) => self.leader_loss_action = fault_action(v),
+            (ParamId::HeartbeatLossAction, U8(v)) => self.heartbeat_loss_action = fault_action(v),
+            (ParamId::MotorResistance, F32(v)) => self.motor_resistance = v,
+            (ParamId::MotorThermalResistance, F32(v)) => self.motor_thermal_resistance = v,
+            (ParamId::MotorThermalTime, F32(v)) => self.motor_thermal_time = v,
+            (ParamId::MotorTempLimit, F32(v)) => self.motor_temp_limit = v,
+            (ParamId::UpdateRate, U16(v)) => self.update_rate = v,
+            (ParamId::EncoderRate, U16(v)) => self.encoder_rate = v,
+            (ParamId::BusVoltageRate, U16(v)) => self.bus_voltage_rate = v,
+            (ParamId::MotorTemperatureRate, U16(v)) => self.motor_temperature_rate = v,
+            (ParamId::FaultsRate, U16(v)) => self.faults_rate = v,
+            (ParamId::SystemStatusRate, U16(v)) => self.system_status_rate = v,
+            (ParamId::CanStatusRate, U16(v)) => self.can_status_rate = v,
+            (ParamId::OutputRate, U16(v)) => self.output_rate = v,
+            // `check` already compared the type with the table
+            _ => return Err(ParamError::WrongType),
+        }
+        Ok(())
+    }
+}
+
+/// Fault action with the number `FAULT_ACTIONS` gives it. Only called after a range check
+fn fault_action(raw: u8) -> FaultAction {
+    match raw {
+        0 => FaultAction::Warn,
+        1 => FaultAction::Coast,
+        2 => FaultAction::Brake,
+        _ => FaultAction::Disable,
+    }
+}
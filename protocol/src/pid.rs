@@ -0,0 +1,89 @@
+/// Gains of a PID controller
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PidGains {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+}
+
+impl PidGains {
+    pub const fn new(kp: f32, ki: f32, kd: f32) -> Self {
+        Self { kp, ki, kd }
+    }
+}
+
+/// PID controller state.
+/// The gains are passed in on every update so they can be changed at runtime
+/// without touching the controller state.
+pub struct Pid {
+    integrator: f32,
+    last_error: Option<f32>,
+    min_output: f32,
+    max_output: f32,
+}
+
+impl Pid {
+    pub const fn new(min_output: f32, max_output: f32) -> Self {
+        Self {
+            integrator: 0.0,
+            last_error: None,
+            min_output,
+            max_output,
+        }
+    }
+
+    /// Changes the output clamp.
+    /// The integrator is pulled back inside the new range right away
+    pub fn set_output_limits(&mut self, min_output: f32, max_output: f32) {
+        self.min_output = min_output;
+        self.max_output = max_output;
+        self.integrator = clamp(self.integrator, min_output, max_output);
+    }
+
+    /// Clears the integrator and derivative history.
+    /// This should be called whenever the loop is not in control of the output
+    pub fn reset(&mut self) {
+        self.integrator = 0.0;
+        self.last_error = None;
+    }
+
+    /// Runs one iteration of the controller and returns the clamped output
+    pub fn update(&mut self, gains: &PidGains, error: f32, dt: f32) -> f32 {
+        let p = gains.kp * error;
+
+        let d = match self.last_error {
+            Some(last) => gains.kd * (error - last) / dt,
+            None => 0.0,
+        };
+        self.last_error = Some(error);
+
+        let unclamped = p + self.integrator + d;
+
+        // anti-windup: stop integrating while the output is saturated,
+        // unless the error would pull it back out of saturation
+        let saturated_high = unclamped >= self.max_output && error > 0.0;
+        let saturated_low = unclamped <= self.min_output && error < 0.0;
+        if !saturated_high && !saturated_low {
+            // also keep the integrator inside the output range on its own
+            self.integrator = clamp(
+                self.integrator + gains.ki * error * dt,
+                self.min_output,
+                self.max_output,
+            );
+        }
+
+        clamp(p + self.integrator + d, self.min_output, self.max_output)
+    }
+}
+
+#[inline]
+fn clamp(val: f32, min: f32, max: f32) -> f32 {
+    if val < min {
+        min
+    } else if val > max {
+        max
+    } else {
+        val
+    }
+}
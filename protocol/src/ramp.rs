@@ -0,0 +1,69 @@
+/// Full scale duty cycle, the unit of `Ramp` rates
+const FULL_SCALE: f32 = i16::MAX as f32;
+
+/// Slew limiter for the duty cycle setpoint.
+/// Rates are in full scale per second and are passed in on every update, like `PidGains`.
+/// A rate of 0 lets the output jump straight to the setpoint
+pub struct Ramp {
+    output: f32,
+}
+
+impl Ramp {
+    pub const fn new() -> Self {
+        Self { output: 0.0 }
+    }
+
+    /// Jumps to an output.
+    /// This should be called whenever something else is in control of the output,
+    /// so the ramp picks up from there
+    pub fn reset(&mut self, output: i16) {
+        self.output = output as f32;
+    }
+
+    /// Moves the output towards `setpoint` and returns it.
+    /// `up` limits the output while it moves away from zero and `down` while it moves
+    /// towards zero, so a reversal slows down at `down` before speeding up at `up`
+    pub fn update(&mut self, setpoint: i16, up: f32, down: f32, dt: f32) -> i16 {
+        let setpoint = setpoint as f32;
+        let mut output = self.output;
+
+        // slow down first, stopping at zero if the setpoint is the other way
+        if output * (setpoint - output) < 0.0 {
+            let target = if output * setpoint < 0.0 {
+                0.0
+            } else {
+                setpoint
+            };
+            output = approach(output, target, down * FULL_SCALE * dt);
+        }
+        // then speed up, once the output is at zero or on the side of the setpoint
+        if output * setpoint >= 0.0 && setpoint.abs() > output.abs() {
+            output = approach(output, setpoint, up * FULL_SCALE * dt);
+        }
+
+        self.output = output;
+        output as i16
+    }
+}
+
+impl Default for Ramp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Steps from `from` towards `to` by at most `max_step`, or all the way if `max_step` is 0
+#[inline]
+fn approach(from: f32, to: f32, max_step: f32) -> f32 {
+    if max_step <= 0.0 {
+        return to;
+    }
+    let step = to - from;
+    if step > max_step {
+        from + max_step
+    } else if step < -max_step {
+        from - max_step
+    } else {
+        to
+    }
+}
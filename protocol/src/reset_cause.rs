@@ -0,0 +1,77 @@
+use core::convert::TryFrom;
+
+// Reset flags in the RCC_CSR register of the stm32f1
+const PIN_RESET: u32 = 1 << 26;
+const POWER_ON_RESET: u32 = 1 << 27;
+const SOFTWARE_RESET: u32 = 1 << 28;
+const WATCHDOG_RESET: u32 = 1 << 29;
+const WINDOW_WATCHDOG_RESET: u32 = 1 << 30;
+const LOW_POWER_RESET: u32 = 1 << 31;
+
+/// Why a board last reset, see `OutgoingFrame::Boot`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum ResetCause {
+    /// The supply came up from nothing
+    PowerOn = 0,
+    /// The supply dipped low enough to reset the chip, but not long enough to lose the ram
+    BrownOut = 1,
+    /// Something pulled the reset pin, like a programmer or the reset button
+    Pin = 2,
+    /// The firmware reset itself, like on the way to or from the bootloader
+    Software = 3,
+    /// The independent watchdog was not fed, so the firmware was stuck
+    Watchdog = 4,
+    WindowWatchdog = 5,
+    /// The chip entered standby or stop while that is configured to reset it
+    LowPower = 6,
+    /// No reset flag was set
+    Unknown = 7,
+}
+
+impl ResetCause {
+    /// Cause from the reset flags of the RCC_CSR register.
+    ///
+    /// Every reset pulses the reset pin, so the pin flag only counts when nothing else is set.
+    /// The stm32f1 has no brown out flag, a dip in the supply sets the power on flag too.
+    /// Unlike a cold start it leaves the ram intact, which `ram_kept` tells, usually from
+    /// whether the `FaultLog` survived
+    pub fn from_flags(csr: u32, ram_kept: bool) -> Self {
+        if csr & LOW_POWER_RESET != 0 {
+            ResetCause::LowPower
+        } else if csr & WINDOW_WATCHDOG_RESET != 0 {
+            ResetCause::WindowWatchdog
+        } else if csr & WATCHDOG_RESET != 0 {
+            ResetCause::Watchdog
+        } else if csr & POWER_ON_RESET != 0 && ram_kept {
+            ResetCause::BrownOut
+        } else if csr & POWER_ON_RESET != 0 {
+            ResetCause::PowerOn
+        } else if csr & SOFTWARE_RESET != 0 {
+            ResetCause::Software
+        } else if csr & PIN_RESET != 0 {
+            ResetCause::Pin
+        } else {
+            ResetCause::Unknown
+        }
+    }
+}
+
+impl TryFrom<u8> for ResetCause {
+    type Error = u8;
+
+    fn try_from(raw: u8) -> Result<Self, Self::Error> {
+        match raw {
+            0 => Ok(ResetCause::PowerOn),
+            1 => Ok(ResetCause::BrownOut),
+            2 => Ok(ResetCause::Pin),
+            3 => Ok(ResetCause::Software),
+            4 => Ok(ResetCause::Watchdog),
+            5 => Ok(ResetCause::WindowWatchdog),
+            6 => Ok(ResetCause::LowPower),
+            7 => Ok(ResetCause::Unknown),
+            _ => Err(raw),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::Config;
+
+/// Number of periodic status frames
+pub const STATUS_FRAMES: usize = 8;
+
+/// Fastest rate of a status frame in hz. Schedules are advanced in whole milliseconds
+pub const MAX_STATUS_RATE: u16 = 1000;
+
+/// A frame a board sends on its own, each at the rate `Config::status_rate` gives it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum StatusFrame {
+    /// `OutgoingFrame::Update`, the output and the current
+    Update = 0,
+    /// `OutgoingFrame::Encoder`
+    Encoder = 1,
+    /// `OutgoingFrame::BusVoltage`
+    BusVoltage = 2,
+    /// `OutgoingFrame::MotorTemperature`, only sent while the thermal limit is on
+    MotorTemperature = 3,
+    /// `OutgoingFrame::Faults`, only sent while a fault is sticky
+    Faults = 4,
+    /// `OutgoingFrame::SystemStatus`
+    SystemStatus = 5,
+    /// `OutgoingFrame::CanStatus`
+    CanStatus = 6,
+    /// `OutgoingFrame::Output`
+    Output = 7,
+}
+
+impl StatusFrame {
+    /// Every status frame, in the order they are queued
+    pub const ALL: [StatusFrame; STATUS_FRAMES] = [
+        StatusFrame::Update,
+        StatusFrame::Encoder,
+        StatusFrame::BusVoltage,
+        StatusFrame::MotorTemperature,
+        StatusFrame::Faults,
+        StatusFrame::SystemStatus,
+        StatusFrame::CanStatus,
+        StatusFrame::Output,
+    ];
+}
+
+/// Decides when each status frame is due.
+///
+/// Every frame earns its rate in credit for each millisecond, and is due again once it has
+/// earned a period's worth of 1000. The remainder carries over, so rates that do not divide
+/// a second or the tick keep their average. A rate of 0 turns a frame off
+pub struct StatusSchedule {
+    /// Time since each frame was last checked
+    since_ms: [u32; STATUS_FRAMES],
+    /// Milliseconds times the rate each frame has earned towards its next period
+    credit: [u32; STATUS_FRAMES],
+}
+
+/// Credit of one period, whatever the rate
+const PERIOD_CREDIT: u32 = 1000;
+
+impl StatusSchedule {
+    pub const fn new() -> Self {
+        Self {
+            since_ms: [0; STATUS_FRAMES],
+            credit: [0; STATUS_FRAMES],
+        }
+    }
+
+    /// Advances every frame by `dt_ms` milliseconds
+    pub fn advance(&mut self, dt_ms: u32) {
+        for since in self.since_ms.iter_mut() {
+            *since = since.saturating_add(dt_ms);
+        }
+    }
+
+    /// Whether a frame should be sent now. Call this once per frame after every `advance`
+    pub fn due(&mut self, config: &Config, frame: StatusFrame) -> bool {
+        let since = core::mem::replace(&mut self.since_ms[frame as usize], 0);
+        let credit = &mut self.credit[frame as usize];
+        let rate = config.status_rate(frame).min(MAX_STATUS_RATE) as u32;
+        if rate == 0 {
+            *credit = 0;
+            return false;
+        }
+        *credit = credit.saturating_add(since.saturating_mul(rate));
+        if *credit >= PERIOD_CREDIT {
+            // a late tick or a faster rate sends once, not a burst to catch up
+            *credit = (*credit - PERIOD_CREDIT) % PERIOD_CREDIT;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Default for StatusSchedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
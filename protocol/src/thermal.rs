@@ -0,0 +1,64 @@
+use crate::Config;
+
+/// Temperature the model starts from and cools down to, in degrees celsius.
+/// We have no sensor for it, so this assumes a warm room rather than a cold one
+pub const AMBIENT_TEMPERATURE: f32 = 25.0;
+
+/// The allowed current fades from full to nothing over this many degrees below the limit
+const DERATING_BAND: f32 = 20.0;
+
+/// First order thermal model of the motor windings.
+///
+/// The windings heat up with `current^2 * Config::motor_resistance` and settle at
+/// `Config::motor_thermal_resistance` degrees above ambient per watt, with the time constant
+/// `Config::motor_thermal_time`. Close to `Config::motor_temp_limit` the allowed current
+/// is derated through `SmartCurrentLimit`. A limit of 0 turns derating off, the estimate
+/// is kept up to date either way
+pub struct ThermalModel {
+    /// Estimated winding temperature above ambient
+    rise: f32,
+}
+
+impl ThermalModel {
+    pub const fn new() -> Self {
+        Self { rise: 0.0 }
+    }
+
+    /// Feeds the model one current sample in amps, taken `dt` seconds after the previous one
+    pub fn update(&mut self, config: &Config, current: f32, dt: f32) {
+        let power = current * current * config.motor_resistance;
+        let settled = power * config.motor_thermal_resistance;
+        let step = (dt / config.motor_thermal_time).min(1.0);
+        self.rise += (settled - self.rise) * step;
+    }
+
+    /// Estimated winding temperature in degrees celsius
+    pub fn temperature(&self) -> f32 {
+        AMBIENT_TEMPERATURE + self.rise
+    }
+
+    /// Fraction of the current limit still allowed, from 1 well below the limit to 0 at it
+    pub fn derating(&self, config: &Config) -> f32 {
+        let limit = config.motor_temp_limit;
+        if limit <= 0.0 {
+            return 1.0;
+        }
+        ((limit - self.temperature()) / DERATING_BAND).clamp(0.0, 1.0)
+    }
+
+    /// Derates a current limit in amps. Without a limit the chopper limit is derated
+    pub fn limit(&self, config: &Config, limit: Option<f32>) -> Option<f32> {
+        let derating = self.derating(config);
+        if derating < 1.0 {
+            Some(derating * limit.unwrap_or(config.current_limit as f32))
+        } else {
+            limit
+        }
+    }
+}
+
+impl Default for ThermalModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,91 @@
+use std::convert::TryFrom;
+
+use bmc_protocol::api::*;
+use bmc_protocol::{
+    BootCommand, BootStatus, CanFrame, FrameConversionError, Id, IdFields, IncomingFrame,
+    IntoWithId,
+};
+
+const BOARD: Id = bmc_protocol::device_id(0x2A);
+
+#[test]
+fn commands_round_trip() {
+    let commands = [
+        BootCommand::Stay,
+        BootCommand::BeginUpdate { len: 0x0001_2344 },
+        BootCommand::WriteData {
+            index: 0x1234,
+            word: [0xDE, 0xAD, 0xBE, 0xEF],
+        },
+        BootCommand::FinishUpdate { crc: 0xCBF4_3926 },
+        BootCommand::Boot,
+    ];
+    for &command in commands.iter() {
+        let encoded: CanFrame = command.into_with_id(BOARD);
+        let fields = IdFields::from_raw(raw(encoded.id()));
+        assert_eq!(fields.api_class, CLASS_BOOTLOADER);
+        assert_eq!(fields.device, 0x2A);
+        assert_eq!(BootCommand::try_from(encoded), Ok(command));
+    }
+}
+
+#[test]
+fn statuses_round_trip() {
+    let statuses = [
+        BootStatus::Ready(2),
+        BootStatus::Erased,
+        BootStatus::Ack(0xFFFF),
+        BootStatus::ImageValid,
+        BootStatus::ImageInvalid,
+        BootStatus::Error,
+    ];
+    for &status in statuses.iter() {
+        let encoded: CanFrame = status.into_with_id(BOARD);
+        let fields = IdFields::from_raw(raw(encoded.id()));
+        assert_eq!(fields.api_class, CLASS_BOOTLOADER_STATUS);
+        assert_eq!(fields.device, 0x2A);
+        assert_eq!(BootStatus::try_from(encoded), Ok(status));
+    }
+}
+
+#[test]
+fn enter_bootloader_is_a_stay_request() {
+    let frame: CanFrame = IncomingFrame::EnterBootloader.into_with_id(BOARD);
+    assert_eq!(BootCommand::try_from(frame), Ok(BootCommand::Stay));
+}
+
+#[test]
+fn invalid_frames_are_rejected() {
+    let short = CanFrame::new_data(id(CLASS_BOOTLOADER, WRITE_DATA), &[0, 0, 1, 2, 3]).unwrap();
+    assert_eq!(
+        BootCommand::try_from(short),
+        Err(FrameConversionError::TooShort {
+            minimum: 6,
+            actual: 5
+        })
+    );
+
+    // application frames are not bootloader commands
+    let setpoint: CanFrame = IncomingFrame::Setpoint(100).into_with_id(BOARD);
+    assert_eq!(
+        BootCommand::try_from(setpoint),
+        Err(FrameConversionError::InvalidApi {
+            class: CLASS_CONTROL,
+            index: SETPOINT
+        })
+    );
+
+    let unknown = CanFrame::new_data(id(CLASS_BOOTLOADER_STATUS, BOOT_STATUS), &[9, 0, 0, 0, 0]);
+    assert!(BootStatus::try_from(unknown.unwrap()).is_err());
+}
+
+fn id(api_class: u8, api_index: u8) -> Id {
+    Id::Extended(IdFields::new(api_class, api_index, 0x2A).raw())
+}
+
+fn raw(id: Id) -> u32 {
+    match id {
+        Id::Extended(raw) => raw,
+        Id::Standard(_) => panic!("bootloader frames use extended ids"),
+    }
+}
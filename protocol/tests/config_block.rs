@@ -0,0 +1,147 @@
+use bmc_protocol::config_block::{CONFIG_MAGIC, CONFIG_VERSION};
+use bmc_protocol::{
+    crc32, decode_config, encode_config, Config, ConfigBlockError, IdleMode, PidGains,
+    PositionInnerLoop, MAX_CONFIG_BLOCK_LEN,
+};
+
+/// Encodes `config` into an erased page, like the firmware writes it
+fn page_with(config: &Config) -> Vec<u8> {
+    let mut block = [0xFF; MAX_CONFIG_BLOCK_LEN];
+    let len = encode_config(config, &mut block);
+    let mut page = vec![0xFF; 1024];
+    page[..len].copy_from_slice(&block[..len]);
+    page
+}
+
+/// Builds a block of an older version around `payload`
+fn old_block(version: u16, payload: &[u8]) -> Vec<u8> {
+    let mut block = Vec::new();
+    block.extend_from_slice(&CONFIG_MAGIC.to_le_bytes());
+    block.extend_from_slice(&version.to_le_bytes());
+    block.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    block.extend_from_slice(payload);
+    let crc = crc32(&block);
+    block.extend_from_slice(&crc.to_le_bytes());
+    block
+}
+
+/// Payload of a version 1 block: the fields in order, up to the position inner loop
+fn v1_payload() -> Vec<u8> {
+    let mut payload = vec![12, 1, 1];
+    payload.extend_from_slice(&2048_u16.to_le_bytes());
+    for gain in [0.5_f32, 0.25, 0.0, 2.0, 0.125, 0.0, 4.0, 0.0, 1.0].iter() {
+        payload.extend_from_slice(&gain.to_le_bytes());
+    }
+    payload.extend_from_slice(&0.01_f32.to_le_bytes());
+    payload.extend_from_slice(&0.75_f32.to_le_bytes());
+    payload.push(1);
+    payload
+}
+
+#[test]
+fn crc_matches_the_standard_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(&[]), 0);
+}
+
+#[test]
+fn config_round_trips() {
+    let config = Config {
+        current_limit: 12,
+        inverted: true,
+        idle_mode: IdleMode::Brake,
+        velocity_gains: PidGains::new(0.5, 0.25, 0.125),
+        follow_leader: 3,
+        update_rate: 100,
+        ..Config::default()
+    };
+    let page = page_with(&config);
+    assert_eq!(decode_config(&page), Ok(config));
+
+    let mut block = [0xFF; MAX_CONFIG_BLOCK_LEN];
+    let len = encode_config(&config, &mut block);
+    assert_eq!(len, MAX_CONFIG_BLOCK_LEN);
+    // flash is written in half words
+    assert_eq!(len % 2, 0);
+    assert_eq!(decode_config(&block[..len]), Ok(config));
+}
+
+#[test]
+fn corrupt_blocks_are_rejected() {
+    let page = page_with(&Config::default());
+
+    let mut flipped = page.clone();
+    flipped[20] ^= 0x01;
+    assert_eq!(decode_config(&flipped), Err(ConfigBlockError::BadCrc));
+
+    assert_eq!(
+        decode_config(&[0xFF; 1024]),
+        Err(ConfigBlockError::BadMagic)
+    );
+    assert_eq!(decode_config(&page[..4]), Err(ConfigBlockError::BadMagic));
+
+    let mut newer = page.clone();
+    newer[4..6].copy_from_slice(&(CONFIG_VERSION + 1).to_le_bytes());
+    assert_eq!(
+        decode_config(&newer),
+        Err(ConfigBlockError::UnknownVersion(CONFIG_VERSION + 1))
+    );
+}
+
+#[test]
+fn truncated_blocks_are_rejected() {
+    let page = page_with(&Config::default());
+    let len = u16::from_le_bytes([page[6], page[7]]);
+    let block_len = 8 + len as usize + 4;
+
+    assert!(decode_config(&page[..block_len]).is_ok());
+    assert_eq!(
+        decode_config(&page[..block_len - 1]),
+        Err(ConfigBlockError::BadLength(len))
+    );
+
+    // a length that runs off the page
+    let mut long = page;
+    long[6..8].copy_from_slice(&2000_u16.to_le_bytes());
+    assert_eq!(decode_config(&long), Err(ConfigBlockError::BadLength(2000)));
+}
+
+#[test]
+fn version_1_blocks_are_migrated() {
+    let config = decode_config(&old_block(1, &v1_payload())).unwrap();
+    let defaults = Config::default();
+    assert_eq!(config.current_limit, 12);
+    assert!(config.inverted);
+    assert_eq!(config.idle_mode, IdleMode::Brake);
+    assert_eq!(config.encoder_cpr, 2048);
+    assert_eq!(config.current_gains, PidGains::new(0.5, 0.25, 0.0));
+    assert_eq!(config.velocity_gains, PidGains::new(2.0, 0.125, 0.0));
+    assert_eq!(config.position_gains, PidGains::new(4.0, 0.0, 1.0));
+    assert_eq!(config.position_tolerance, 0.01);
+    assert_eq!(config.position_output_limit, 0.75);
+    assert_eq!(config.position_inner_loop, PositionInnerLoop::Current);
+    // version 1 had no groups, and nothing after that
+    assert_eq!(config.group(), None);
+    assert_eq!(config.update_rate, defaults.update_rate);
+}
+
+#[test]
+fn version_2_blocks_are_migrated() {
+    let mut payload = v1_payload();
+    payload.extend_from_slice(&[5, 1]);
+    let config = decode_config(&old_block(2, &payload)).unwrap();
+    assert_eq!(config.current_limit, 12);
+    assert_eq!(config.group_number, 5);
+    assert_eq!(config.group_slot, 1);
+
+    // a slot past the end leaves the group
+    let mut payload = v1_payload();
+    payload.extend_from_slice(&[5, 200]);
+    let config = decode_config(&old_block(2, &payload)).unwrap();
+    assert_eq!(config.group(), None);
+
+    // a short payload keeps the defaults of the fields it lacks
+    let config = decode_config(&old_block(2, &[7])).unwrap();
+    assert_eq!(config.current_limit, 7);
+    assert_eq!(config.encoder_cpr, Config::default().encoder_cpr);
+}
@@ -0,0 +1,23 @@
+use bmc_protocol::{voltage_duty, MIN_BUS_VOLTAGE};
+
+#[test]
+fn voltage_is_scaled_by_the_bus() {
+    assert_eq!(voltage_duty(6.0, 12.0), i16::MAX / 2);
+    assert_eq!(voltage_duty(-6.0, 12.0), -(i16::MAX / 2));
+    // the same voltage takes more duty from a drained battery
+    assert!(voltage_duty(6.0, 11.0) > voltage_duty(6.0, 12.8));
+}
+
+#[test]
+fn voltage_is_clamped_to_full_scale() {
+    assert_eq!(voltage_duty(24.0, 12.0), i16::MAX);
+    assert_eq!(voltage_duty(-24.0, 12.0), -i16::MAX);
+}
+
+#[test]
+fn no_bus_no_output() {
+    assert_eq!(voltage_duty(6.0, 0.0), 0);
+    assert_eq!(voltage_duty(6.0, MIN_BUS_VOLTAGE - 0.1), 0);
+    assert_eq!(voltage_duty(6.0, f32::NAN), 0);
+    assert_eq!(voltage_duty(f32::NAN, 12.0), 0);
+}
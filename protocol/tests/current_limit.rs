@@ -0,0 +1,80 @@
+use bmc_protocol::{Config, SmartCurrentLimit};
+
+const DT: f32 = 1.0 / 500.0;
+
+fn limited_config() -> Config {
+    Config {
+        smart_current_stall: 20.0,
+        smart_current_free: 40.0,
+        smart_current_rpm: 1000.0,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn off_by_default() {
+    assert_eq!(SmartCurrentLimit::limit(&Config::default(), 0.0), None);
+
+    let mut limit = SmartCurrentLimit::new();
+    for _ in 0..100 {
+        assert_eq!(limit.update(None, 30_000, 50.0, DT), 30_000);
+    }
+}
+
+#[test]
+fn limit_follows_velocity() {
+    let mut config = limited_config();
+    assert_eq!(SmartCurrentLimit::limit(&config, 0.0), Some(20.0));
+    assert_eq!(SmartCurrentLimit::limit(&config, 500.0), Some(30.0));
+    assert_eq!(SmartCurrentLimit::limit(&config, -500.0), Some(30.0));
+    assert_eq!(SmartCurrentLimit::limit(&config, 5000.0), Some(40.0));
+
+    config.smart_current_free = 0.0;
+    assert_eq!(SmartCurrentLimit::limit(&config, 5000.0), Some(20.0));
+    config.smart_current_free = 40.0;
+    config.smart_current_rpm = 0.0;
+    assert_eq!(SmartCurrentLimit::limit(&config, 0.0), Some(40.0));
+}
+
+#[test]
+fn under_the_limit_passes_duty() {
+    let mut limit = SmartCurrentLimit::new();
+    for _ in 0..100 {
+        assert_eq!(limit.update(Some(20.0), -30_000, 15.0, DT), -30_000);
+    }
+}
+
+#[test]
+fn over_the_limit_scales_back_then_recovers() {
+    let mut limit = SmartCurrentLimit::new();
+
+    let mut last = i16::MAX;
+    for _ in 0..5 {
+        let duty = limit.update(Some(20.0), i16::MAX, 30.0, DT);
+        assert!(duty < last, "{} >= {}", duty, last);
+        last = duty;
+    }
+    // negative duty is cut the same way
+    let reverse = limit.update(Some(20.0), -i16::MAX, 30.0, DT);
+    assert!(reverse < 0 && reverse > -last, "{}", reverse);
+
+    for _ in 0..100 {
+        limit.update(Some(20.0), i16::MAX, 30.0, DT);
+    }
+    assert_eq!(limit.update(Some(20.0), i16::MAX, 30.0, DT), 0);
+
+    for _ in 0..100 {
+        limit.update(Some(20.0), i16::MAX, 10.0, DT);
+    }
+    assert_eq!(limit.update(Some(20.0), i16::MAX, 10.0, DT), i16::MAX);
+}
+
+#[test]
+fn reset_drops_the_cut() {
+    let mut limit = SmartCurrentLimit::new();
+    for _ in 0..100 {
+        limit.update(Some(20.0), i16::MAX, 30.0, DT);
+    }
+    limit.reset();
+    assert_eq!(limit.update(Some(20.0), i16::MAX, 20.0, DT), i16::MAX);
+}
@@ -0,0 +1,71 @@
+use bmc_protocol::{Config, Fault, FaultAction, FaultManager, Faults, ParamId, ParamValue};
+
+#[test]
+fn faults_latch_until_cleared() {
+    let mut faults = FaultManager::new();
+    assert!(faults.set_active(Fault::DriverFault, true));
+    // still present, so this is not a new occurrence
+    assert!(!faults.set_active(Fault::DriverFault, true));
+
+    faults.set_active(Fault::DriverFault, false);
+    assert_eq!(faults.active(), Faults::NONE);
+    assert!(faults.sticky().contains(Fault::DriverFault));
+    // coming back before a clear is the same occurrence
+    assert!(!faults.set_active(Fault::DriverFault, true));
+
+    faults.set_active(Fault::DriverFault, false);
+    faults.clear();
+    assert_eq!(faults.sticky(), Faults::NONE);
+    assert!(faults.set_active(Fault::DriverFault, true));
+}
+
+#[test]
+fn clear_keeps_active_faults() {
+    let mut faults = FaultManager::new();
+    faults.set_active(Fault::DriverFault, true);
+    faults.set_active(Fault::Overcurrent, true);
+    faults.set_active(Fault::Overcurrent, false);
+
+    faults.clear();
+    assert_eq!(
+        faults.sticky().iter().collect::<Vec<_>>(),
+        vec![Fault::DriverFault]
+    );
+}
+
+#[test]
+fn strongest_sticky_action_wins() {
+    let mut config = Config::default();
+    config
+        .set(ParamId::DriverFaultAction, ParamValue::U8(1))
+        .unwrap();
+    config
+        .set(ParamId::OvercurrentAction, ParamValue::U8(2))
+        .unwrap();
+    assert_eq!(config.fault_action(Fault::DriverFault), FaultAction::Coast);
+
+    let mut faults = FaultManager::new();
+    assert_eq!(faults.action(&config), FaultAction::Warn);
+
+    faults.set_active(Fault::DriverFault, true);
+    assert_eq!(faults.action(&config), FaultAction::Coast);
+
+    // a fault that came and went still holds the output
+    faults.set_active(Fault::Overcurrent, true);
+    faults.set_active(Fault::Overcurrent, false);
+    assert_eq!(faults.action(&config), FaultAction::Brake);
+
+    faults.set_active(Fault::DriverFault, false);
+    faults.clear();
+    assert_eq!(faults.action(&config), FaultAction::Warn);
+}
+
+#[test]
+fn fault_bits_follow_the_enum() {
+    for (bit, &fault) in Fault::ALL.iter().enumerate() {
+        assert_eq!(fault as usize, bit);
+        let mut set = Faults::NONE;
+        set.insert(fault);
+        assert_eq!(set, Faults(1 << bit));
+    }
+}
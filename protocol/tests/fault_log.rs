@@ -0,0 +1,89 @@
+use bmc_protocol::{FaultEvent, FaultLog, FaultRecord, FAULT_LOG_LEN};
+
+fn recovered() -> FaultLog {
+    let mut log = FaultLog::new();
+    log.recover();
+    log
+}
+
+#[test]
+fn newest_record_comes_first() {
+    let mut log = recovered();
+    assert!(log.is_empty());
+    log.push(FaultEvent::Overcurrent, 10);
+    log.push(FaultEvent::DriverFault, 20);
+
+    assert_eq!(log.len(), 2);
+    assert_eq!(
+        log.get(0),
+        Some(FaultRecord {
+            event: FaultEvent::DriverFault,
+            boot: 1,
+            time_ms: 20,
+        })
+    );
+    assert_eq!(log.get(1).unwrap().event, FaultEvent::Overcurrent);
+    assert_eq!(log.get(2), None);
+}
+
+#[test]
+fn full_log_drops_the_oldest() {
+    let mut log = recovered();
+    for time in 0..FAULT_LOG_LEN as u32 + 3 {
+        log.push(FaultEvent::CanTimeout, time);
+    }
+    assert_eq!(log.len(), FAULT_LOG_LEN);
+    assert_eq!(log.get(0).unwrap().time_ms, FAULT_LOG_LEN as u32 + 2);
+    assert_eq!(log.get(FAULT_LOG_LEN - 1).unwrap().time_ms, 3);
+    assert_eq!(log.get(FAULT_LOG_LEN), None);
+}
+
+#[test]
+fn log_survives_a_reset() {
+    let mut log = recovered();
+    log.push(FaultEvent::Panic, 1234);
+
+    // a reset leaves the memory alone, and the next boot recovers it
+    assert!(log.recover());
+    assert_eq!(log.boot(), 2);
+    assert_eq!(
+        log.get(0),
+        Some(FaultRecord {
+            event: FaultEvent::Panic,
+            boot: 1,
+            time_ms: 1234,
+        })
+    );
+
+    log.push(FaultEvent::HeartbeatLoss, 5);
+    assert_eq!(log.get(0).unwrap().boot, 2);
+}
+
+#[test]
+fn garbage_is_not_trusted() {
+    // what the memory might hold after a power up
+    let mut log: FaultLog =
+        unsafe { std::mem::transmute([0xA5_u8; std::mem::size_of::<FaultLog>()]) };
+    assert!(!log.recover());
+    assert!(log.is_empty());
+    assert_eq!(log.boot(), 1);
+}
+
+#[test]
+fn clear_keeps_the_boot_count() {
+    let mut log = recovered();
+    log.recover();
+    log.push(FaultEvent::DriverFault, 1);
+    log.clear();
+    assert!(log.is_empty());
+
+    log.recover();
+    assert!(log.is_empty());
+    assert_eq!(log.boot(), 3);
+}
+
+#[test]
+fn log_fits_its_memory_region() {
+    // the FAULT_LOG region in firmware/memory.x and bootloader/memory.x
+    assert!(std::mem::size_of::<FaultLog>() <= 128);
+}
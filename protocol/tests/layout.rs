@@ -0,0 +1,304 @@
+use std::collections::HashSet;
+use std::convert::{TryFrom, TryInto};
+
+use bmc_protocol::layout::{Direction, Message, SignalType, MESSAGES};
+use bmc_protocol::param::PARAMS;
+use bmc_protocol::{
+    CanBusState, CanFrame, CanLastError, ErrorCode, FaultEvent, FaultRecord, Faults, Id,
+    IncomingFrame, IntoWithId, OutgoingFrame, OutputState, ParamId, ParamValue, ResetCause,
+};
+
+const BOARD: u8 = 0x2A;
+
+/// Reads a signal out of a payload the way a DBC consumer would
+fn read_signal(data: &[u8], name: &str, message: &Message) -> f64 {
+    let signal = message
+        .signals
+        .iter()
+        .find(|s| s.name == name)
+        .unwrap_or_else(|| panic!("{} has no signal {}", message.name, name));
+    let bytes = &data[signal.offset as usize..(signal.offset + signal.len) as usize];
+    match (signal.ty, signal.len) {
+        (SignalType::Unsigned, 1) => bytes[0] as f64,
+        (SignalType::Unsigned, 2) => u16::from_le_bytes(bytes.try_into().unwrap()) as f64,
+        (SignalType::Unsigned, 3) => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) as f64,
+        (SignalType::Unsigned, 4) => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+        (SignalType::Signed, 1) => bytes[0] as i8 as f64,
+        (SignalType::Signed, 2) => i16::from_le_bytes(bytes.try_into().unwrap()) as f64,
+        (SignalType::Float, 4) => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+        other => panic!("unsupported signal {:?}", other),
+    }
+}
+
+/// Checks the id, frame type and dlc against the layout
+fn check_frame(frame: &CanFrame, message: &Message) {
+    assert_eq!(
+        frame.id(),
+        Id::Extended(message.id(BOARD)),
+        "{}",
+        message.name
+    );
+    assert!(!frame.is_remote_frame(), "{}", message.name);
+    assert_eq!(frame.dlc(), message.dlc, "{}", message.name);
+    for signal in message.signals {
+        assert!(
+            signal.offset + signal.len <= message.dlc,
+            "{}.{} is outside the frame",
+            message.name,
+            signal.name
+        );
+    }
+}
+
+fn incoming(frame: IncomingFrame, signals: &[(&str, f64)]) {
+    let message = frame.layout();
+    assert_eq!(message.direction, Direction::ToBoard);
+    let encoded: CanFrame = frame.into_with_id(bmc_protocol::device_id(BOARD));
+    check_frame(&encoded, message);
+    assert_eq!(signals.len(), message.signals.len(), "{}", message.name);
+    for &(name, value) in signals {
+        assert_eq!(read_signal(encoded.data(), name, message), value);
+    }
+}
+
+fn outgoing(frame: OutgoingFrame, signals: &[(&str, f64)]) {
+    let message = frame.layout();
+    assert_eq!(message.direction, Direction::FromBoard);
+    let encoded: CanFrame = frame.into_with_id(bmc_protocol::device_id(BOARD));
+    check_frame(&encoded, message);
+    assert_eq!(signals.len(), message.signals.len(), "{}", message.name);
+    for &(name, value) in signals {
+        assert_eq!(read_signal(encoded.data(), name, message), value);
+    }
+}
+
+#[test]
+fn incoming_frames_match_layout() {
+    incoming(IncomingFrame::HeartBeat, &[]);
+    incoming(IncomingFrame::Stop, &[]);
+    incoming(IncomingFrame::Setpoint(-1234), &[("Setpoint", -1234.0)]);
+    incoming(
+        IncomingFrame::SetCurrentSetpoint(-2.5),
+        &[("CurrentSetpoint", -2.5)],
+    );
+    incoming(
+        IncomingFrame::SetVelocitySetpoint(1500.0),
+        &[("VelocitySetpoint", 1500.0)],
+    );
+    incoming(
+        IncomingFrame::SetPositionSetpoint(-3.5),
+        &[("PositionSetpoint", -3.5)],
+    );
+    incoming(
+        IncomingFrame::SetVoltageSetpoint(11.5),
+        &[("VoltageSetpoint", 11.5)],
+    );
+    incoming(
+        IncomingFrame::SetParam(ParamId::EncoderCpr, ParamValue::U16(4096)),
+        &[("ParamId", 3.0), ("ParamValue", 4096.0)],
+    );
+    incoming(
+        IncomingFrame::GetParam(ParamId::PositionKi),
+        &[("ParamId", 0x17 as f64)],
+    );
+    incoming(IncomingFrame::SaveConfig, &[]);
+    incoming(IncomingFrame::RestoreDefaults, &[]);
+    incoming(IncomingFrame::EnterBootloader, &[]);
+    incoming(IncomingFrame::QueryVersion, &[]);
+    incoming(IncomingFrame::ClearFaults, &[]);
+    incoming(IncomingFrame::GetFaultLog(4), &[("FaultLogIndex", 4.0)]);
+    incoming(IncomingFrame::ClearFaultLog, &[]);
+    incoming(
+        IncomingFrame::GroupSetpoint([-1, 2, -3, 4]),
+        &[
+            ("Slot0Setpoint", -1.0),
+            ("Slot1Setpoint", 2.0),
+            ("Slot2Setpoint", -3.0),
+            ("Slot3Setpoint", 4.0),
+        ],
+    );
+}
+
+#[test]
+fn outgoing_frames_match_layout() {
+    outgoing(
+        OutgoingFrame::Error(ErrorCode::CanError),
+        &[("ErrorCode", 2.0)],
+    );
+    outgoing(
+        OutgoingFrame::Update {
+            current_now: 1.5,
+            duty_now: -300,
+        },
+        &[("DutyNow", -300.0), ("CurrentNow", 1.5)],
+    );
+    outgoing(
+        OutgoingFrame::Overcurrent {
+            current_now: 11.0,
+            current_limit: 10.0,
+        },
+        &[("OvercurrentNow", 11.0), ("OvercurrentLimit", 10.0)],
+    );
+    outgoing(
+        OutgoingFrame::Encoder {
+            position: 2.25,
+            velocity: -60.0,
+        },
+        &[("Position", 2.25), ("Velocity", -60.0)],
+    );
+    outgoing(
+        OutgoingFrame::Version {
+            protocol: 4,
+            firmware: [0, 1, 7],
+        },
+        &[
+            ("ProtocolVersion", 4.0),
+            ("FirmwareMajor", 0.0),
+            ("FirmwareMinor", 1.0),
+            ("FirmwarePatch", 7.0),
+        ],
+    );
+    outgoing(
+        OutgoingFrame::Faults {
+            active: Faults(0b10),
+            sticky: Faults(0b11),
+        },
+        &[("ActiveFaults", 2.0), ("StickyFaults", 3.0)],
+    );
+    outgoing(
+        OutgoingFrame::FaultLogEntry {
+            index: 1,
+            record: Some(FaultRecord {
+                event: FaultEvent::CanTimeout,
+                boot: 7,
+                time_ms: 123_456,
+            }),
+        },
+        &[
+            ("FaultLogIndex", 1.0),
+            ("FaultEvent", 2.0),
+            ("FaultBoot", 7.0),
+            ("FaultTime", 123_456.0),
+        ],
+    );
+    outgoing(
+        OutgoingFrame::ParamValue(ParamId::Inverted, ParamValue::Bool(true)),
+        &[("ParamId", 1.0), ("ParamValue", 1.0)],
+    );
+    outgoing(
+        OutgoingFrame::MotorTemperature {
+            temperature: 87.5,
+            derating: 0.25,
+        },
+        &[("MotorTemperature", 87.5), ("CurrentDerating", 0.25)],
+    );
+    outgoing(OutgoingFrame::BusVoltage(12.5), &[("BusVoltage", 12.5)]);
+    outgoing(
+        OutgoingFrame::SystemStatus {
+            active: Faults(0b100),
+            sticky: Faults(0b101),
+            mcu_temperature: -5,
+            uptime: 86_400,
+        },
+        &[
+            ("StatusActiveFaults", 4.0),
+            ("StatusStickyFaults", 5.0),
+            ("McuTemperature", -5.0),
+            ("Uptime", 86_400.0),
+        ],
+    );
+    outgoing(
+        OutgoingFrame::CanStatus {
+            tx_errors: 9,
+            rx_errors: 1,
+            bus_off_count: 2,
+            tx_queue_depth: 14,
+            state: CanBusState::ErrorPassive,
+            last_error: CanLastError::Acknowledge,
+        },
+        &[
+            ("TxErrors", 9.0),
+            ("RxErrors", 1.0),
+            ("BusOffCount", 2.0),
+            ("TxQueueDepth", 14.0),
+            ("CanBusState", 2.0),
+            ("CanLastError", 3.0),
+        ],
+    );
+    outgoing(
+        OutgoingFrame::Output {
+            duty: -300,
+            state: OutputState::Brake,
+        },
+        &[("AppliedDuty", -300.0), ("OutputState", 2.0)],
+    );
+    outgoing(
+        OutgoingFrame::Boot {
+            reset_cause: ResetCause::Watchdog,
+            boot: 300,
+        },
+        &[("ResetCause", 4.0), ("BootCount", 300.0)],
+    );
+}
+
+#[test]
+fn messages_are_unique() {
+    let mut names = HashSet::new();
+    let mut ids = HashSet::new();
+    for message in MESSAGES {
+        assert!(
+            names.insert(message.name),
+            "{} is listed twice",
+            message.name
+        );
+        assert!(
+            ids.insert(message.id(BOARD)),
+            "{} reuses an id",
+            message.name
+        );
+    }
+}
+
+#[test]
+fn enumerated_values_match() {
+    for message in MESSAGES {
+        for signal in message.signals {
+            for &(raw, name) in signal.values {
+                let decoded = match signal.name {
+                    "CanBusState" => format!("{:?}", CanBusState::try_from(raw as u8).unwrap()),
+                    "CanLastError" => {
+                        format!("{:?}", CanLastError::try_from(raw as u8).unwrap())
+                    }
+                    "ErrorCode" => format!("{:?}", ErrorCode::try_from(raw as u8).unwrap()),
+                    "FaultEvent" if raw == 0xFF => {
+                        let frame: CanFrame = OutgoingFrame::FaultLogEntry {
+                            index: 0,
+                            record: None,
+                        }
+                        .into_with_id(bmc_protocol::device_id(BOARD));
+                        assert_eq!(frame.data()[1] as u32, raw);
+                        "NoRecord".to_string()
+                    }
+                    "FaultEvent" => format!("{:?}", FaultEvent::try_from(raw as u8).unwrap()),
+                    "OutputState" => format!("{:?}", OutputState::try_from(raw as u8).unwrap()),
+                    "ResetCause" => format!("{:?}", ResetCause::try_from(raw as u8).unwrap()),
+                    "ParamId" => ParamId::try_from(raw as u16)
+                        .unwrap()
+                        .info()
+                        .name
+                        .to_string(),
+                    other => panic!("no check for the values of {}", other),
+                };
+                assert_eq!(decoded, name);
+            }
+            if signal.name == "ParamId" {
+                assert_eq!(
+                    signal.values.len(),
+                    PARAMS.len(),
+                    "{} does not name every parameter",
+                    message.name
+                );
+            }
+        }
+    }
+}
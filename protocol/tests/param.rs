@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+use bmc_protocol::param::{ParamError, ParamType, PARAMS};
+use bmc_protocol::{Config, GroupSlot, ParamId, ParamValue, BROADCAST, GROUP_SLOTS};
+
+#[test]
+fn table_is_consistent() {
+    let mut names = HashSet::new();
+    let mut last = None;
+    for param in PARAMS {
+        assert!(names.insert(param.name), "{} is listed twice", param.name);
+        assert!(
+            last < Some(param.id as u16),
+            "{} is out of id order",
+            param.name
+        );
+        last = Some(param.id as u16);
+
+        assert_eq!(ParamId::try_from(param.id as u16), Ok(param.id));
+        assert_eq!(param.id.info(), param);
+        assert_eq!(param.default.ty(), param.ty, "{}", param.name);
+        assert!(param.min <= param.max, "{}", param.name);
+        assert_eq!(param.check(param.default), Ok(()), "{}", param.name);
+        for &(raw, _) in param.values {
+            let value = ParamValue::from_raw(param.ty, raw).unwrap();
+            assert_eq!(param.check(value), Ok(()), "{}", param.name);
+        }
+    }
+    assert_eq!(ParamId::try_from(0xFFFF), Err(0xFFFF));
+}
+
+#[test]
+fn config_defaults_come_from_the_table() {
+    let config = Config::default();
+    for param in PARAMS {
+        assert_eq!(config.get(param.id), param.default, "{}", param.name);
+    }
+    assert_eq!(config.group(), None);
+    assert_eq!(config.leader(), None);
+    assert_eq!(config.leader_timeout, 200);
+    // heartbeats are opt in
+    assert_eq!(config.heartbeat_timeout(), None);
+}
+
+#[test]
+fn config_set_then_get() {
+    let mut config = Config::default();
+    for param in PARAMS {
+        let value = ParamValue::from_raw(param.ty, param.max as u32)
+            .filter(|_| param.ty != ParamType::F32)
+            .unwrap_or(ParamValue::F32(param.max));
+        assert_eq!(config.set(param.id, value), Ok(()), "{}", param.name);
+        assert_eq!(config.get(param.id), value, "{}", param.name);
+    }
+}
+
+#[test]
+fn rejected_values_leave_config_unchanged() {
+    let mut config = Config::default();
+    let before = config;
+
+    let rejected = [
+        (
+            ParamId::CurrentLimit,
+            ParamValue::U8(200),
+            ParamError::OutOfRange,
+        ),
+        (
+            ParamId::CurrentLimit,
+            ParamValue::F32(5.0),
+            ParamError::WrongType,
+        ),
+        (
+            ParamId::EncoderCpr,
+            ParamValue::U16(0),
+            ParamError::OutOfRange,
+        ),
+        (ParamId::IdleMode, ParamValue::U8(2), ParamError::OutOfRange),
+        (
+            ParamId::VelocityKp,
+            ParamValue::F32(-1.0),
+            ParamError::OutOfRange,
+        ),
+        (
+            ParamId::VelocityKp,
+            ParamValue::F32(f32::NAN),
+            ParamError::OutOfRange,
+        ),
+        (
+            ParamId::PositionTolerance,
+            ParamValue::F32(f32::INFINITY),
+            ParamError::OutOfRange,
+        ),
+        (
+            ParamId::GroupSlot,
+            ParamValue::U8(5),
+            ParamError::OutOfRange,
+        ),
+        (ParamId::Inverted, ParamValue::U8(1), ParamError::WrongType),
+    ];
+    for &(id, value, error) in rejected.iter() {
+        assert_eq!(config.set(id, value), Err(error), "{:?} = {:?}", id, value);
+    }
+    assert_eq!(config, before);
+}
+
+#[test]
+fn group_membership_follows_params() {
+    let mut config = Config::default();
+    config.set(ParamId::Group, ParamValue::U8(7)).unwrap();
+    assert_eq!(config.group(), None);
+    config.set(ParamId::GroupSlot, ParamValue::U8(2)).unwrap();
+    assert_eq!(config.group(), Some(GroupSlot { group: 7, slot: 2 }));
+    config
+        .set(ParamId::GroupSlot, ParamValue::U8(GROUP_SLOTS as u8))
+        .unwrap();
+    assert_eq!(config.group(), None);
+}
+
+#[test]
+fn leader_follows_params() {
+    let mut config = Config::default();
+    config
+        .set(ParamId::FollowLeader, ParamValue::U8(3))
+        .unwrap();
+    assert_eq!(config.leader(), Some(3));
+    config
+        .set(ParamId::FollowLeader, ParamValue::U8(BROADCAST))
+        .unwrap();
+    assert_eq!(config.leader(), None);
+}
+
+#[test]
+fn zero_heartbeat_timeout_disables_it() {
+    let mut config = Config::default();
+    config
+        .set(ParamId::HeartbeatTimeout, ParamValue::U16(100))
+        .unwrap();
+    assert_eq!(config.heartbeat_timeout(), Some(100));
+    config
+        .set(ParamId::HeartbeatTimeout, ParamValue::U16(0))
+        .unwrap();
+    assert_eq!(config.heartbeat_timeout(), None);
+    assert!(config
+        .set(ParamId::HeartbeatTimeout, ParamValue::U16(60_000))
+        .is_err());
+}
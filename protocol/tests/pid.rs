@@ -0,0 +1,68 @@
+use bmc_protocol::{Pid, PidGains};
+
+const DT: f32 = 1.0 / 500.0;
+
+/// Runs `n` updates with the same error, returns the last output
+fn run(pid: &mut Pid, gains: &PidGains, error: f32, n: usize) -> f32 {
+    let mut output = 0.0;
+    for _ in 0..n {
+        output = pid.update(gains, error, DT);
+    }
+    output
+}
+
+#[test]
+fn output_is_clamped() {
+    let gains = PidGains::new(10.0, 0.0, 0.0);
+    let mut pid = Pid::new(-1.0, 0.5);
+    assert_eq!(pid.update(&gains, 1.0, DT), 0.5);
+    assert_eq!(pid.update(&gains, -1.0, DT), -1.0);
+    assert_eq!(pid.update(&gains, 0.03125, DT), 0.3125);
+}
+
+#[test]
+fn saturation_does_not_wind_up() {
+    let gains = PidGains::new(0.5, 1.0, 0.0);
+    let mut pid = Pid::new(-1.0, 1.0);
+    // twenty seconds against the limit, the integrator only grows until the output saturates
+    assert_eq!(run(&mut pid, &gains, 1.0, 10_000), 1.0);
+
+    // so the output leaves saturation as soon as the error changes sign
+    let output = pid.update(&gains, -0.1, DT);
+    assert!(output < 0.5, "{}", output);
+
+    // and the integrator unwinds at the same rate it would have wound up
+    let mut n = 1;
+    while pid.update(&gains, -0.1, DT) > 0.0 {
+        n += 1;
+        assert!(n < 2500, "output stayed positive");
+    }
+    assert!(n > 2000, "{}", n);
+}
+
+#[test]
+fn narrower_limits_pull_the_integrator_in() {
+    let gains = PidGains::new(0.0, 1.0, 0.0);
+    let mut pid = Pid::new(-1.0, 1.0);
+    assert_eq!(run(&mut pid, &gains, 1.0, 1000), 1.0);
+
+    pid.set_output_limits(-0.2, 0.2);
+    assert_eq!(pid.update(&gains, 0.0, DT), 0.2);
+
+    // widening them again does not bring the old integrator back
+    pid.set_output_limits(-1.0, 1.0);
+    assert_eq!(pid.update(&gains, 0.0, DT), 0.2);
+}
+
+#[test]
+fn reset_clears_the_history() {
+    let gains = PidGains::new(0.0, 1.0, 1.0);
+    let mut pid = Pid::new(-1.0, 1.0);
+    run(&mut pid, &gains, 0.5, 1000);
+
+    pid.reset();
+    // no integrator left, and no derivative kick from the error before the reset
+    assert_eq!(pid.update(&gains, 0.0, DT), 0.0);
+    let mut pid = Pid::new(-1.0, 1.0);
+    assert_eq!(pid.update(&PidGains::new(0.0, 0.0, 1.0), 0.5, DT), 0.0);
+}
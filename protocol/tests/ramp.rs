@@ -0,0 +1,78 @@
+use bmc_protocol::Ramp;
+
+const DT: f32 = 1.0 / 500.0;
+
+/// Runs updates until the output reaches the setpoint, returns how many it took
+fn updates_to_reach(ramp: &mut Ramp, setpoint: i16, up: f32, down: f32) -> usize {
+    for n in 1..=10_000 {
+        if ramp.update(setpoint, up, down, DT) == setpoint {
+            return n;
+        }
+    }
+    panic!("never reached {}", setpoint);
+}
+
+#[test]
+fn zero_rates_do_not_ramp() {
+    let mut ramp = Ramp::new();
+    for &setpoint in [i16::MAX, -i16::MAX, 1000, 0, i16::MIN].iter() {
+        assert_eq!(ramp.update(setpoint, 0.0, 0.0, DT), setpoint);
+    }
+}
+
+#[test]
+fn speeding_up_follows_the_up_rate() {
+    let mut ramp = Ramp::new();
+    // one full scale per second takes 500 updates
+    let n = updates_to_reach(&mut ramp, i16::MAX, 1.0, 0.0);
+    assert!((499..=501).contains(&n), "{}", n);
+
+    let mut ramp = Ramp::new();
+    let n = updates_to_reach(&mut ramp, -i16::MAX / 2, 4.0, 0.0);
+    assert!((62..=63).contains(&n), "{}", n);
+}
+
+#[test]
+fn slowing_down_follows_the_down_rate() {
+    let mut ramp = Ramp::new();
+    ramp.reset(i16::MAX);
+    // without a down rate, stopping is immediate even though speeding up is limited
+    assert_eq!(ramp.update(0, 1.0, 0.0, DT), 0);
+
+    ramp.reset(i16::MAX);
+    let n = updates_to_reach(&mut ramp, 0, 0.0, 2.0);
+    assert!((249..=251).contains(&n), "{}", n);
+
+    ramp.reset(-i16::MAX);
+    let n = updates_to_reach(&mut ramp, -i16::MAX / 2, 0.0, 2.0);
+    assert!((124..=126).contains(&n), "{}", n);
+}
+
+#[test]
+fn reversal_slows_down_then_speeds_up() {
+    let mut ramp = Ramp::new();
+    ramp.reset(i16::MAX);
+
+    let mut last = i16::MAX;
+    let mut through_zero = None;
+    for n in 1..=1000 {
+        let output = ramp.update(-i16::MAX, 1.0, 2.0, DT);
+        assert!(output <= last, "output went up at {}", n);
+        if last > 0 && output <= 0 {
+            through_zero = Some(n);
+        }
+        last = output;
+    }
+    assert_eq!(last, -i16::MAX);
+    // half a second at the down rate, then a second at the up rate
+    let n = through_zero.unwrap();
+    assert!((249..=251).contains(&n), "{}", n);
+}
+
+#[test]
+fn reset_jumps_to_the_output() {
+    let mut ramp = Ramp::new();
+    ramp.reset(10_000);
+    assert_eq!(ramp.update(10_000, 1.0, 1.0, DT), 10_000);
+    assert!(ramp.update(20_000, 1.0, 1.0, DT) < 10_100);
+}
@@ -0,0 +1,39 @@
+use bmc_protocol::ResetCause;
+
+const PIN: u32 = 1 << 26;
+const POWER_ON: u32 = 1 << 27;
+const SOFTWARE: u32 = 1 << 28;
+const WATCHDOG: u32 = 1 << 29;
+
+#[test]
+fn the_pin_flag_comes_with_every_reset() {
+    assert_eq!(ResetCause::from_flags(PIN, false), ResetCause::Pin);
+    assert_eq!(
+        ResetCause::from_flags(PIN | SOFTWARE, false),
+        ResetCause::Software
+    );
+    assert_eq!(
+        ResetCause::from_flags(PIN | WATCHDOG, true),
+        ResetCause::Watchdog
+    );
+    assert_eq!(
+        ResetCause::from_flags(PIN | POWER_ON, false),
+        ResetCause::PowerOn
+    );
+}
+
+#[test]
+fn a_power_on_that_kept_the_ram_is_a_brown_out() {
+    assert_eq!(
+        ResetCause::from_flags(PIN | POWER_ON, true),
+        ResetCause::BrownOut
+    );
+    // other resets keep the ram anyway
+    assert_eq!(ResetCause::from_flags(PIN, true), ResetCause::Pin);
+}
+
+#[test]
+fn no_flags_is_unknown() {
+    // the other bits of the register are not reset flags
+    assert_eq!(ResetCause::from_flags(0x0C, false), ResetCause::Unknown);
+}
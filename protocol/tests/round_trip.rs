@@ -0,0 +1,345 @@
+use std::convert::TryFrom;
+
+use bmc_protocol::api::*;
+use bmc_protocol::{
+    CanBusState, CanFrame, CanLastError, ErrorCode, FaultEvent, FaultRecord, Faults,
+    FrameConversionError, GroupSlot, Id, IdFields, IncomingFrame, IntoWithId, OutgoingFrame,
+    OutputState, ParamId, ParamValue, ResetCause,
+};
+
+const BOARD: Id = bmc_protocol::device_id(0x2A);
+
+/// Id of an api on our board
+fn id(api_class: u8, api_index: u8) -> Id {
+    Id::Extended(IdFields::new(api_class, api_index, 0x2A).raw())
+}
+
+fn incoming_round_trip(frame: IncomingFrame) {
+    let encoded: CanFrame = frame.into_with_id(BOARD);
+    assert_eq!(bmc_protocol::board_id(encoded.id()), 0x2A);
+    assert_eq!(IncomingFrame::try_from(encoded), Ok(frame));
+}
+
+fn outgoing_round_trip(frame: OutgoingFrame) {
+    let encoded: CanFrame = frame.into_with_id(BOARD);
+    assert_eq!(bmc_protocol::board_id(encoded.id()), 0x2A);
+    assert_eq!(OutgoingFrame::try_from(encoded), Ok(frame));
+}
+
+#[test]
+fn incoming_frames_round_trip() {
+    let frames = [
+        IncomingFrame::Setpoint(-1234),
+        IncomingFrame::HeartBeat,
+        IncomingFrame::Stop,
+        IncomingFrame::SetCurrentSetpoint(-2.5),
+        IncomingFrame::SetVelocitySetpoint(3000.0),
+        IncomingFrame::SetPositionSetpoint(-12.75),
+        IncomingFrame::SetVoltageSetpoint(-10.5),
+        IncomingFrame::SaveConfig,
+        IncomingFrame::RestoreDefaults,
+        IncomingFrame::EnterBootloader,
+        IncomingFrame::QueryVersion,
+        IncomingFrame::GroupSetpoint([1, -2, i16::MAX, i16::MIN]),
+        IncomingFrame::SetParam(ParamId::Inverted, ParamValue::Bool(true)),
+        IncomingFrame::SetParam(ParamId::CurrentLimit, ParamValue::U8(15)),
+        IncomingFrame::SetParam(ParamId::EncoderCpr, ParamValue::U16(4096)),
+        IncomingFrame::SetParam(ParamId::VelocityKd, ParamValue::F32(-0.125)),
+        IncomingFrame::GetParam(ParamId::GroupSlot),
+        IncomingFrame::ClearFaults,
+        IncomingFrame::GetFaultLog(3),
+        IncomingFrame::ClearFaultLog,
+    ];
+    for &frame in frames.iter() {
+        incoming_round_trip(frame);
+    }
+}
+
+#[test]
+fn every_param_round_trips() {
+    for param in bmc_protocol::param::PARAMS {
+        incoming_round_trip(IncomingFrame::SetParam(param.id, param.default));
+        incoming_round_trip(IncomingFrame::GetParam(param.id));
+        outgoing_round_trip(OutgoingFrame::ParamValue(param.id, param.default));
+    }
+}
+
+#[test]
+fn outgoing_frames_round_trip() {
+    let frames = [
+        OutgoingFrame::Update {
+            current_now: 1.5,
+            duty_now: -20000,
+        },
+        OutgoingFrame::Overcurrent {
+            current_now: 12.0,
+            current_limit: 10.0,
+        },
+        OutgoingFrame::Error(ErrorCode::None),
+        OutgoingFrame::Error(ErrorCode::MotorDriverFault),
+        OutgoingFrame::Error(ErrorCode::CanError),
+        OutgoingFrame::Error(ErrorCode::Other),
+        OutgoingFrame::Encoder {
+            position: -3.25,
+            velocity: 120.0,
+        },
+        OutgoingFrame::Version {
+            protocol: bmc_protocol::PROTOCOL_VERSION,
+            firmware: [1, 2, 3],
+        },
+        OutgoingFrame::Faults {
+            active: Faults(0x0001),
+            sticky: Faults(0x8003),
+        },
+        OutgoingFrame::FaultLogEntry {
+            index: 2,
+            record: Some(FaultRecord {
+                event: FaultEvent::Panic,
+                boot: 513,
+                time_ms: 90_061,
+            }),
+        },
+        OutgoingFrame::FaultLogEntry {
+            index: 15,
+            record: None,
+        },
+        OutgoingFrame::MotorTemperature {
+            temperature: 87.5,
+            derating: 0.25,
+        },
+        OutgoingFrame::BusVoltage(12.625),
+        OutgoingFrame::SystemStatus {
+            active: Faults(0x0004),
+            sticky: Faults(0x0005),
+            mcu_temperature: -12,
+            uptime: 0x12_3456,
+        },
+        OutgoingFrame::CanStatus {
+            tx_errors: 8,
+            rx_errors: 128,
+            bus_off_count: 513,
+            tx_queue_depth: 3,
+            state: CanBusState::ErrorActive,
+            last_error: CanLastError::None,
+        },
+        OutgoingFrame::CanStatus {
+            tx_errors: 255,
+            rx_errors: 0,
+            bus_off_count: 1,
+            tx_queue_depth: 16,
+            state: CanBusState::BusOff,
+            last_error: CanLastError::BitDominant,
+        },
+        OutgoingFrame::Output {
+            duty: -16_000,
+            state: OutputState::Drive,
+        },
+        OutgoingFrame::Output {
+            duty: 0,
+            state: OutputState::Sleep,
+        },
+        OutgoingFrame::Boot {
+            reset_cause: ResetCause::BrownOut,
+            boot: 0xBEEF,
+        },
+    ];
+    for &frame in frames.iter() {
+        outgoing_round_trip(frame);
+    }
+}
+
+#[test]
+fn short_frames_are_rejected() {
+    let frame = CanFrame::new_data(id(CLASS_CONTROL, SETPOINT), &[0x01]).unwrap();
+    assert_eq!(
+        IncomingFrame::try_from(frame),
+        Err(FrameConversionError::TooShort {
+            minimum: 2,
+            actual: 1
+        })
+    );
+
+    let frame = CanFrame::new_data(id(CLASS_CONFIG, SET_PARAM), &[0x00, 0x01]).unwrap();
+    assert_eq!(
+        IncomingFrame::try_from(frame),
+        Err(FrameConversionError::TooShort {
+            minimum: 6,
+            actual: 2
+        })
+    );
+}
+
+#[test]
+fn remote_frames_are_rejected() {
+    let remote = CanFrame::new_remote(id(CLASS_CONTROL, SETPOINT), 2).unwrap();
+    assert_eq!(
+        IncomingFrame::try_from(remote),
+        Err(FrameConversionError::FrameType)
+    );
+
+    let remote = CanFrame::new_remote(id(CLASS_STATUS, UPDATE), 6).unwrap();
+    assert_eq!(
+        OutgoingFrame::try_from(remote),
+        Err(FrameConversionError::FrameType)
+    );
+}
+
+#[test]
+fn invalid_payloads_are_rejected() {
+    let frame = CanFrame::new_data(id(CLASS_SYSTEM, 0xF), &[]).unwrap();
+    assert_eq!(
+        IncomingFrame::try_from(frame),
+        Err(FrameConversionError::InvalidApi {
+            class: CLASS_SYSTEM,
+            index: 0xF
+        })
+    );
+
+    // frames from a board are not commands
+    let frame = CanFrame::new_data(id(CLASS_STATUS, UPDATE), &[0; 6]).unwrap();
+    assert_eq!(
+        IncomingFrame::try_from(frame),
+        Err(FrameConversionError::InvalidApi {
+            class: CLASS_STATUS,
+            index: UPDATE
+        })
+    );
+
+    let frame = CanFrame::new_data(id(CLASS_CONFIG, GET_PARAM), &[0xFF, 0x7F]).unwrap();
+    assert_eq!(
+        IncomingFrame::try_from(frame),
+        Err(FrameConversionError::InvalidParam { id: 0x7FFF })
+    );
+
+    // values must fit the type of their parameter, the range is up to the board
+    let mut payload = (ParamId::IdleMode as u16).to_le_bytes().to_vec();
+    payload.extend_from_slice(&0x100_u32.to_le_bytes());
+    let frame = CanFrame::new_data(id(CLASS_CONFIG, SET_PARAM), &payload).unwrap();
+    assert_eq!(
+        IncomingFrame::try_from(frame),
+        Err(FrameConversionError::InvalidFrame(
+            "Parameter value does not fit its type"
+        ))
+    );
+
+    let frame = CanFrame::new_data(id(CLASS_STATUS, OUTPUT), &[0, 0, 4]).unwrap();
+    assert_eq!(
+        OutgoingFrame::try_from(frame),
+        Err(FrameConversionError::InvalidFrame("Unknown output state"))
+    );
+
+    let frame = CanFrame::new_data(id(CLASS_STATUS, CAN_STATUS), &[0, 0, 0, 0, 0, 4, 0]).unwrap();
+    assert_eq!(
+        OutgoingFrame::try_from(frame),
+        Err(FrameConversionError::InvalidFrame("Unknown can bus state"))
+    );
+
+    let frame = CanFrame::new_data(id(CLASS_STATUS, BOOT), &[8, 1, 0]).unwrap();
+    assert_eq!(
+        OutgoingFrame::try_from(frame),
+        Err(FrameConversionError::InvalidFrame("Unknown reset cause"))
+    );
+}
+
+#[test]
+fn uptime_saturates() {
+    let frame: CanFrame = OutgoingFrame::SystemStatus {
+        active: Faults::NONE,
+        sticky: Faults::NONE,
+        mcu_temperature: 30,
+        uptime: u32::MAX,
+    }
+    .into_with_id(BOARD);
+    match OutgoingFrame::try_from(frame) {
+        Ok(OutgoingFrame::SystemStatus { uptime, .. }) => assert_eq!(uptime, 0xFF_FFFF),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn foreign_ids_are_rejected() {
+    // a version 2 heartbeat
+    let standard = CanFrame::new_remote(Id::Standard(0x02A), 0).unwrap();
+    // the same api from another manufacturer
+    let foreign = IdFields {
+        manufacturer: 5,
+        ..IdFields::new(CLASS_SYSTEM, HEARTBEAT, 0x2A)
+    };
+    let foreign = CanFrame::new_data(Id::Extended(foreign.raw()), &[]).unwrap();
+    for &frame in [standard, foreign].iter() {
+        assert_eq!(
+            IncomingFrame::try_from(frame),
+            Err(FrameConversionError::InvalidIdFormat)
+        );
+        assert_eq!(
+            OutgoingFrame::try_from(frame),
+            Err(FrameConversionError::InvalidIdFormat)
+        );
+    }
+}
+
+#[test]
+fn id_fields_round_trip() {
+    let fields = IdFields::new(CLASS_FAULT, OVERCURRENT, 0xA5);
+    assert_eq!(fields.raw(), 0x0208_91A5);
+    assert_eq!(IdFields::from_raw(fields.raw()), fields);
+    assert!(fields.raw() <= 0x1FFF_FFFF);
+    assert_eq!(fields.raw() & bmc_protocol::DEVICE_MASK, 0x0208_00A5);
+
+    let encoded: CanFrame = IncomingFrame::Stop.into_with_id(BOARD);
+    assert_eq!(
+        encoded.id(),
+        Id::Extended(IdFields::new(CLASS_CONTROL, STOP, 0x2A).raw())
+    );
+
+    // the bootloader matches this id without depending on this crate
+    let encoded: CanFrame = IncomingFrame::EnterBootloader.into_with_id(BOARD);
+    assert_eq!(encoded.id(), Id::Extended(0x0208_222A));
+}
+
+#[test]
+fn oversized_frames_cannot_be_built() {
+    assert!(CanFrame::new_data(BOARD, &[0; 9]).is_none());
+    assert!(CanFrame::new_remote(BOARD, 9).is_none());
+}
+
+#[test]
+fn values_are_little_endian() {
+    let frame: CanFrame = IncomingFrame::Setpoint(0x0102).into_with_id(BOARD);
+    assert_eq!(frame.data(), &[0x02, 0x01]);
+
+    let frame: CanFrame =
+        IncomingFrame::SetParam(ParamId::EncoderCpr, ParamValue::U16(0x0800)).into_with_id(BOARD);
+    assert_eq!(frame.data(), &[0x03, 0x00, 0x00, 0x08, 0x00, 0x00]);
+
+    // 1.0 is 0x3F800000
+    let frame: CanFrame = IncomingFrame::SetCurrentSetpoint(1.0).into_with_id(BOARD);
+    assert_eq!(frame.data(), &[0x00, 0x00, 0x80, 0x3F]);
+
+    let frame: CanFrame = OutgoingFrame::Update {
+        current_now: -2.0,
+        duty_now: -2,
+    }
+    .into_with_id(BOARD);
+    assert_eq!(frame.data(), &[0xFE, 0xFF, 0x00, 0x00, 0x00, 0xC0]);
+}
+
+#[test]
+fn group_members_pick_their_slot() {
+    let setpoints = [10, 20, 30, 40];
+    let member = GroupSlot { group: 7, slot: 2 };
+    assert_eq!(member.pick(7, &setpoints), Some(30));
+    assert_eq!(member.pick(8, &setpoints), None);
+
+    // group frames are addressed by group number, not board id
+    let frame: CanFrame =
+        IncomingFrame::GroupSetpoint(setpoints).into_with_id(bmc_protocol::device_id(7));
+    assert_eq!(bmc_protocol::board_id(frame.id()), 7);
+    match frame.id() {
+        Id::Extended(raw) => assert_eq!(
+            raw & bmc_protocol::API_MASK,
+            IdFields::new(CLASS_CONTROL, GROUP_SETPOINT, 0).raw()
+        ),
+        Id::Standard(_) => panic!("group frames use extended ids"),
+    }
+}
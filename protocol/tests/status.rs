@@ -0,0 +1,82 @@
+use bmc_protocol::{Config, StatusFrame, StatusSchedule};
+
+/// Runs a schedule for `ms` one millisecond ticks and counts how often `frame` was due
+fn count_due(config: &Config, frame: StatusFrame, ms: u32) -> u32 {
+    let mut schedule = StatusSchedule::new();
+    let mut sent = 0;
+    for _ in 0..ms {
+        schedule.advance(1);
+        if schedule.due(config, frame) {
+            sent += 1;
+        }
+    }
+    sent
+}
+
+#[test]
+fn frames_follow_the_default_rates() {
+    let config = Config::default();
+    for &frame in StatusFrame::ALL.iter() {
+        let rate = config.status_rate(frame) as u32;
+        assert_eq!(count_due(&config, frame, 1000), rate, "{:?}", frame);
+    }
+    assert_eq!(config.status_rate(StatusFrame::Update), 20);
+}
+
+#[test]
+fn each_frame_has_its_own_rate() {
+    let config = Config {
+        update_rate: 200,
+        encoder_rate: 0,
+        faults_rate: 3,
+        ..Config::default()
+    };
+    assert_eq!(count_due(&config, StatusFrame::Update, 1000), 200);
+    assert_eq!(count_due(&config, StatusFrame::Encoder, 1000), 0);
+    assert_eq!(count_due(&config, StatusFrame::Faults, 1000), 3);
+    assert_eq!(count_due(&config, StatusFrame::BusVoltage, 1000), 20);
+}
+
+#[test]
+fn rates_that_do_not_divide_a_second_keep_their_average() {
+    for &rate in [300, 600, 700, 7].iter() {
+        let config = Config {
+            update_rate: rate,
+            ..Config::default()
+        };
+        assert_eq!(
+            count_due(&config, StatusFrame::Update, 1000),
+            rate as u32,
+            "{}",
+            rate
+        );
+    }
+}
+
+#[test]
+fn coarse_ticks_keep_the_average_rate() {
+    let config = Config {
+        update_rate: 200,
+        ..Config::default()
+    };
+    let mut schedule = StatusSchedule::new();
+    let mut sent = 0;
+    for _ in 0..500 {
+        schedule.advance(2);
+        if schedule.due(&config, StatusFrame::Update) {
+            sent += 1;
+        }
+    }
+    assert_eq!(sent, 200);
+}
+
+#[test]
+fn late_ticks_do_not_burst() {
+    let config = Config::default();
+    let mut schedule = StatusSchedule::new();
+    schedule.advance(1000);
+    assert!(schedule.due(&config, StatusFrame::Update));
+    assert!(!schedule.due(&config, StatusFrame::Update));
+    schedule.advance(1);
+    assert!(!schedule.due(&config, StatusFrame::Update));
+}
@@ -0,0 +1,86 @@
+use bmc_protocol::{Config, ThermalModel, AMBIENT_TEMPERATURE};
+
+/// Half buffer period of the firmware adc
+const DT: f32 = 0.0032;
+
+fn limited_config() -> Config {
+    Config {
+        motor_resistance: 0.1,
+        motor_thermal_resistance: 5.0,
+        motor_thermal_time: 10.0,
+        motor_temp_limit: 100.0,
+        ..Config::default()
+    }
+}
+
+fn run(model: &mut ThermalModel, config: &Config, current: f32, seconds: f32) {
+    for _ in 0..(seconds / DT) as usize {
+        model.update(config, current, DT);
+    }
+}
+
+#[test]
+fn settles_at_the_steady_state_temperature() {
+    let config = limited_config();
+    let mut model = ThermalModel::new();
+    assert_eq!(model.temperature(), AMBIENT_TEMPERATURE);
+
+    // 10 amps into 0.1 ohm is 10 watts, so 50 degrees over ambient
+    run(&mut model, &config, 10.0, 1.0);
+    let after_a_second = model.temperature();
+    assert!(
+        after_a_second > AMBIENT_TEMPERATURE + 4.0,
+        "{}",
+        after_a_second
+    );
+    assert!(
+        after_a_second < AMBIENT_TEMPERATURE + 6.0,
+        "{}",
+        after_a_second
+    );
+
+    run(&mut model, &config, 10.0, 100.0);
+    assert!((model.temperature() - (AMBIENT_TEMPERATURE + 50.0)).abs() < 0.1);
+
+    run(&mut model, &config, 0.0, 100.0);
+    assert!((model.temperature() - AMBIENT_TEMPERATURE).abs() < 0.1);
+}
+
+#[test]
+fn derates_close_to_the_limit() {
+    let config = limited_config();
+    let mut model = ThermalModel::new();
+    assert_eq!(model.derating(&config), 1.0);
+    assert_eq!(model.limit(&config, Some(30.0)), Some(30.0));
+    assert_eq!(model.limit(&config, None), None);
+
+    // 20 amps settles at 225 degrees, way past the limit
+    run(&mut model, &config, 20.0, 100.0);
+    assert_eq!(model.derating(&config), 0.0);
+    assert_eq!(model.limit(&config, Some(30.0)), Some(0.0));
+
+    // cool down into the derating band
+    let mut last = 0.0;
+    while model.derating(&config) < 0.5 {
+        model.update(&config, 0.0, DT);
+        let derating = model.derating(&config);
+        assert!(derating >= last);
+        last = derating;
+    }
+    let limit = model.limit(&config, None).unwrap();
+    let chopper = config.current_limit as f32;
+    assert!(limit >= 0.5 * chopper && limit < 0.6 * chopper, "{}", limit);
+}
+
+#[test]
+fn no_limit_no_derating() {
+    let config = Config {
+        motor_temp_limit: 0.0,
+        ..limited_config()
+    };
+    let mut model = ThermalModel::new();
+    run(&mut model, &config, 50.0, 100.0);
+    assert!(model.temperature() > 1000.0);
+    assert_eq!(model.derating(&config), 1.0);
+    assert_eq!(model.limit(&config, None), None);
+}
@@ -0,0 +1,656 @@
+//! Simulated board.
+//!
+//! `receive` mirrors the acceptance filters and `handle_rx_frame`, and `update` mirrors
+//! `motor_update` in the firmware, so a simulated board reacts to commands the same way real hardware does.
+//! Keep them in sync when the firmware changes.
+
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use bmc_protocol::{
+    voltage_duty, CanBusState, CanFrame, CanLastError, Config, Fault, FaultAction, FaultEvent,
+    FaultLog, FaultManager, Id, IdleMode, IncomingFrame, OutgoingFrame, OutputState, Pid, PidGains,
+    PositionInnerLoop, Ramp, ResetCause, SmartCurrentLimit, StatusFrame, StatusSchedule,
+    ThermalModel, AMBIENT_TEMPERATURE,
+};
+
+use crate::plant::{Bridge, Motor, MotorParams};
+
+/// Motor update period of the firmware in seconds (500hz)
+pub const MOTOR_UPDATE_DT: f32 = 1.0 / 500.0;
+
+/// Any duty cycle below this is treated as zero, same as the firmware
+const DEFAULT_MOTOR_DEADBAND: i16 = (i16::MAX as f32 * 0.01) as i16;
+
+/// Weight of a new sample in the encoder velocity filter, same as the firmware
+const VELOCITY_FILTER_ALPHA: f32 = 0.1;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ControlMode {
+    DutyCycle,
+    Voltage,
+    Current,
+    Velocity,
+    Position,
+}
+
+pub struct Board {
+    id: u8,
+    motor: Motor,
+
+    /// Last heartbeat, see `Config::heartbeat_timeout`
+    last_heartbeat: Option<Instant>,
+
+    /// Config in ram, and the copy "in flash" that survives a simulated reset
+    config: Config,
+    saved_config: Config,
+
+    control_mode: ControlMode,
+    setpoint: i16,
+    leader_duty: i16,
+    last_leader_update: Option<Instant>,
+    voltage_setpoint: f32,
+    ramp: Ramp,
+    smart_current_limit: SmartCurrentLimit,
+    thermal: ThermalModel,
+    current_setpoint: f32,
+    velocity_setpoint: f32,
+    position_setpoint: f32,
+    current_pid: Pid,
+    velocity_pid: Pid,
+    position_pid: Pid,
+
+    last_count: u16,
+    encoder_position: i32,
+    encoder_velocity: f32,
+
+    applied_duty: i16,
+    output_state: OutputState,
+    current_now: f32,
+    position_now: f32,
+    velocity_now: f32,
+
+    faults: FaultManager,
+
+    /// Fault history, which survives a simulated reset like the no-init ram it mirrors
+    fault_log: FaultLog,
+    booted: Instant,
+
+    /// When each status frame is due
+    status: StatusSchedule,
+
+    /// Frames waiting to be sent
+    tx: Vec<OutgoingFrame>,
+}
+
+impl Board {
+    pub fn new(id: u8, params: MotorParams) -> Self {
+        let motor = Motor::new(params);
+        let last_count = motor.encoder_count();
+        let mut board = Self {
+            id,
+            motor,
+            last_heartbeat: None,
+            config: Config::default(),
+            saved_config: Config::default(),
+            control_mode: ControlMode::DutyCycle,
+            setpoint: 0,
+            leader_duty: 0,
+            last_leader_update: None,
+            voltage_setpoint: 0.0,
+            ramp: Ramp::new(),
+            smart_current_limit: SmartCurrentLimit::new(),
+            thermal: ThermalModel::new(),
+            current_setpoint: 0.0,
+            velocity_setpoint: 0.0,
+            position_setpoint: 0.0,
+            current_pid: Pid::new(0.0, 1.0),
+            velocity_pid: Pid::new(-1.0, 1.0),
+            position_pid: Pid::new(0.0, 0.0),
+            last_count,
+            encoder_position: 0,
+            encoder_velocity: 0.0,
+            applied_duty: 0,
+            output_state: OutputState::Coast,
+            current_now: 0.0,
+            position_now: 0.0,
+            velocity_now: 0.0,
+            faults: FaultManager::new(),
+            fault_log: {
+                let mut log = FaultLog::new();
+                log.recover();
+                log
+            },
+            booted: Instant::now(),
+            status: StatusSchedule::new(),
+            tx: Vec::new(),
+        };
+        board.announce(ResetCause::PowerOn);
+        board
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    fn log(&self, msg: std::fmt::Arguments) {
+        eprintln!("[{:#04x}] {}", self.id, msg);
+    }
+
+    /// Simulates a reset. Everything but the saved config and the motor itself is lost
+    fn reset(&mut self) {
+        let mut fresh = Board::new(self.id, MotorParams::default());
+        std::mem::swap(&mut fresh.motor, &mut self.motor);
+        fresh.last_count = fresh.motor.encoder_count();
+        fresh.config = self.saved_config;
+        fresh.saved_config = self.saved_config;
+        fresh.fault_log = self.fault_log;
+        fresh.fault_log.recover();
+        fresh.tx.clear();
+        fresh.announce(ResetCause::Software);
+        *self = fresh;
+    }
+
+    /// Queues the frame a board sends after every reset
+    fn announce(&mut self, reset_cause: ResetCause) {
+        self.tx.push(OutgoingFrame::Boot {
+            reset_cause,
+            boot: self.fault_log.boot(),
+        });
+    }
+
+    /// Whether a frame passes the acceptance filters of the firmware:
+    /// frames for our id, broadcast frames, every group setpoint and every board's updates
+    fn accepts(&self, id: Id) -> bool {
+        let raw = match id {
+            Id::Extended(raw) => raw,
+            Id::Standard(_) => return false,
+        };
+        let device = raw & bmc_protocol::DEVICE_MASK;
+        device == bmc_protocol::IdFields::new(0, 0, self.id).raw()
+            || device == bmc_protocol::IdFields::new(0, 0, bmc_protocol::BROADCAST).raw()
+            || raw & bmc_protocol::API_MASK
+                == bmc_protocol::IdFields::new(
+                    bmc_protocol::api::CLASS_CONTROL,
+                    bmc_protocol::api::GROUP_SETPOINT,
+                    0,
+                )
+                .raw()
+            || raw & bmc_protocol::API_MASK
+                == bmc_protocol::IdFields::new(
+                    bmc_protocol::api::CLASS_STATUS,
+                    bmc_protocol::api::UPDATE,
+                    0,
+                )
+                .raw()
+    }
+
+    /// Handles a frame from the bus, if it passes our filters
+    pub fn receive(&mut self, frame: CanFrame, now: Instant) {
+        if !self.accepts(frame.id()) {
+            return;
+        }
+        let from = bmc_protocol::board_id(frame.id());
+        match IncomingFrame::try_from(frame) {
+            Ok(incoming) => self.handle(incoming, from, now),
+            Err(e) => match OutgoingFrame::try_from(frame) {
+                Ok(OutgoingFrame::Update { duty_now, .. }) => {
+                    if self.config.leader() == Some(from) {
+                        self.leader_duty = duty_now;
+                        self.last_leader_update = Some(now);
+                    }
+                }
+                // the firmware logs a warning and drops these too
+                _ => self.log(format_args!("Could not decode frame: {:?}", e)),
+            },
+        }
+    }
+
+    /// Handles a frame sent to device number `to`, like `handle_rx_frame`
+    fn handle(&mut self, frame: IncomingFrame, to: u8, now: Instant) {
+        use IncomingFrame::*;
+
+        match frame {
+            Setpoint(setpoint) => {
+                self.log(format_args!("Setting setpoint to {}", setpoint));
+                self.setpoint = setpoint;
+                self.control_mode = ControlMode::DutyCycle;
+            }
+            HeartBeat => self.last_heartbeat = Some(now),
+            GroupSetpoint(setpoints) => {
+                if let Some(setpoint) = self.config.group().and_then(|g| g.pick(to, &setpoints)) {
+                    self.setpoint = setpoint;
+                    self.control_mode = ControlMode::DutyCycle;
+                }
+            }
+            SetParam(id, value) => {
+                self.log(format_args!("Setting {:?} to {:?}", id, value));
+                if let Err(e) = self.config.set(id, value) {
+                    self.log(format_args!("Rejected {:?}: {:?}", id, e));
+                }
+                self.tx
+                    .push(OutgoingFrame::ParamValue(id, self.config.get(id)));
+            }
+            GetParam(id) => {
+                self.tx
+                    .push(OutgoingFrame::ParamValue(id, self.config.get(id)));
+            }
+            Stop => {
+                self.log(format_args!("Stopping motor (setpoint = 0)"));
+                self.setpoint = 0;
+                self.control_mode = ControlMode::DutyCycle;
+            }
+            SetCurrentSetpoint(amps) => {
+                self.log(format_args!("Setting current setpoint to {} amps", amps));
+                self.current_setpoint = amps;
+                self.control_mode = ControlMode::Current;
+            }
+            SetVelocitySetpoint(rpm) => {
+                self.log(format_args!("Setting velocity setpoint to {} rpm", rpm));
+                self.velocity_setpoint = rpm;
+                self.control_mode = ControlMode::Velocity;
+            }
+            SetVoltageSetpoint(volts) => {
+                self.log(format_args!("Setting voltage setpoint to {} volts", volts));
+                self.voltage_setpoint = volts;
+                self.control_mode = ControlMode::Voltage;
+            }
+            SetPositionSetpoint(rotations) => {
+                self.log(format_args!(
+                    "Setting position setpoint to {} rotations",
+                    rotations
+                ));
+                self.position_setpoint = rotations;
+                self.control_mode = ControlMode::Position;
+            }
+            SaveConfig => {
+                if self.applied_duty != 0 {
+                    self.log(format_args!(
+                        "Refusing to save config while the motor is running"
+                    ));
+                } else {
+                    self.log(format_args!("Saving config"));
+                    self.saved_config = self.config;
+                }
+            }
+            EnterBootloader => {
+                if self.applied_duty != 0 {
+                    self.log(format_args!(
+                        "Refusing to enter the bootloader while the motor is running"
+                    ));
+                } else {
+                    // there is no simulated bootloader, so this is a plain reset
+                    self.log(format_args!("Entering bootloader (resetting)"));
+                    self.reset();
+                }
+            }
+            ClearFaults => {
+                self.faults.clear();
+                self.log(format_args!(
+                    "Clearing faults, still active: {:?}",
+                    self.faults.active()
+                ));
+                self.push_faults();
+            }
+            GetFaultLog(index) => {
+                self.tx.push(OutgoingFrame::FaultLogEntry {
+                    index,
+                    record: self.fault_log.get(index as usize),
+                });
+            }
+            ClearFaultLog => {
+                self.log(format_args!("Clearing fault log"));
+                self.fault_log.clear();
+            }
+            QueryVersion => {
+                self.tx.push(OutgoingFrame::Version {
+                    protocol: bmc_protocol::PROTOCOL_VERSION,
+                    firmware: firmware_version(),
+                });
+            }
+            RestoreDefaults => {
+                if self.applied_duty != 0 {
+                    self.log(format_args!(
+                        "Refusing to restore defaults while the motor is running"
+                    ));
+                } else {
+                    self.log(format_args!("Restoring default config"));
+                    self.config = Config::default();
+                    self.saved_config = self.config;
+                }
+            }
+        }
+    }
+
+    /// Runs one motor update and steps the motor model, like `motor_update`
+    pub fn update(&mut self, now: Instant) {
+        let config = self.config;
+        let inverted = config.inverted;
+        let current_limit = config.current_limit;
+        let current_now = self.current_now;
+        let inner_loop = config.position_inner_loop;
+
+        // encoder, the same way `Encoder::update` reads the timer
+        let count = self.motor.encoder_count();
+        let delta = count.wrapping_sub(self.last_count) as i16;
+        self.last_count = count;
+        self.encoder_position = self.encoder_position.wrapping_add(delta as i32);
+        let raw_velocity = delta as f32 / MOTOR_UPDATE_DT;
+        self.encoder_velocity += VELOCITY_FILTER_ALPHA * (raw_velocity - self.encoder_velocity);
+
+        let sensor_sign = if inverted { -1.0 } else { 1.0 };
+        let cpr = config.encoder_cpr as f32;
+        let position_now = sensor_sign * self.encoder_position as f32 / cpr;
+        let velocity_now = sensor_sign * self.encoder_velocity * 60.0 / cpr;
+        self.position_now = position_now;
+        self.velocity_now = velocity_now;
+
+        // the simulated driver never faults, but it does chop at the current limit.
+        // The firmware feeds the thermal model from the adc, here every update gets one sample
+        self.thermal.update(&config, current_now, MOTOR_UPDATE_DT);
+        if self
+            .faults
+            .set_active(Fault::Overheat, self.thermal.derating(&config) < 1.0)
+        {
+            self.log(format_args!("Motor overheating, derating current"));
+            self.log_fault(FaultEvent::Overheat, now);
+            self.push_faults();
+        }
+
+        let following = config.leader().is_some();
+        if !following {
+            self.last_leader_update = None;
+        }
+        let leader_timed_out = self
+            .last_leader_update
+            .map(|last| {
+                now.duration_since(last) > Duration::from_millis(config.leader_timeout as u64)
+            })
+            .unwrap_or(false);
+        let leader_lost = following && (self.last_leader_update.is_none() || leader_timed_out);
+        if self
+            .faults
+            .set_active(Fault::LeaderLoss, following && leader_timed_out)
+        {
+            self.log(format_args!("Leader lost"));
+            self.log_fault(FaultEvent::LeaderLoss, now);
+            self.push_faults();
+        }
+        let fault_action = self.faults.action(&config);
+        let inhibited = fault_action != FaultAction::Warn;
+
+        let (current_active, velocity_active) = match self.control_mode {
+            _ if inhibited || following => (false, false),
+            ControlMode::DutyCycle | ControlMode::Voltage => (false, false),
+            ControlMode::Current => (true, false),
+            ControlMode::Velocity => (false, true),
+            ControlMode::Position => (
+                inner_loop == PositionInnerLoop::Current,
+                inner_loop == PositionInnerLoop::Velocity,
+            ),
+        };
+        if !current_active {
+            self.current_pid.reset();
+        }
+        if !velocity_active {
+            self.velocity_pid.reset();
+        }
+        if inhibited || following || self.control_mode != ControlMode::Position {
+            self.position_pid.reset();
+        }
+
+        let setpoint = match self.control_mode {
+            _ if inhibited || leader_lost => 0,
+            _ if following => self.leader_duty,
+            ControlMode::DutyCycle => self.ramp.update(
+                self.setpoint,
+                config.ramp_up,
+                config.ramp_down,
+                MOTOR_UPDATE_DT,
+            ),
+            ControlMode::Voltage => self.ramp.update(
+                voltage_duty(self.voltage_setpoint, self.motor.bus_voltage()),
+                config.ramp_up,
+                config.ramp_down,
+                MOTOR_UPDATE_DT,
+            ),
+            ControlMode::Current => current_loop(
+                &mut self.current_pid,
+                &config.current_gains,
+                self.current_setpoint,
+                current_now,
+                current_limit,
+            ),
+            ControlMode::Velocity => velocity_loop(
+                &mut self.velocity_pid,
+                &config.velocity_gains,
+                self.velocity_setpoint,
+                velocity_now,
+            ),
+            ControlMode::Position => {
+                let limit = config.position_output_limit;
+                self.position_pid.set_output_limits(-limit, limit);
+
+                let error = self.position_setpoint - position_now;
+                let error = if error.abs() < config.position_tolerance {
+                    0.0
+                } else {
+                    error
+                };
+
+                let output =
+                    self.position_pid
+                        .update(&config.position_gains, error, MOTOR_UPDATE_DT);
+
+                match inner_loop {
+                    PositionInnerLoop::Velocity => velocity_loop(
+                        &mut self.velocity_pid,
+                        &config.velocity_gains,
+                        output,
+                        velocity_now,
+                    ),
+                    PositionInnerLoop::Current => current_loop(
+                        &mut self.current_pid,
+                        &config.current_gains,
+                        output,
+                        current_now,
+                        current_limit,
+                    ),
+                }
+            }
+        };
+        let open_loop = !following
+            && matches!(
+                self.control_mode,
+                ControlMode::DutyCycle | ControlMode::Voltage
+            );
+        if inhibited || !open_loop {
+            self.ramp.reset(setpoint);
+        }
+        let setpoint = if inhibited {
+            self.smart_current_limit.reset();
+            setpoint
+        } else {
+            let limit = self
+                .thermal
+                .limit(&config, SmartCurrentLimit::limit(&config, velocity_now));
+            self.smart_current_limit
+                .update(limit, setpoint, current_now, MOTOR_UPDATE_DT)
+        };
+        let setpoint = setpoint * (if inverted { -1 } else { 1 });
+
+        // followers need heartbeats too, so they stop even if the host only loses the leader.
+        // The motor stays stopped until the first heartbeat, but only losing one we had is a fault
+        let heartbeat_timeout = config.heartbeat_timeout();
+        let heartbeat_timed_out = match (heartbeat_timeout, self.last_heartbeat) {
+            (Some(timeout_ms), Some(last)) => {
+                now.duration_since(last) > Duration::from_millis(timeout_ms as u64)
+            }
+            _ => false,
+        };
+        let heartbeat_lost =
+            heartbeat_timeout.is_some() && (self.last_heartbeat.is_none() || heartbeat_timed_out);
+        if self
+            .faults
+            .set_active(Fault::HeartbeatLoss, heartbeat_timed_out)
+        {
+            self.log(format_args!("Heartbeat lost"));
+            self.log_fault(FaultEvent::HeartbeatLoss, now);
+            // like the firmware, a returning heartbeat does not restart the last command
+            self.setpoint = 0;
+            self.current_setpoint = 0.0;
+            self.velocity_setpoint = 0.0;
+            self.voltage_setpoint = 0.0;
+            self.position_setpoint = position_now;
+            self.push_faults();
+        }
+        if heartbeat_lost {
+            self.ramp.reset(0);
+        }
+        let stop = heartbeat_lost || setpoint.abs() < DEFAULT_MOTOR_DEADBAND;
+
+        // a sleeping driver leaves the motor coasting, same as `FaultAction::Coast`
+        let idle_mode = match fault_action {
+            FaultAction::Warn => config.idle_mode,
+            FaultAction::Coast | FaultAction::Disable => IdleMode::Coast,
+            FaultAction::Brake => IdleMode::Brake,
+        };
+        let bridge = if !stop {
+            Bridge::Drive(setpoint as f32 / i16::MAX as f32)
+        } else if idle_mode == IdleMode::Coast {
+            Bridge::Coast
+        } else {
+            Bridge::Brake
+        };
+        self.applied_duty = if stop { 0 } else { setpoint };
+        self.output_state = match bridge {
+            _ if fault_action == FaultAction::Disable => OutputState::Sleep,
+            Bridge::Drive(_) => OutputState::Drive,
+            Bridge::Coast => OutputState::Coast,
+            Bridge::Brake => OutputState::Brake,
+        };
+
+        self.motor
+            .step(bridge, current_limit as f32, MOTOR_UPDATE_DT);
+        self.current_now = self.motor.sensed_current();
+
+        // the driver flags overcurrent while the chopper is active,
+        // and `exti9_5` reports the first time since a clear
+        if self
+            .faults
+            .set_active(Fault::Overcurrent, self.motor.chopping())
+        {
+            self.log_fault(FaultEvent::Overcurrent, now);
+            self.tx.push(OutgoingFrame::Overcurrent {
+                current_now: self.current_now,
+                current_limit: current_limit as f32,
+            });
+            self.push_faults();
+        }
+    }
+
+    fn log_fault(&mut self, event: FaultEvent, now: Instant) {
+        let time_ms = now.duration_since(self.booted).as_millis() as u32;
+        self.fault_log.push(event, time_ms);
+    }
+
+    fn push_faults(&mut self) {
+        self.tx.push(OutgoingFrame::Faults {
+            active: self.faults.active(),
+            sticky: self.faults.sticky(),
+        });
+    }
+
+    /// Queues the status frames that are due `dt_ms` after the last call, like `send_update`
+    pub fn send_update(&mut self, dt_ms: u32) {
+        self.status.advance(dt_ms);
+        for &status in StatusFrame::ALL.iter() {
+            if !self.status.due(&self.config, status) {
+                continue;
+            }
+            let frame = match status {
+                // followers mirror this, so it is the output after a stop, not the setpoint
+                StatusFrame::Update => OutgoingFrame::Update {
+                    current_now: self.current_now,
+                    duty_now: self.applied_duty,
+                },
+                StatusFrame::Encoder => OutgoingFrame::Encoder {
+                    position: self.position_now,
+                    velocity: self.velocity_now,
+                },
+                StatusFrame::BusVoltage => OutgoingFrame::BusVoltage(self.motor.bus_voltage()),
+                StatusFrame::MotorTemperature if self.config.motor_temp_limit > 0.0 => {
+                    OutgoingFrame::MotorTemperature {
+                        temperature: self.thermal.temperature(),
+                        derating: self.thermal.derating(&self.config),
+                    }
+                }
+                StatusFrame::Faults if !self.faults.sticky().is_empty() => OutgoingFrame::Faults {
+                    active: self.faults.active(),
+                    sticky: self.faults.sticky(),
+                },
+                // the simulated chip stays at room temperature and never sees a bus error
+                StatusFrame::SystemStatus => OutgoingFrame::SystemStatus {
+                    active: self.faults.active(),
+                    sticky: self.faults.sticky(),
+                    mcu_temperature: AMBIENT_TEMPERATURE as i8,
+                    uptime: self.booted.elapsed().as_secs() as u32,
+                },
+                StatusFrame::CanStatus => OutgoingFrame::CanStatus {
+                    tx_errors: 0,
+                    rx_errors: 0,
+                    bus_off_count: 0,
+                    tx_queue_depth: self.tx.len() as u8,
+                    state: CanBusState::ErrorActive,
+                    last_error: CanLastError::None,
+                },
+                StatusFrame::Output => OutgoingFrame::Output {
+                    duty: self.applied_duty,
+                    state: self.output_state,
+                },
+                _ => continue,
+            };
+            self.tx.push(frame);
+        }
+    }
+
+    /// Takes every frame queued since the last call
+    pub fn take_tx(&mut self) -> Vec<OutgoingFrame> {
+        std::mem::take(&mut self.tx)
+    }
+}
+
+/// Simulated boards report the simulator version as their firmware version
+fn firmware_version() -> [u8; 3] {
+    [
+        env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+        env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+        env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0),
+    ]
+}
+
+/// Same as `current_loop` in the firmware
+fn current_loop(
+    pid: &mut Pid,
+    gains: &PidGains,
+    setpoint: f32,
+    current_now: f32,
+    current_limit: u8,
+) -> i16 {
+    let target = setpoint.abs().min(current_limit as f32);
+
+    let output = pid.update(gains, target - current_now, MOTOR_UPDATE_DT);
+    let duty = (output * i16::MAX as f32) as i16;
+
+    if setpoint < 0.0 {
+        -duty
+    } else {
+        duty
+    }
+}
+
+/// Same as `velocity_loop` in the firmware
+fn velocity_loop(pid: &mut Pid, gains: &PidGains, setpoint: f32, velocity_now: f32) -> i16 {
+    let output = pid.update(gains, setpoint - velocity_now, MOTOR_UPDATE_DT);
+    (output * i16::MAX as f32) as i16
+}
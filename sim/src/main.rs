@@ -0,0 +1,131 @@
+//! `bmc-sim`: simulates motor controller boards on a SocketCAN interface.
+//!
+//! Each simulated board answers commands and sends `Update`/`Encoder` frames
+//! like the firmware, driving a DC motor model instead of real hardware.
+//! Run it against a virtual bus (see the README) and point host code at it.
+
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use structopt::StructOpt;
+
+use bmc_protocol::{CanFrame, IntoWithId};
+use bmc_socketcan::CanSocket;
+
+mod board;
+mod plant;
+
+use board::{Board, MOTOR_UPDATE_DT};
+use plant::MotorParams;
+
+#[derive(StructOpt)]
+#[structopt(name = "bmc-sim", about = "Simulate motor controllers on a CAN bus")]
+struct Opt {
+    /// SocketCAN interface to use
+    #[structopt(short, long, default_value = "vcan0")]
+    interface: String,
+
+    /// Id of a board to simulate. Pass this once per board.
+    /// Accepts decimal or hex with a `0x` prefix
+    #[structopt(short, long = "board", required = true, parse(try_from_str = parse_board_id))]
+    boards: Vec<u8>,
+
+    /// Motor supply voltage in volts
+    #[structopt(long, default_value = "12.0")]
+    supply_voltage: f32,
+}
+
+fn parse_board_id(s: &str) -> Result<u8> {
+    let id = if let Some(hex) = s.strip_prefix("0x") {
+        u8::from_str_radix(hex, 16)
+    } else {
+        s.parse()
+    };
+    id.with_context(|| format!("invalid board id '{}'", s))
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::from_args();
+
+    let socket = CanSocket::open(&opt.interface)
+        .with_context(|| format!("could not open can interface '{}'", opt.interface))?;
+    let socket = Arc::new(socket);
+
+    let params = MotorParams {
+        supply_voltage: opt.supply_voltage,
+        ..MotorParams::default()
+    };
+    let mut boards: Vec<Board> = opt
+        .boards
+        .iter()
+        .map(|&id| Board::new(id, params))
+        .collect();
+
+    // reads block, so they get their own thread and the simulation never stalls
+    let (rx_send, rx_recv) = mpsc::channel::<CanFrame>();
+    let rx_socket = Arc::clone(&socket);
+    thread::spawn(move || loop {
+        match rx_socket.read() {
+            Ok(frame) => {
+                if rx_send.send(frame).is_err() {
+                    return;
+                }
+            }
+            Err(e) => eprintln!("Could not read frame: {}", e),
+        }
+    });
+
+    eprintln!("Simulating {} board(s) on {}", boards.len(), opt.interface);
+
+    let period = Duration::from_secs_f32(MOTOR_UPDATE_DT);
+    let period_ms = period.as_millis() as u32;
+    let mut next_tick = Instant::now();
+    loop {
+        let now = Instant::now();
+
+        for frame in rx_recv.try_iter() {
+            // broadcast and group frames go to more than one board
+            for board in boards.iter_mut() {
+                board.receive(frame, now);
+            }
+        }
+
+        for board in boards.iter_mut() {
+            board.update(now);
+        }
+
+        // status frames go out at the rates in each board's config,
+        // but no faster than the simulation steps
+        for board in boards.iter_mut() {
+            board.send_update(period_ms);
+        }
+
+        let mut sent = Vec::new();
+        for board in boards.iter_mut() {
+            let id = bmc_protocol::device_id(board.id());
+            for frame in board.take_tx() {
+                let frame: CanFrame = frame.into_with_id(id);
+                // a full tx queue just means nobody is listening, drop the frame like the board would
+                let _ = socket.write(&frame);
+                sent.push((board.id(), frame));
+            }
+        }
+        // the socket does not hand us back our own frames, so boards hear each other here,
+        // which followers need to see their leader. Like bxcan, a board never hears itself
+        for (from, frame) in sent {
+            for board in boards.iter_mut().filter(|b| b.id() != from) {
+                board.receive(frame, now);
+            }
+        }
+
+        next_tick += period;
+        match next_tick.checked_duration_since(Instant::now()) {
+            Some(wait) => thread::sleep(wait),
+            // we fell behind, so skip ahead instead of trying to catch up
+            None => next_tick = Instant::now(),
+        }
+    }
+}
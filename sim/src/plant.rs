@@ -0,0 +1,137 @@
+//! Brushed DC motor driven by an H-bridge with a current chopper.
+
+use std::f32::consts::PI;
+
+/// Time step of the electrical model in seconds.
+/// This has to be well below the electrical time constant (L / R)
+const SUBSTEP: f32 = 20e-6;
+
+/// Physical parameters of the motor and supply
+#[derive(Copy, Clone, Debug)]
+pub struct MotorParams {
+    /// Bridge supply voltage in volts
+    pub supply_voltage: f32,
+    /// Winding resistance in ohms
+    pub resistance: f32,
+    /// Winding inductance in henries
+    pub inductance: f32,
+    /// Torque constant in Nm/A, which is also the back emf constant in V/(rad/s)
+    pub torque_constant: f32,
+    /// Rotor and load inertia in kg m^2
+    pub inertia: f32,
+    /// Viscous friction in Nm/(rad/s)
+    pub damping: f32,
+    /// Counts per revolution of the encoder on the motor shaft
+    pub encoder_cpr: u16,
+}
+
+impl Default for MotorParams {
+    /// A small 12V motor: roughly 11000 rpm free speed and 12A stall current
+    fn default() -> Self {
+        Self {
+            supply_voltage: 12.0,
+            resistance: 1.0,
+            inductance: 0.5e-3,
+            torque_constant: 0.01,
+            inertia: 5e-6,
+            damping: 1e-6,
+            encoder_cpr: 2048,
+        }
+    }
+}
+
+/// What the H-bridge is doing
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bridge {
+    /// Driving with a duty cycle from -1.0 to 1.0
+    Drive(f32),
+    /// Both low sides on, the motor terminals are shorted
+    Brake,
+    /// Every switch off, current can only decay through the body diodes
+    Coast,
+}
+
+pub struct Motor {
+    params: MotorParams,
+    /// Winding current in amps
+    current: f32,
+    /// Shaft velocity in rad/s
+    velocity: f32,
+    /// Shaft angle in rad
+    angle: f32,
+    /// Whether the chopper limited the current during the last step
+    chopping: bool,
+}
+
+impl Motor {
+    pub fn new(params: MotorParams) -> Self {
+        Self {
+            params,
+            current: 0.0,
+            velocity: 0.0,
+            angle: 0.0,
+            chopping: false,
+        }
+    }
+
+    /// Advances the model by `dt` seconds.
+    /// The chopper switches the bridge to slow decay whenever the current
+    /// magnitude is above `current_limit`, like the motor driver does
+    pub fn step(&mut self, bridge: Bridge, current_limit: f32, dt: f32) {
+        let p = self.params;
+        let steps = (dt / SUBSTEP).ceil().max(1.0) as u32;
+        let h = dt / steps as f32;
+
+        self.chopping = false;
+        for _ in 0..steps {
+            let back_emf = p.torque_constant * self.velocity;
+
+            let voltage = match bridge {
+                Bridge::Drive(duty) => {
+                    let drive = duty.clamp(-1.0, 1.0) * p.supply_voltage;
+                    let pushing = (drive - back_emf) * self.current > 0.0;
+                    if self.current.abs() >= current_limit && pushing {
+                        self.chopping = true;
+                        0.0
+                    } else {
+                        drive
+                    }
+                }
+                Bridge::Brake => 0.0,
+                // the diodes conduct against the supply until the current dies out
+                Bridge::Coast if self.current != 0.0 => -self.current.signum() * p.supply_voltage,
+                Bridge::Coast => back_emf,
+            };
+
+            let last_current = self.current;
+            self.current += (voltage - p.resistance * self.current - back_emf) / p.inductance * h;
+            if bridge == Bridge::Coast && last_current * self.current <= 0.0 {
+                self.current = 0.0;
+            }
+
+            let torque = p.torque_constant * self.current - p.damping * self.velocity;
+            self.velocity += torque / p.inertia * h;
+            self.angle += self.velocity * h;
+        }
+    }
+
+    /// Current magnitude, which is all the board's current sensor can see
+    pub fn sensed_current(&self) -> f32 {
+        self.current.abs()
+    }
+
+    /// Raw 16 bit encoder count, like the timer in encoder mode reads
+    pub fn encoder_count(&self) -> u16 {
+        let counts = self.angle / (2.0 * PI) * self.params.encoder_cpr as f32;
+        (counts.floor() as i64) as u16
+    }
+
+    /// Supply voltage, which the model holds steady no matter the load
+    pub fn bus_voltage(&self) -> f32 {
+        self.params.supply_voltage
+    }
+
+    pub fn chopping(&self) -> bool {
+        self.chopping
+    }
+}
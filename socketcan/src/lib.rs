@@ -0,0 +1,178 @@
+//! Minimal raw SocketCAN socket shared by the host tools.
+//!
+//! We only need to send and receive classic frames, so this talks to the kernel
+//! directly instead of pulling in a SocketCAN crate.
+//! A socket can be shared between threads, reads and writes only need `&self`.
+
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+use bmc_protocol::{CanFrame, Id};
+
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+const CAN_SFF_MASK: u32 = 0x0000_07FF;
+const CAN_EFF_MASK: u32 = 0x1FFF_FFFF;
+
+/// `struct can_frame` from `linux/can.h`
+#[repr(C)]
+#[derive(Default)]
+struct RawFrame {
+    can_id: u32,
+    can_dlc: u8,
+    _pad: u8,
+    _res0: u8,
+    _res1: u8,
+    data: [u8; 8],
+}
+
+/// `struct sockaddr_can` from `linux/can.h`
+#[repr(C)]
+struct SockAddrCan {
+    can_family: libc::sa_family_t,
+    can_ifindex: libc::c_int,
+    // union of the transport protocol addresses, unused for raw sockets
+    can_addr: [u32; 2],
+}
+
+/// A raw can socket bound to one interface
+pub struct CanSocket {
+    fd: RawFd,
+}
+
+impl CanSocket {
+    /// Opens a raw socket on the interface called `interface`, e.g. `can0` or `vcan0`
+    pub fn open(interface: &str) -> io::Result<CanSocket> {
+        let name = CString::new(interface)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let fd = unsafe { libc::socket(libc::PF_CAN, libc::SOCK_RAW, libc::CAN_RAW) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // from here on the fd is closed on drop, including on errors
+        let socket = CanSocket { fd };
+
+        let addr = SockAddrCan {
+            can_family: libc::AF_CAN as libc::sa_family_t,
+            can_ifindex: ifindex as libc::c_int,
+            can_addr: [0; 2],
+        };
+        let res = unsafe {
+            libc::bind(
+                socket.fd,
+                &addr as *const SockAddrCan as *const libc::sockaddr,
+                mem::size_of::<SockAddrCan>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(socket)
+    }
+
+    /// Sets how long `read` waits for a frame. `None` blocks forever
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        let timeout = timeout.unwrap_or_default();
+        let tv = libc::timeval {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+        };
+        let res = unsafe {
+            libc::setsockopt(
+                self.fd,
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &tv as *const libc::timeval as *const libc::c_void,
+                mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn write(&self, frame: &CanFrame) -> io::Result<()> {
+        let mut can_id = match frame.id() {
+            Id::Standard(id) => id as u32 & CAN_SFF_MASK,
+            Id::Extended(id) => (id & CAN_EFF_MASK) | CAN_EFF_FLAG,
+        };
+        if frame.is_remote_frame() {
+            can_id |= CAN_RTR_FLAG;
+        }
+        let mut raw = RawFrame {
+            can_id,
+            can_dlc: frame.dlc(),
+            ..RawFrame::default()
+        };
+        raw.data[..frame.data().len()].copy_from_slice(frame.data());
+
+        let size = mem::size_of::<RawFrame>();
+        let written = unsafe {
+            libc::write(
+                self.fd,
+                &raw as *const RawFrame as *const libc::c_void,
+                size,
+            )
+        };
+        if written < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if written as usize != size {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "short write"));
+        }
+        Ok(())
+    }
+
+    /// Blocks until the next frame arrives. Error frames are skipped.
+    /// If a read timeout is set and expires, this returns an error of kind `WouldBlock`
+    pub fn read(&self) -> io::Result<CanFrame> {
+        loop {
+            let mut raw = RawFrame::default();
+            let size = mem::size_of::<RawFrame>();
+            let read = unsafe {
+                libc::read(
+                    self.fd,
+                    &mut raw as *mut RawFrame as *mut libc::c_void,
+                    size,
+                )
+            };
+            if read < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if read as usize != size || raw.can_id & CAN_ERR_FLAG != 0 {
+                continue;
+            }
+
+            let id = if raw.can_id & CAN_EFF_FLAG != 0 {
+                Id::Extended(raw.can_id & CAN_EFF_MASK)
+            } else {
+                Id::Standard((raw.can_id & CAN_SFF_MASK) as u16)
+            };
+            let dlc = raw.can_dlc.min(8);
+            let frame = if raw.can_id & CAN_RTR_FLAG != 0 {
+                CanFrame::new_remote(id, dlc)
+            } else {
+                CanFrame::new_data(id, &raw.data[..dlc as usize])
+            };
+            // dlc was clamped above, so this always succeeds
+            return Ok(frame.unwrap());
+        }
+    }
+}
+
+impl Drop for CanSocket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}